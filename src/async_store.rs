@@ -0,0 +1,706 @@
+use crate::audit::CommitOptions;
+use crate::history::{DocumentRevision, PathChange, Revision, Snapshot};
+use crate::json_document::Document;
+use crate::detached::Detached;
+use crate::git_args::args;
+use crate::history::{parse_log, LOG_FORMAT};
+use crate::json_document::{map_from_str, map_into_string};
+use crate::repo_store::{tmp_path, CleanMode, GitStore, RepoStore, RepoStoreError, SyncPolicy};
+use serde_json::{Map, Value};
+use std::path::{Component, Path};
+use std::sync::Arc;
+
+/// Async wrapper of a [RepoStore] running its blocking calls on the blocking thread pool of a tokio runtime,
+/// available with the `async` feature. Each call holds a thread of the blocking pool with `spawn_blocking`
+/// until the git processes the store spawns exit and its file I/O completes, so the calls running at once
+/// are bounded by the size of the blocking pool. [AsyncGitStore] reads and writes the documents of a [GitStore]
+/// with async I/O instead.
+///
+/// Dropping a future before it completes does not interrupt the operation: it runs to its end in the background,
+/// so a cancelled call never leaves a write half applied, only its outcome being lost.
+/// The errors wrapped by the returned [RepoStoreError] are reduced to their messages.
+pub struct BlockingPoolStore<S: RepoStore<Map<String, Value>> + Send + Sync + 'static> {
+    store: Arc<S>,
+}
+
+impl<S: RepoStore<Map<String, Value>> + Send + Sync + 'static> Clone for BlockingPoolStore<S> {
+    fn clone(&self) -> Self {
+        Self { store: self.store.clone() }
+    }
+}
+
+impl<S: RepoStore<Map<String, Value>> + Send + Sync + 'static> BlockingPoolStore<S> {
+    pub fn new(store: S) -> Self {
+        Self { store: Arc::new(store) }
+    }
+
+    /// The wrapped store, whose calls block the current thread
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Runs `f` on the blocking thread pool, to its end even when the returned future is dropped
+    pub async fn run<R, F>(&self, f: F) -> Result<R, RepoStoreError>
+    where
+        R: Send + 'static,
+        F: FnOnce(&S) -> Result<R, RepoStoreError> + Send + 'static,
+    {
+        let store = self.store.clone();
+        match tokio::task::spawn_blocking(move || f(&store).map_err(Detached::detach)).await {
+            Ok(result) => result.map_err(Detached::attach),
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            // the runtime shut down before the operation started
+            Err(e) => Err(RepoStoreError::Interrupted(Box::new(e))),
+        }
+    }
+
+    pub async fn initialize(&self) -> Result<(), RepoStoreError> {
+        self.run(|store| store.initialize()).await
+    }
+
+    pub async fn document(&self, path: &str) -> Result<Document<Map<String, Value>>, RepoStoreError> {
+        let path = String::from(path);
+        self.run(move |store| store.document(&path)).await
+    }
+
+    /// Writes `document`, returning it with its new revision
+    pub async fn write_document(&self, path: &str, mut document: Document<Map<String, Value>>) -> Result<Document<Map<String, Value>>, RepoStoreError> {
+        let path = String::from(path);
+        self.run(move |store| store.write_document(&path, &mut document).map(|_| document)).await
+    }
+
+    pub async fn remove_document(&self, path: &str) -> Result<(), RepoStoreError> {
+        let path = String::from(path);
+        self.run(move |store| store.remove_document(&path)).await
+    }
+
+    /// Writes `document` only if the stored revision is still `expected`, see [RepoStore::write_document_if]
+    pub async fn write_document_if(&self, path: &str, mut document: Document<Map<String, Value>>, expected: Option<&str>) -> Result<Document<Map<String, Value>>, RepoStoreError> {
+        let path = String::from(path);
+        let expected = expected.map(String::from);
+        self.run(move |store| store.write_document_if(&path, &mut document, expected.as_deref()).map(|_| document)).await
+    }
+
+    pub async fn remove_document_if(&self, path: &str, expected: &str) -> Result<(), RepoStoreError> {
+        let (path, expected) = (String::from(path), String::from(expected));
+        self.run(move |store| store.remove_document_if(&path, &expected)).await
+    }
+
+    pub async fn revision(&self, path: &str) -> Result<Option<String>, RepoStoreError> {
+        let path = String::from(path);
+        self.run(move |store| store.revision(&path)).await
+    }
+
+    pub async fn documents(&self, dir: &str) -> Result<Vec<String>, RepoStoreError> {
+        let dir = String::from(dir);
+        self.run(move |store| store.documents(&dir)).await
+    }
+
+    pub async fn create_dir(&self, dir: &str) -> Result<(), RepoStoreError> {
+        let dir = String::from(dir);
+        self.run(move |store| store.create_dir(&dir)).await
+    }
+
+    pub async fn remove_dir(&self, dir: &str) -> Result<(), RepoStoreError> {
+        let dir = String::from(dir);
+        self.run(move |store| store.remove_dir(&dir)).await
+    }
+
+    pub async fn document_at(&self, path: &str, revision: &Revision) -> Result<Document<Map<String, Value>>, RepoStoreError> {
+        let (path, revision) = (String::from(path), revision.clone());
+        self.run(move |store| store.document_at(&path, &revision)).await
+    }
+
+    pub async fn revision_at(&self, path: &str, revision: &Revision) -> Result<Option<String>, RepoStoreError> {
+        let (path, revision) = (String::from(path), revision.clone());
+        self.run(move |store| store.revision_at(&path, &revision)).await
+    }
+
+    pub async fn revisions(&self, path: &str) -> Result<Vec<DocumentRevision>, RepoStoreError> {
+        let path = String::from(path);
+        self.run(move |store| store.revisions(&path)).await
+    }
+
+    pub async fn resolve(&self, revision: &Revision) -> Result<String, RepoStoreError> {
+        let revision = revision.clone();
+        self.run(move |store| store.resolve(&revision)).await
+    }
+
+    pub async fn commits(&self, from: Option<&Revision>, to: &Revision) -> Result<Vec<DocumentRevision>, RepoStoreError> {
+        let (from, to) = (from.cloned(), to.clone());
+        self.run(move |store| store.commits(from.as_ref(), &to)).await
+    }
+
+    pub async fn document_commits(&self, path: &str, to: &Revision) -> Result<Vec<DocumentRevision>, RepoStoreError> {
+        let (path, to) = (String::from(path), to.clone());
+        self.run(move |store| store.document_commits(&path, &to)).await
+    }
+
+    pub async fn pull(&self, rebase: bool) -> Result<(), RepoStoreError> {
+        self.run(move |store| store.pull(rebase)).await
+    }
+
+    pub async fn push(&self) -> Result<(), RepoStoreError> {
+        self.run(|store| store.push()).await
+    }
+
+    /// Pushes the local commits, see [RepoStore::sync]
+    pub async fn sync(&self, policy: &SyncPolicy) -> Result<(), RepoStoreError> {
+        let policy = *policy;
+        self.run(move |store| store.sync(&policy)).await
+    }
+
+    pub async fn commit(&self, msg: &str) -> Result<(), RepoStoreError> {
+        let msg = String::from(msg);
+        self.run(move |store| store.commit(&msg)).await
+    }
+
+    pub async fn commit_documents(&self, msg: &str, paths: &[String]) -> Result<(), RepoStoreError> {
+        let (msg, paths) = (String::from(msg), paths.to_vec());
+        self.run(move |store| store.commit_documents(&msg, &paths)).await
+    }
+
+    /// Commits with the given options, see [RepoStore::commit_with]
+    pub async fn commit_with(&self, msg: &str, paths: Option<&[String]>, options: &CommitOptions) -> Result<(), RepoStoreError> {
+        let (msg, paths, options) = (String::from(msg), paths.map(<[String]>::to_vec), options.clone());
+        self.run(move |store| store.commit_with(&msg, paths.as_deref(), &options)).await
+    }
+
+    pub async fn restore_documents(&self, paths: &[String]) -> Result<(), RepoStoreError> {
+        let paths = paths.to_vec();
+        self.run(move |store| store.restore_documents(&paths)).await
+    }
+
+    pub async fn clean(&self, mode: CleanMode) -> Result<Vec<String>, RepoStoreError> {
+        self.run(move |store| store.clean(mode)).await
+    }
+
+    pub async fn current_branch(&self) -> Result<String, RepoStoreError> {
+        self.run(|store| store.current_branch()).await
+    }
+
+    pub async fn branches(&self) -> Result<Vec<String>, RepoStoreError> {
+        self.run(|store| store.branches()).await
+    }
+
+    pub async fn create_branch(&self, name: &str, from: &Revision) -> Result<(), RepoStoreError> {
+        let (name, from) = (String::from(name), from.clone());
+        self.run(move |store| store.create_branch(&name, &from)).await
+    }
+
+    pub async fn switch_branch(&self, name: &str) -> Result<(), RepoStoreError> {
+        let name = String::from(name);
+        self.run(move |store| store.switch_branch(&name)).await
+    }
+
+    pub async fn delete_branch(&self, name: &str) -> Result<(), RepoStoreError> {
+        let name = String::from(name);
+        self.run(move |store| store.delete_branch(&name)).await
+    }
+
+    pub async fn merge_branch(&self, name: &str) -> Result<(), RepoStoreError> {
+        let name = String::from(name);
+        self.run(move |store| store.merge_branch(&name)).await
+    }
+
+    pub async fn create_snapshot(&self, name: &str, msg: &str) -> Result<(), RepoStoreError> {
+        let (name, msg) = (String::from(name), String::from(msg));
+        self.run(move |store| store.create_snapshot(&name, &msg)).await
+    }
+
+    pub async fn snapshots(&self) -> Result<Vec<Snapshot>, RepoStoreError> {
+        self.run(|store| store.snapshots()).await
+    }
+
+    pub async fn restore_snapshot(&self, name: &str, msg: &str) -> Result<(), RepoStoreError> {
+        let (name, msg) = (String::from(name), String::from(msg));
+        self.run(move |store| store.restore_snapshot(&name, &msg)).await
+    }
+
+    pub async fn paths_at(&self, revision: &Revision) -> Result<Vec<String>, RepoStoreError> {
+        let revision = revision.clone();
+        self.run(move |store| store.paths_at(&revision)).await
+    }
+
+    pub async fn changed_paths(&self, from: Option<&Revision>, to: &Revision) -> Result<Vec<PathChange>, RepoStoreError> {
+        let (from, to) = (from.cloned(), to.clone());
+        self.run(move |store| store.changed_paths(from.as_ref(), &to)).await
+    }
+
+    pub async fn head(&self) -> Result<Option<String>, RepoStoreError> {
+        self.run(|store| store.head()).await
+    }
+}
+
+/// Async version of a [GitStore], available with the `async` feature. Documents are read and written with `tokio::fs`,
+/// and the git commands reading them and their history run as child processes of tokio that no thread waits on.
+/// The lock serializing the changes to the repository is awaited the same way.
+/// The operations going through the [GitBackend](crate::backend::GitBackend) of the store, which may work in-process,
+/// and those integrating or rewriting changes (initialization, commits, pulls, pushes, branches, snapshots and cleaning)
+/// run on the blocking pool as with [BlockingPoolStore].
+///
+/// Every call runs as a task of its own: dropping a future before it completes does not interrupt the operation,
+/// which runs to its end in the background, so a cancelled call never leaves a write half applied.
+/// The errors wrapped by the returned [RepoStoreError] are reduced to their messages.
+#[derive(Clone)]
+pub struct AsyncGitStore {
+    pool: BlockingPoolStore<GitStore>,
+}
+
+impl AsyncGitStore {
+    pub fn new(store: GitStore) -> Self {
+        Self { pool: BlockingPoolStore::new(store) }
+    }
+
+    /// The wrapped store, whose calls block the current thread
+    pub fn store(&self) -> &GitStore {
+        self.pool.store()
+    }
+
+    /// Runs the operation `f` returns as a task, to its end even when the returned future is dropped
+    async fn spawn<R, F, T>(&self, f: F) -> Result<R, RepoStoreError>
+    where
+        R: Send + 'static,
+        F: FnOnce(Arc<GitStore>) -> T,
+        T: Future<Output = Result<R, RepoStoreError>> + Send + 'static,
+    {
+        let task = f(self.pool.store.clone());
+        match tokio::spawn(async move { task.await.map_err(Detached::detach) }).await {
+            Ok(result) => result.map_err(Detached::attach),
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            // the runtime shut down before the operation completed
+            Err(e) => Err(RepoStoreError::Interrupted(Box::new(e))),
+        }
+    }
+
+    pub async fn initialize(&self) -> Result<(), RepoStoreError> {
+        self.pool.initialize().await
+    }
+
+    pub async fn document(&self, path: &str) -> Result<Document<Map<String, Value>>, RepoStoreError> {
+        let path = String::from(path);
+        self.spawn(move |store| async move { document(&store, &path).await }).await
+    }
+
+    /// Writes `document`, returning it with its new revision
+    pub async fn write_document(&self, path: &str, mut document: Document<Map<String, Value>>) -> Result<Document<Map<String, Value>>, RepoStoreError> {
+        let path = String::from(path);
+        self.spawn(move |store| async move {
+            let _lock = store.lock_async().await?;
+            write_document(&store, &path, &mut document).await.map(|_| document)
+        }).await
+    }
+
+    pub async fn remove_document(&self, path: &str) -> Result<(), RepoStoreError> {
+        let path = String::from(path);
+        self.spawn(move |store| async move {
+            let _lock = store.lock_async().await?;
+            remove_document(&store, &path).await
+        }).await
+    }
+
+    /// Writes `document` only if the stored revision is still `expected`, see [RepoStore::write_document_if]
+    pub async fn write_document_if(&self, path: &str, mut document: Document<Map<String, Value>>, expected: Option<&str>) -> Result<Document<Map<String, Value>>, RepoStoreError> {
+        let path = String::from(path);
+        let expected = expected.map(String::from);
+        self.spawn(move |store| async move {
+            let _lock = store.lock_async().await?;
+            check_revision(&store, &path, expected.as_deref()).await?;
+            write_document(&store, &path, &mut document).await.map(|_| document)
+        }).await
+    }
+
+    pub async fn remove_document_if(&self, path: &str, expected: &str) -> Result<(), RepoStoreError> {
+        let (path, expected) = (String::from(path), String::from(expected));
+        self.spawn(move |store| async move {
+            let _lock = store.lock_async().await?;
+            check_revision(&store, &path, Some(&expected)).await?;
+            remove_document(&store, &path).await
+        }).await
+    }
+
+    pub async fn revision(&self, path: &str) -> Result<Option<String>, RepoStoreError> {
+        let path = String::from(path);
+        self.spawn(move |store| async move { revision(&store, &path).await }).await
+    }
+
+    pub async fn documents(&self, dir: &str) -> Result<Vec<String>, RepoStoreError> {
+        let dir = String::from(dir);
+        self.spawn(move |store| async move { documents(&store, &dir).await }).await
+    }
+
+    pub async fn create_dir(&self, dir: &str) -> Result<(), RepoStoreError> {
+        let dir = String::from(dir);
+        self.spawn(move |store| async move { create_dir(&store, &dir).await }).await
+    }
+
+    pub async fn remove_dir(&self, dir: &str) -> Result<(), RepoStoreError> {
+        let dir = String::from(dir);
+        self.spawn(move |store| async move { remove_dir(&store, &dir).await }).await
+    }
+
+    pub async fn document_at(&self, path: &str, revision: &Revision) -> Result<Document<Map<String, Value>>, RepoStoreError> {
+        let (path, revision) = (String::from(path), revision.clone());
+        self.spawn(move |store| async move { document_at(&store, &path, &revision).await }).await
+    }
+
+    pub async fn revision_at(&self, path: &str, revision: &Revision) -> Result<Option<String>, RepoStoreError> {
+        let (path, revision) = (String::from(path), revision.clone());
+        self.spawn(move |store| async move {
+            let commit = resolve(&store, &revision).await?;
+            let cmd = store.git("rev-parse")
+                .add_option(args(&["--verify", "--quiet", format!("{}:{}", commit, path).as_str()]));
+            // fails quietly when the path does not exist in the commit
+            Ok(cmd.run_async().await.ok().map(|o| o.trim().to_string()))
+        }).await
+    }
+
+    pub async fn revisions(&self, path: &str) -> Result<Vec<DocumentRevision>, RepoStoreError> {
+        let path = String::from(path);
+        self.spawn(move |store| async move {
+            let cmd = store.git("log")
+                .add_option(args(&[LOG_FORMAT, "--", path.as_str()]));
+            match cmd.run_async().await {
+                Ok(o) => Ok(parse_log(&o)),
+                Err(e) => Err(RepoStoreError::History(Box::new(e))),
+            }
+        }).await
+    }
+
+    pub async fn resolve(&self, revision: &Revision) -> Result<String, RepoStoreError> {
+        let revision = revision.clone();
+        self.spawn(move |store| async move { resolve(&store, &revision).await }).await
+    }
+
+    pub async fn commits(&self, from: Option<&Revision>, to: &Revision) -> Result<Vec<DocumentRevision>, RepoStoreError> {
+        let (from, to) = (from.cloned(), to.clone());
+        self.spawn(move |store| async move {
+            if to == Revision::rev("HEAD") && is_unborn(&store).await {
+                return Ok(vec![]);
+            }
+            let mut range = resolve(&store, &to).await?;
+            if let Some(from) = from {
+                range = format!("{}..{}", resolve(&store, &from).await?, range);
+            }
+            let cmd = store.git("log")
+                .add_option(args(&[LOG_FORMAT, "--first-parent", "--reverse", range.as_str()]));
+            match cmd.run_async().await {
+                Ok(o) => Ok(parse_log(&o)),
+                Err(e) => Err(RepoStoreError::History(Box::new(e))),
+            }
+        }).await
+    }
+
+    pub async fn document_commits(&self, path: &str, to: &Revision) -> Result<Vec<DocumentRevision>, RepoStoreError> {
+        let (path, to) = (String::from(path), to.clone());
+        self.spawn(move |store| async move {
+            if to == Revision::rev("HEAD") && is_unborn(&store).await {
+                return Ok(vec![]);
+            }
+            let to = resolve(&store, &to).await?;
+            let cmd = store.git("log")
+                .add_option(args(&[LOG_FORMAT, "--first-parent", "--reverse", to.as_str(), "--", path.as_str()]));
+            match cmd.run_async().await {
+                Ok(o) => Ok(parse_log(&o)),
+                Err(e) => Err(RepoStoreError::History(Box::new(e))),
+            }
+        }).await
+    }
+
+    pub async fn pull(&self, rebase: bool) -> Result<(), RepoStoreError> {
+        self.pool.pull(rebase).await
+    }
+
+    pub async fn push(&self) -> Result<(), RepoStoreError> {
+        self.pool.push().await
+    }
+
+    /// Pushes the local commits, see [RepoStore::sync]
+    pub async fn sync(&self, policy: &SyncPolicy) -> Result<(), RepoStoreError> {
+        self.pool.sync(policy).await
+    }
+
+    pub async fn commit(&self, msg: &str) -> Result<(), RepoStoreError> {
+        self.pool.commit(msg).await
+    }
+
+    pub async fn commit_documents(&self, msg: &str, paths: &[String]) -> Result<(), RepoStoreError> {
+        self.pool.commit_documents(msg, paths).await
+    }
+
+    /// Commits with the given options, see [RepoStore::commit_with]
+    pub async fn commit_with(&self, msg: &str, paths: Option<&[String]>, options: &CommitOptions) -> Result<(), RepoStoreError> {
+        self.pool.commit_with(msg, paths, options).await
+    }
+
+    pub async fn restore_documents(&self, paths: &[String]) -> Result<(), RepoStoreError> {
+        self.pool.restore_documents(paths).await
+    }
+
+    pub async fn clean(&self, mode: CleanMode) -> Result<Vec<String>, RepoStoreError> {
+        self.pool.clean(mode).await
+    }
+
+    pub async fn current_branch(&self) -> Result<String, RepoStoreError> {
+        self.pool.current_branch().await
+    }
+
+    pub async fn branches(&self) -> Result<Vec<String>, RepoStoreError> {
+        self.pool.branches().await
+    }
+
+    pub async fn create_branch(&self, name: &str, from: &Revision) -> Result<(), RepoStoreError> {
+        self.pool.create_branch(name, from).await
+    }
+
+    pub async fn switch_branch(&self, name: &str) -> Result<(), RepoStoreError> {
+        self.pool.switch_branch(name).await
+    }
+
+    pub async fn delete_branch(&self, name: &str) -> Result<(), RepoStoreError> {
+        self.pool.delete_branch(name).await
+    }
+
+    pub async fn merge_branch(&self, name: &str) -> Result<(), RepoStoreError> {
+        self.pool.merge_branch(name).await
+    }
+
+    pub async fn create_snapshot(&self, name: &str, msg: &str) -> Result<(), RepoStoreError> {
+        self.pool.create_snapshot(name, msg).await
+    }
+
+    pub async fn snapshots(&self) -> Result<Vec<Snapshot>, RepoStoreError> {
+        self.pool.snapshots().await
+    }
+
+    pub async fn restore_snapshot(&self, name: &str, msg: &str) -> Result<(), RepoStoreError> {
+        self.pool.restore_snapshot(name, msg).await
+    }
+
+    pub async fn paths_at(&self, revision: &Revision) -> Result<Vec<String>, RepoStoreError> {
+        self.pool.paths_at(revision).await
+    }
+
+    pub async fn changed_paths(&self, from: Option<&Revision>, to: &Revision) -> Result<Vec<PathChange>, RepoStoreError> {
+        self.pool.changed_paths(from, to).await
+    }
+
+    pub async fn head(&self) -> Result<Option<String>, RepoStoreError> {
+        self.spawn(|store| async move {
+            if is_unborn(&store).await {
+                return Ok(None);
+            }
+            resolve(&store, &Revision::rev("HEAD")).await.map(Some)
+        }).await
+    }
+}
+
+// The operations below follow those of the RepoStore implementation of GitStore step by step
+
+async fn document(store: &GitStore, path: &str) -> Result<Document<Map<String, Value>>, RepoStoreError> {
+    let doc_path = store.doc_path(path)?;
+    store.check_checked_out(path, RepoStoreError::Read)?;
+    // the revision is computed from the content read, writes replacing documents whole
+    let content = match tokio::fs::read(doc_path).await {
+        Ok(content) => content,
+        Err(e) => return Err(RepoStoreError::Read(Box::new(e))),
+    };
+    let revision = blob_id(store, path, &content).await?;
+    if store.requires_signatures() {
+        verify_document(store, path, "HEAD", Some(&revision)).await?;
+    }
+    match Document::load(&mut content.as_slice(), map_from_str) {
+        Ok(mut doc) => {
+            doc.set_revision(Some(revision));
+            Ok(doc)
+        },
+        Err(e) => Err(RepoStoreError::Read(Box::new(e))),
+    }
+}
+
+/// Writes `document` aside and renames it over `path`, the caller holding the lock of the store
+async fn write_document(store: &GitStore, path: &str, document: &mut Document<Map<String, Value>>) -> Result<(), RepoStoreError> {
+    let doc_path = store.doc_path(path)?;
+    store.check_checked_out(path, RepoStoreError::Write)?;
+    if let Some(parent) = doc_path.parent()
+        && let Err(e) = tokio::fs::create_dir_all(parent).await {
+        return Err(RepoStoreError::Write(Box::new(e)));
+    }
+    let tmp_path = tmp_path(&doc_path);
+    if let Err(e) = tokio::fs::write(&tmp_path, map_into_string(document.content())).await {
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+        return Err(RepoStoreError::Write(Box::new(e)));
+    }
+    match tokio::fs::rename(&tmp_path, &doc_path).await {
+        Ok(_) => {
+            document.set_revision(revision(store, path).await?);
+            Ok(())
+        },
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+            Err(RepoStoreError::Write(Box::new(e)))
+        },
+    }
+}
+
+/// Removes the document at `path`, the caller holding the lock of the store
+async fn remove_document(store: &GitStore, path: &str) -> Result<(), RepoStoreError> {
+    let doc_path = store.doc_path(path)?;
+    store.check_checked_out(path, RepoStoreError::Write)?;
+    match tokio::fs::remove_file(doc_path).await {
+        Ok(_) => Ok(()),
+        Err(e) => Err(RepoStoreError::Write(Box::new(e))),
+    }
+}
+
+async fn check_revision(store: &GitStore, path: &str, expected: Option<&str>) -> Result<(), RepoStoreError> {
+    if revision(store, path).await?.as_deref() == expected {
+        Ok(())
+    } else {
+        Err(RepoStoreError::RevisionConflict(String::from(path)))
+    }
+}
+
+async fn revision(store: &GitStore, path: &str) -> Result<Option<String>, RepoStoreError> {
+    let doc_path = store.doc_path(path)?;
+    if !tokio::fs::metadata(&doc_path).await.is_ok_and(|m| m.is_file()) {
+        return Ok(None);
+    }
+    match tokio::fs::read(doc_path).await {
+        Ok(content) => Ok(Some(blob_id(store, path, &content).await?)),
+        Err(e) => Err(RepoStoreError::Read(Box::new(e))),
+    }
+}
+
+async fn documents(store: &GitStore, dir: &str) -> Result<Vec<String>, RepoStoreError> {
+    let dir_path = store.doc_path(dir)?;
+    store.check_checked_out(format!("{}/", dir.trim_end_matches('/')).as_str(), RepoStoreError::Read)?;
+    let mut entries = match tokio::fs::read_dir(dir_path).await {
+        Ok(entries) => entries,
+        Err(e) => return Err(RepoStoreError::Read(Box::new(e))),
+    };
+    let mut names = Vec::new();
+    loop {
+        match entries.next_entry().await {
+            Ok(Some(entry)) => {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if !name.starts_with('.') && tokio::fs::metadata(entry.path()).await.is_ok_and(|m| m.is_file()) {
+                    names.push(name);
+                }
+            },
+            Ok(None) => break,
+            Err(e) => return Err(RepoStoreError::Read(Box::new(e))),
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+async fn create_dir(store: &GitStore, dir: &str) -> Result<(), RepoStoreError> {
+    let dir_path = store.doc_path(dir)?;
+    store.check_checked_out(format!("{}/", dir.trim_end_matches('/')).as_str(), RepoStoreError::Write)?;
+    let _lock = store.lock_async().await?;
+    if let Err(e) = tokio::fs::create_dir_all(&dir_path).await {
+        return Err(RepoStoreError::Write(Box::new(e)));
+    }
+    // git does not track empty directories
+    match tokio::fs::write(dir_path.join(".gitkeep"), "").await {
+        Ok(_) => Ok(()),
+        Err(e) => Err(RepoStoreError::Write(Box::new(e))),
+    }
+}
+
+async fn remove_dir(store: &GitStore, dir: &str) -> Result<(), RepoStoreError> {
+    let dir_path = store.doc_path(dir)?;
+    // the root holds the repository itself
+    if Path::new(dir).components().all(|c| c == Component::CurDir) {
+        return Err(RepoStoreError::InvalidPath(String::from(dir)));
+    }
+    store.check_checked_out(format!("{}/", dir.trim_end_matches('/')).as_str(), RepoStoreError::Write)?;
+    let _lock = store.lock_async().await?;
+    match tokio::fs::remove_dir_all(dir_path).await {
+        Ok(_) => Ok(()),
+        Err(e) => Err(RepoStoreError::Write(Box::new(e))),
+    }
+}
+
+async fn document_at(store: &GitStore, path: &str, revision: &Revision) -> Result<Document<Map<String, Value>>, RepoStoreError> {
+    let commit = resolve(store, revision).await?;
+    if store.requires_signatures() {
+        verify_document(store, path, commit.as_str(), None).await?;
+    }
+    // read from stdout alone, as [GitStore::file_at] does
+    let cmd = store.fetching_git("cat-file")?
+        .add_option(args(&["blob", format!("{}:{}", commit, path).as_str()]));
+    let content = match cmd.run_async().await {
+        Ok(o) => o,
+        Err(e) => return Err(RepoStoreError::Read(Box::new(e))),
+    };
+    let cmd = store.git("rev-parse")
+        .add_option(args(&[format!("{}:{}", commit, path).as_str()]));
+    let revision = match cmd.run_async().await {
+        Ok(o) => o.trim().to_string(),
+        Err(e) => return Err(RepoStoreError::Read(Box::new(e))),
+    };
+    match Document::load(&mut content.as_bytes(), map_from_str) {
+        Ok(mut doc) => {
+            doc.set_revision(Some(revision));
+            Ok(doc)
+        },
+        Err(e) => Err(RepoStoreError::Read(Box::new(e))),
+    }
+}
+
+async fn resolve(store: &GitStore, revision: &Revision) -> Result<String, RepoStoreError> {
+    let cmd = match revision {
+        Revision::Rev(rev) => store.git("rev-parse")
+            .add_option(args(&["--verify", "--quiet", format!("{}^{{commit}}", rev).as_str()])),
+        Revision::AsOf(timestamp) => store.git("rev-list")
+            .add_option(args(&["-1", format!("--before=@{}", timestamp).as_str(), "HEAD"])),
+    };
+    match cmd.run_async().await {
+        Ok(o) if !o.trim().is_empty() => Ok(o.trim().to_string()),
+        Ok(_) => Err(RepoStoreError::History(format!("no commit found for {:?}", revision).into())),
+        Err(e) => Err(RepoStoreError::History(Box::new(e))),
+    }
+}
+
+/// Whether the active branch has no commit yet
+async fn is_unborn(store: &GitStore) -> bool {
+    store.git("rev-parse")
+        .add_option(args(&["--verify", "--quiet", "HEAD"]))
+        .run_async()
+        .await
+        .is_err()
+}
+
+/// Id git gives to the blob of `content` stored at `path`, hashed by the git command line whatever the backend of the store
+async fn blob_id(store: &GitStore, path: &str, content: &[u8]) -> Result<String, RepoStoreError> {
+    let cmd = store.git("hash-object")
+        .add_option(args(&["--stdin", format!("--path={}", path).as_str()]))
+        .stdin(content);
+    match cmd.run_async().await {
+        Ok(o) => Ok(o.trim().to_string()),
+        Err(e) => Err(RepoStoreError::Read(Box::new(e))),
+    }
+}
+
+/// Fails unless the last commit changing `path` up to `commit` has a trusted signature
+/// and, when given, `revision` is the version of the document in that commit
+async fn verify_document(store: &GitStore, path: &str, commit: &str, revision: Option<&str>) -> Result<(), RepoStoreError> {
+    if let Some(revision) = revision {
+        let cmd = store.git("rev-parse")
+            .add_option(args(&["--verify", "--quiet", format!("{}:{}", commit, path).as_str()]));
+        match cmd.run_async().await {
+            Ok(o) if o.trim() == revision => {},
+            _ => return Err(RepoStoreError::UntrustedDocument(String::from(path))),
+        }
+    }
+    let cmd = store.git("log")
+        .add_option(args(&[LOG_FORMAT, "-1", commit, "--", path]));
+    match cmd.run_async().await {
+        Ok(o) if parse_log(&o).first().is_some_and(DocumentRevision::is_trusted) => Ok(()),
+        Ok(_) => Err(RepoStoreError::UntrustedDocument(String::from(path))),
+        Err(e) => Err(RepoStoreError::History(Box::new(e))),
+    }
+}
@@ -0,0 +1,312 @@
+use crate::diff::key_changes;
+use crate::history::{DocumentRevision, Revision};
+use crate::json_document::map_from_str;
+use crate::repo_store::{Identity, RepoStore, RepoStoreError};
+use serde_json::{Map, Value};
+use std::fmt::{Display, Formatter};
+
+const OPERATION: &str = "Operation";
+const ACTOR: &str = "Actor";
+const REQUEST_ID: &str = "Request-Id";
+const DOCUMENT: &str = "Document";
+const KEY: &str = "Key";
+
+/// Kind of change recorded by a commit of the store
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    /// Changes committed with [RepoStore::commit] or [RepoStore::commit_documents]
+    Commit,
+    /// Writes committed by a [Transaction](crate::transaction::Transaction)
+    Transaction,
+    /// Documents reverted to a snapshot by [RepoStore::restore_snapshot]
+    Restore,
+    /// Writes buffered by a [WriteBehindStore](crate::write_behind::WriteBehindStore)
+    Flush,
+    /// An operation named by the application
+    Other(String),
+}
+
+impl Operation {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Operation::Commit => "commit",
+            Operation::Transaction => "transaction",
+            Operation::Restore => "restore",
+            Operation::Flush => "flush",
+            Operation::Other(name) => name.as_str(),
+        }
+    }
+}
+
+impl From<&str> for Operation {
+    fn from(name: &str) -> Self {
+        match name {
+            "commit" => Operation::Commit,
+            "transaction" => Operation::Transaction,
+            "restore" => Operation::Restore,
+            "flush" => Operation::Flush,
+            other => Operation::Other(String::from(other)),
+        }
+    }
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Who made a commit and why, the author and committer being recorded by git and the rest in its trailers
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommitOptions {
+    operation: Option<Operation>,
+    actor: Option<String>,
+    request_id: Option<String>,
+    author: Option<Identity>,
+    committer: Option<Identity>,
+}
+
+impl CommitOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Overrides the operation recorded, which otherwise depends on the method committing
+    pub fn operation(self, operation: Operation) -> Self {
+        Self {
+            operation: Some(operation),
+            ..self
+        }
+    }
+
+    /// The user or service on whose behalf the commit is made
+    pub fn actor(self, actor: &str) -> Self {
+        Self {
+            actor: Some(String::from(actor)),
+            ..self
+        }
+    }
+
+    /// Id of the request that led to the commit, to correlate it with application logs
+    pub fn request_id(self, request_id: &str) -> Self {
+        Self {
+            request_id: Some(String::from(request_id)),
+            ..self
+        }
+    }
+
+    /// Author of the changes, the store identity by default
+    pub fn author(self, author: Identity) -> Self {
+        Self {
+            author: Some(author),
+            ..self
+        }
+    }
+
+    /// Committer, the store identity by default
+    pub fn committer(self, committer: Identity) -> Self {
+        Self {
+            committer: Some(committer),
+            ..self
+        }
+    }
+
+    pub fn author_identity(&self) -> Option<&Identity> {
+        self.author.as_ref()
+    }
+
+    pub fn committer_identity(&self) -> Option<&Identity> {
+        self.committer.as_ref()
+    }
+
+    /// Sets the operation recorded unless one was given
+    pub(crate) fn default_operation(self, operation: Operation) -> Self {
+        Self {
+            operation: self.operation.or(Some(operation)),
+            ..self
+        }
+    }
+
+    /// Trailers of a commit made with these options, before adding its changes
+    pub(crate) fn trailers(&self, default: Operation) -> CommitTrailers {
+        CommitTrailers {
+            operation: self.operation.clone().unwrap_or(default),
+            actor: self.actor.clone(),
+            request_id: self.request_id.clone(),
+            documents: vec![],
+            keys: vec![],
+        }
+    }
+}
+
+/// A leaf key of a document changed by a commit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentKey {
+    path: String,
+    key: String,
+}
+
+impl DocumentKey {
+    pub fn new(path: &str, key: &str) -> Self {
+        Self {
+            path: String::from(path),
+            key: String::from(key),
+        }
+    }
+
+    /// Document path
+    pub fn path(&self) -> &str {
+        self.path.as_str()
+    }
+
+    /// Dotted key
+    pub fn key(&self) -> &str {
+        self.key.as_str()
+    }
+}
+
+/// Machine-readable trailers the stores append to the message of the commits they create, such as
+///
+/// ```text
+/// Operation: transaction
+/// Actor: jane
+/// Request-Id: 42
+/// Document: users/john.json
+/// Key: users/john.json:age
+/// ```
+///
+/// Keys are written as `<path>:<key>`, after the document of the path, and only listed for JSON documents.
+/// Committing fails when a value would span lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitTrailers {
+    operation: Operation,
+    actor: Option<String>,
+    request_id: Option<String>,
+    documents: Vec<String>,
+    keys: Vec<DocumentKey>,
+}
+
+impl CommitTrailers {
+    pub fn operation(&self) -> &Operation {
+        &self.operation
+    }
+
+    pub fn actor(&self) -> Option<&str> {
+        self.actor.as_deref()
+    }
+
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    /// Paths of the documents added, modified or removed, sorted
+    pub fn documents(&self) -> &[String] {
+        self.documents.as_slice()
+    }
+
+    /// Leaf keys changed in JSON documents, sorted by path then key
+    pub fn keys(&self) -> &[DocumentKey] {
+        self.keys.as_slice()
+    }
+
+    /// Records the change of the file at `path` from `old` to `new`, `None` when absent
+    pub(crate) fn change(&mut self, path: &str, old: Option<&str>, new: Option<&str>) {
+        if old == new {
+            return;
+        }
+        self.documents.push(String::from(path));
+        if path.ends_with(".json") {
+            let parse = |content: Option<&str>| content.map_or(Ok(Map::new()), map_from_str);
+            if let (Ok(old), Ok(new)) = (parse(old), parse(new)) {
+                let keys = key_changes(&old, &new).into_iter().map(|k| DocumentKey::new(path, k.key()));
+                self.keys.extend(keys);
+            }
+        }
+    }
+
+    /// Appends the trailers to `msg` as its last paragraph, failing when a value spans lines
+    /// as it would end the trailer block early
+    pub(crate) fn message(&self, msg: &str) -> Result<String, RepoStoreError> {
+        let mut documents = self.documents.clone();
+        documents.sort();
+        documents.dedup();
+        let mut keys: Vec<&DocumentKey> = self.keys.iter().collect();
+        keys.sort_by(|a, b| (a.path(), a.key()).cmp(&(b.path(), b.key())));
+
+        let mut trailers = vec![format!("{}: {}", OPERATION, self.operation)];
+        trailers.extend(self.actor.iter().map(|a| format!("{}: {}", ACTOR, a)));
+        trailers.extend(self.request_id.iter().map(|r| format!("{}: {}", REQUEST_ID, r)));
+        trailers.extend(documents.iter().map(|d| format!("{}: {}", DOCUMENT, d)));
+        trailers.extend(keys.iter().map(|k| format!("{}: {}:{}", KEY, k.path(), k.key())));
+        if let Some(trailer) = trailers.iter().find(|t| t.contains('\n') || t.contains('\r')) {
+            return Err(RepoStoreError::Commit(format!("trailer value spans lines: {:?}", trailer).into()));
+        }
+        Ok(format!("{}\n\n{}", msg.trim_end(), trailers.join("\n")))
+    }
+
+    /// Splits the trailers written by [CommitTrailers::message] off a commit message,
+    /// returning the message without them. Messages whose last paragraph holds other lines are left whole.
+    pub fn parse(message: &str) -> (&str, Option<Self>) {
+        let message = message.trim_end();
+        // a commit with an empty message keeps only the trailers
+        let (body, paragraph) = message.rsplit_once("\n\n").unwrap_or(("", message));
+        let mut operation = None;
+        let mut trailers = CommitTrailers {
+            operation: Operation::Commit,
+            actor: None,
+            request_id: None,
+            documents: vec![],
+            keys: vec![],
+        };
+        for line in paragraph.lines() {
+            let Some((token, value)) = line.split_once(": ") else {
+                return (message, None);
+            };
+            match token {
+                OPERATION => operation = Some(Operation::from(value)),
+                ACTOR => trailers.actor = Some(String::from(value)),
+                REQUEST_ID => trailers.request_id = Some(String::from(value)),
+                DOCUMENT => trailers.documents.push(String::from(value)),
+                // the path is the longest document listed ahead that the value starts with, either may hold colons
+                KEY => match trailers.documents.iter().filter(|d| value.strip_prefix(d.as_str()).is_some_and(|k| k.starts_with(':'))).max_by_key(|d| d.len()) {
+                    Some(path) => trailers.keys.push(DocumentKey::new(path, &value[path.len() + 1..])),
+                    None => return (message, None),
+                },
+                _ => return (message, None),
+            }
+        }
+        match operation {
+            Some(operation) => (body.trim_end(), Some(CommitTrailers { operation, ..trailers })),
+            None => (message, None),
+        }
+    }
+}
+
+/// A commit created by a store, with its trailers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditRecord {
+    commit: DocumentRevision,
+    trailers: CommitTrailers,
+}
+
+impl AuditRecord {
+    pub fn commit(&self) -> &DocumentRevision {
+        &self.commit
+    }
+
+    pub fn trailers(&self) -> &CommitTrailers {
+        &self.trailers
+    }
+}
+
+/// Lists the commits from `from` to `to` carrying trailers, oldest first, as [RepoStore::commits] does.
+/// Commits made outside of the stores, merges included, are skipped.
+pub fn audit_log<S: RepoStore<Map<String, Value>>>(store: &S, from: Option<&Revision>, to: &Revision) -> Result<Vec<AuditRecord>, RepoStoreError> {
+    Ok(store.commits(from, to)?
+        .into_iter()
+        .filter_map(|commit| {
+            let trailers = commit.trailers()?.clone();
+            Some(AuditRecord { commit, trailers })
+        })
+        .collect())
+}
@@ -0,0 +1,420 @@
+use crate::credentials::Credential;
+use crate::git_args::{args, config_env, EnvCommand};
+use crate::repo_store::{under, Identity};
+use gitwrap::wrap_command::FnOptionArg;
+use gitwrap::{add, clone, commit, git, pull, rev_parse, status};
+use gitwrap::WrapError;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub enum GitBackendError {
+    NotARepository(PathBuf),
+    NothingToCommit,
+    /// A pull stopped on conflicts in the given paths, leaving the merge or rebase in progress
+    Conflict(Vec<String>),
+    /// The remote refused to update a reference, usually because it is not a fast-forward
+    Rejected(String),
+    Unsupported(String),
+    /// A failed git command or library call
+    Git(Box<dyn Error>),
+}
+
+impl Error for GitBackendError {}
+
+impl GitBackendError {
+    fn format(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GitBackendError::NotARepository(p) => write!(f, "not a git repository: {}", p.display()),
+            GitBackendError::NothingToCommit => write!(f, "nothing to commit"),
+            GitBackendError::Conflict(p) => write!(f, "conflicts in {}", p.join(", ")),
+            GitBackendError::Rejected(e) => write!(f, "rejected by remote: {}", e),
+            GitBackendError::Unsupported(e) => write!(f, "unsupported: {}", e),
+            GitBackendError::Git(e) => write!(f, "git failed: {}", e),
+        }
+    }
+}
+
+impl Display for GitBackendError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.format(f)
+    }
+}
+
+impl Debug for GitBackendError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.format(f)
+    }
+}
+
+/// A remote repository and the means to reach it
+#[derive(Debug, Clone, Default)]
+pub struct Remote {
+    url: String,
+    credential: Option<Credential>,
+    insecure: bool,
+}
+
+impl Remote {
+    pub fn new(url: &str, credential: Option<Credential>) -> Self {
+        Self {
+            url: String::from(url),
+            credential,
+            insecure: false,
+        }
+    }
+
+    /// Disables TLS certificate verification when set
+    pub fn insecure(self, insecure: bool) -> Self {
+        Self {
+            insecure,
+            ..self
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        self.url.as_str()
+    }
+
+    pub fn credential(&self) -> Option<&Credential> {
+        self.credential.as_ref()
+    }
+
+    pub fn is_insecure(&self) -> bool {
+        self.insecure
+    }
+}
+
+/// How much of the remote repository a clone fetches and checks out, everything by default
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CloneOptions {
+    depth: Option<u32>,
+    filter: Option<String>,
+    sparse: Option<Vec<String>>,
+}
+
+impl CloneOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fetches only the last `depth` commits of each branch, history reaching no further until fetched
+    pub fn depth(self, depth: u32) -> Self {
+        Self {
+            depth: Some(depth),
+            ..self
+        }
+    }
+
+    /// Fetches only the objects matching the partial clone `filter`, such as `blob:none`,
+    /// the missing ones being fetched from the remote when first read
+    pub fn filter(self, filter: &str) -> Self {
+        Self {
+            filter: Some(String::from(filter)),
+            ..self
+        }
+    }
+
+    /// Checks out only the files at the root, directly in the parents of `dirs` and under `dirs`
+    pub fn sparse(self, dirs: &[&str]) -> Self {
+        Self {
+            sparse: Some(dirs.iter().map(|d| d.trim_matches('/').to_string()).collect()),
+            ..self
+        }
+    }
+
+    pub fn clone_depth(&self) -> Option<u32> {
+        self.depth
+    }
+
+    pub fn clone_filter(&self) -> Option<&str> {
+        self.filter.as_deref()
+    }
+
+    /// Directories of the sparse checkout, `None` when everything is checked out
+    pub fn sparse_dirs(&self) -> Option<&[String]> {
+        self.sparse.as_deref()
+    }
+
+    /// Whether `path` is checked out, as a file at the root, directly in a parent of a sparse directory or under one.
+    /// A path ending with `/` stands for the files directly in that directory.
+    pub fn is_checked_out(&self, path: &str) -> bool {
+        let Some(dirs) = &self.sparse else {
+            return true;
+        };
+        let path = path.trim_start_matches('/');
+        let parent = path.rsplit_once('/').map_or("", |(parent, _)| parent);
+        parent.is_empty() || dirs.iter().any(|dir| under(path, dir) || under(dir, parent))
+    }
+}
+
+/// The git operations a [crate::repo_store::GitStore] runs on its working copy, those reaching the remote included.
+/// The store runs its other operations with the `git` command line whatever the backend.
+/// Repositories are given by the root of their working tree, and remote operations use the `origin` remote.
+pub trait GitBackend: Debug + Send + Sync {
+    /// Clones `remote` into `path`, checking out `branch` or the remote default one, as limited by `options`
+    fn clone_repo(&self, remote: &Remote, path: &Path, branch: Option<&str>, options: &CloneOptions) -> Result<(), GitBackendError>;
+    /// Whether `path` is the root of a working tree
+    fn is_repo(&self, path: &Path) -> bool;
+    /// Paths with uncommitted changes, untracked files included. Renames list both paths.
+    fn status(&self, path: &Path) -> Result<Vec<String>, GitBackendError>;
+    /// Stages and commits every change, or only the changes under `paths` when given.
+    /// The author and committer default to the identity configured in the repository.
+    fn commit(&self, path: &Path, msg: &str, paths: Option<&[String]>, author: Option<&Identity>, committer: Option<&Identity>) -> Result<(), GitBackendError>;
+    /// Content of `file` in the tree of the last commit, `None` when it is absent or the branch has no commit yet.
+    /// A blob missing from a partial clone is fetched from `remote`, when given.
+    fn head_file(&self, path: &Path, file: &str, remote: Option<&Remote>) -> Result<Option<String>, GitBackendError>;
+    /// Id git gives to the blob of `content` stored at `file`, in the object format of the repository
+    /// and after the filters and end of line conversions set for the file
+    fn hash_blob(&self, path: &Path, file: &str, content: &[u8]) -> Result<String, GitBackendError>;
+    fn fetch(&self, path: &Path, remote: &Remote) -> Result<(), GitBackendError>;
+    /// Fetches the history of the remote branches down to `depth` commits from their tips, all of it when `None`
+    fn fetch_history(&self, path: &Path, remote: &Remote, depth: Option<u32>) -> Result<(), GitBackendError>;
+    /// Integrates the upstream branch, by rebasing the local commits or merging.
+    /// Conflicts are reported with [GitBackendError::Conflict], leaving the operation in progress
+    /// in the same state the git command line would.
+    fn pull(&self, path: &Path, remote: &Remote, rebase: bool) -> Result<(), GitBackendError>;
+    /// Pushes the current branch to its upstream, failing with [GitBackendError::Rejected] when it is behind
+    fn push(&self, path: &Path, remote: &Remote) -> Result<(), GitBackendError>;
+}
+
+/// Runs the `git` command line through gitwrap
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GitwrapBackend;
+
+impl GitwrapBackend {
+    /// A command reaching the remote, with the credentials passed through its environment
+    /// so they are never written to the repository configuration
+    fn remote_command(cmd: &str, remote: &Remote) -> EnvCommand {
+        EnvCommand::new(cmd).envs(remote_env(remote))
+    }
+
+    fn conflicted_paths(path: &Path) -> Vec<String> {
+        let cmd = git("diff")
+            .add_option(args(&["--name-only", "-z", "--diff-filter=U"]));
+        match cmd.current_dir(dir(path)).run() {
+            Ok(o) => o.split('\0').filter(|p| !p.is_empty()).map(String::from).collect(),
+            Err(_) => vec![],
+        }
+    }
+}
+
+/// Environment variables passing the credentials and TLS settings of `remote` to a git command
+pub(crate) fn remote_env(remote: &Remote) -> Vec<(String, String)> {
+    let mut entries = vec![];
+    if let Some(credential) = remote.credential() {
+        entries.push(credential.config_entry());
+    }
+    if remote.is_insecure() {
+        entries.push(("http.sslVerify", String::from("false")));
+    }
+    config_env(&entries)
+}
+
+fn dir(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+/// Whether the repository at `path` misses the history before some commits
+pub(crate) fn is_shallow(path: &Path) -> bool {
+    let cmd = EnvCommand::new("rev-parse")
+        .add_option(args(&["--is-shallow-repository"]));
+    cmd.current_dir(dir(path)).run().is_ok_and(|o| o.trim() == "true")
+}
+
+impl GitBackend for GitwrapBackend {
+    fn clone_repo(&self, remote: &Remote, path: &Path, branch: Option<&str>, options: &CloneOptions) -> Result<(), GitBackendError> {
+        let mut custom_options : Vec<FnOptionArg> = vec![];
+        if let Some(branch) = branch {
+            custom_options.push(clone::branch(branch))
+        }
+        if let Some(depth) = options.clone_depth() {
+            // keeps fetching every branch, as a full clone does
+            custom_options.push(args(&["--depth", depth.to_string().as_str(), "--no-single-branch"]));
+        }
+        if let Some(filter) = options.clone_filter() {
+            custom_options.push(args(&[format!("--filter={}", filter).as_str()]));
+        }
+        if options.sparse_dirs().is_some() {
+            custom_options.push(args(&["--sparse"]));
+        }
+        let cmd = Self::remote_command("clone", remote)
+            .add_option(clone::repository(remote.url()))
+            .add_option(clone::directory(dir(path)))
+            .add_options(custom_options);
+        if let Err(e) = cmd.run() {
+            return Err(GitBackendError::Git(Box::new(e)));
+        }
+
+        let Some(dirs) = options.sparse_dirs() else {
+            return Ok(());
+        };
+        let dirs: Vec<&str> = dirs.iter().map(String::as_str).collect();
+        // checking out the directories fetches their missing blobs in a partial clone
+        let cmd_sparse = Self::remote_command("sparse-checkout", remote)
+            .add_option(args(&["set", "--cone", "--"]))
+            .add_option(args(&dirs));
+        match cmd_sparse.current_dir(dir(path)).run() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(GitBackendError::Git(Box::new(e))),
+        }
+    }
+
+    fn is_repo(&self, path: &Path) -> bool {
+        let cmd = EnvCommand::new("rev-parse")
+            .add_option(rev_parse::show_toplevel());
+        match cmd.current_dir(dir(path)).run() {
+            Ok(o) => match (fs::canonicalize(o.trim()), fs::canonicalize(path)) {
+                (Ok(top_level), Ok(repo_path)) => top_level == repo_path,
+                _ => false,
+            },
+            Err(_) => false,
+        }
+    }
+
+    fn status(&self, path: &Path) -> Result<Vec<String>, GitBackendError> {
+        let cmd = EnvCommand::new("status")
+            .add_option(status::porcelain("v1"))
+            .add_option(status::null())
+            .add_option(status::untracked_files("all"));
+        match cmd.current_dir(dir(path)).run() {
+            Ok(o) => Ok(parse_porcelain_paths(&o)),
+            Err(e) => Err(GitBackendError::Git(Box::new(e))),
+        }
+    }
+
+    fn commit(&self, path: &Path, msg: &str, paths: Option<&[String]>, author: Option<&Identity>, committer: Option<&Identity>) -> Result<(), GitBackendError> {
+        let pathspec: Vec<&str> = paths.unwrap_or_default().iter().map(String::as_str).collect();
+        let mut cmd_add = add::add()
+            .add_option(add::all());
+        let mut cmd_commit = EnvCommand::new("commit")
+            .add_option(commit::message(msg))
+            .envs(author.map(Identity::author_env).unwrap_or_default())
+            .envs(committer.map(Identity::committer_env).unwrap_or_default());
+        if paths.is_some() {
+            cmd_add = cmd_add
+                .add_option(add::hyphen_hyphen())
+                .add_option(args(&pathspec));
+            cmd_commit = cmd_commit
+                .add_option(commit::hyphen_hyphen())
+                .add_option(args(&pathspec));
+        }
+        if let Err(e) = cmd_add.current_dir(dir(path)).run() {
+            return Err(GitBackendError::Git(Box::new(e)));
+        }
+        // the exit status tells whether anything is staged, whatever the language of the messages of git
+        let cmd_staged = EnvCommand::new("diff")
+            .add_option(args(&["--cached", "--quiet", "--"]))
+            .add_option(args(&pathspec));
+        match cmd_staged.current_dir(dir(path)).run() {
+            Ok(_) => return Err(GitBackendError::NothingToCommit),
+            Err(WrapError::ExitStatus(_, 1)) => {},
+            Err(e) => return Err(GitBackendError::Git(Box::new(e))),
+        }
+        match cmd_commit.current_dir(dir(path)).run() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(GitBackendError::Git(Box::new(e))),
+        }
+    }
+
+    fn head_file(&self, path: &Path, file: &str, remote: Option<&Remote>) -> Result<Option<String>, GitBackendError> {
+        let cmd = EnvCommand::new("cat-file")
+            .add_option(args(&["blob", format!("HEAD:{}", file).as_str()]))
+            .envs(remote.map(remote_env).unwrap_or_default());
+        // fails when the file or HEAD does not exist
+        Ok(cmd.current_dir(dir(path)).run().ok())
+    }
+
+    fn hash_blob(&self, path: &Path, file: &str, content: &[u8]) -> Result<String, GitBackendError> {
+        let cmd = EnvCommand::new("hash-object")
+            .add_option(args(&["--stdin", format!("--path={}", file).as_str()]));
+        match cmd.stdin(content).current_dir(dir(path)).run() {
+            Ok(o) => Ok(o.trim().to_string()),
+            Err(e) => Err(GitBackendError::Git(Box::new(e))),
+        }
+    }
+
+    fn fetch(&self, path: &Path, remote: &Remote) -> Result<(), GitBackendError> {
+        let cmd = Self::remote_command("fetch", remote);
+        match cmd.current_dir(dir(path)).run() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(GitBackendError::Git(Box::new(e))),
+        }
+    }
+
+    fn fetch_history(&self, path: &Path, remote: &Remote, depth: Option<u32>) -> Result<(), GitBackendError> {
+        let depth = match depth {
+            Some(depth) => format!("--depth={}", depth),
+            None if !is_shallow(path) => return Ok(()),
+            None => String::from("--unshallow"),
+        };
+        let cmd = Self::remote_command("fetch", remote)
+            .add_option(args(&[depth.as_str()]));
+        match cmd.current_dir(dir(path)).run() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(GitBackendError::Git(Box::new(e))),
+        }
+    }
+
+    fn pull(&self, path: &Path, remote: &Remote, rebase: bool) -> Result<(), GitBackendError> {
+        let cmd = Self::remote_command("pull", remote)
+            .add_option(if rebase { pull::rebase("") } else { pull::no_rebase() });
+        match cmd.current_dir(dir(path)).run() {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                let conflicts = Self::conflicted_paths(path);
+                if conflicts.is_empty() {
+                    Err(GitBackendError::Git(Box::new(e)))
+                } else {
+                    Err(GitBackendError::Conflict(conflicts))
+                }
+            },
+        }
+    }
+
+    fn push(&self, path: &Path, remote: &Remote) -> Result<(), GitBackendError> {
+        // the active branch is pushed under its own name, tracking it when it is new to the remote
+        let cmd = Self::remote_command("push", remote)
+            .add_option(args(&["--porcelain", "--set-upstream", "origin", "HEAD"]));
+        match cmd.current_dir(dir(path)).run() {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                let output = e.to_string();
+                if is_rejected(&output) {
+                    Err(GitBackendError::Rejected(output))
+                } else {
+                    Err(GitBackendError::Git(Box::new(e)))
+                }
+            },
+        }
+    }
+}
+
+/// Whether the output of `git push --porcelain` reports a reference the remote refused to update
+/// because it is not a fast-forward, rather than a hook declining it
+fn is_rejected(output: &str) -> bool {
+    output.lines().any(|line| {
+        let mut fields = line.split('\t');
+        fields.next() == Some("!") && fields.nth(1).is_some_and(|summary| summary.starts_with("[rejected]"))
+    })
+}
+
+/// Extracts the paths from `git status --porcelain=v1 -z` output.
+/// Renames and copies list both the new and the original path.
+fn parse_porcelain_paths(output: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+    let mut entries = output.split('\0').filter(|e| !e.is_empty());
+    while let Some(entry) = entries.next() {
+        if entry.len() < 4 {
+            continue;
+        }
+        let (code, path) = entry.split_at(3);
+        paths.push(path.to_string());
+        if (code.starts_with('R') || code.starts_with('C'))
+            && let Some(orig) = entries.next() {
+            paths.push(orig.to_string());
+        }
+    }
+    paths
+}
@@ -0,0 +1,831 @@
+use crate::audit::{CommitOptions, Operation};
+use crate::git_args::{args, config_env, EnvCommand};
+use crate::history::{parse_log, parse_name_status, parse_tags, DocumentRevision, PathChange, Revision, Snapshot, LOG_FORMAT, TAG_FORMAT};
+use crate::json_document::{map_from_str, map_into_string, Document};
+use crate::merge::{merge_document, DocumentConflict, MergePolicy, MergeTimes};
+use crate::repo_store::{under, CleanMode, GitCommit, RepoStore, RepoStoreError};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+/// Times a commit is retried when the branch moves while it is being written
+const COMMIT_ATTEMPTS: usize = 10;
+
+/// First git version whose `merge-tree` writes the merged tree, `--write-tree`
+const MERGE_TREE_VERSION: (u32, u32) = (2, 38);
+
+/// Version of the installed git, read once
+static GIT_VERSION: OnceLock<Option<(u32, u32)>> = OnceLock::new();
+
+static INDEX_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A write not committed yet, `None` content removing the document
+#[derive(Debug, Clone)]
+struct Pending {
+    content: Option<String>,
+    /// Revision the branch must still have for the document when committing, for conditional writes
+    base: Option<Option<String>>,
+}
+
+/// A [RepoStore] working directly on a bare repository, without a working tree.
+/// Documents are read from the tree of a branch, and writes are kept in memory until committed,
+/// when blobs, trees and a commit are created and the branch is moved with a compare-and-swap.
+/// Processes sharing the repository never overwrite each other's commits, and conditional writes
+/// are checked again against the branch when committing.
+/// The bare repository is the shared copy, with no remote and no commits left to push:
+/// [RepoStore::pull], [RepoStore::push] and so [RepoStore::sync] succeed without doing anything,
+/// and [RepoStore::clean] discards the uncommitted writes whatever its mode.
+/// Branches are merged without a working tree, JSON documents changed on both sides key by key,
+/// which needs git 2.38 or later.
+/// Commit signing and signature checks are configured per invocation, the repository configuration being left untouched.
+#[derive(Debug)]
+pub struct BareGitStore {
+    repo_path: PathBuf,
+    branch: Mutex<String>,
+    commit: GitCommit,
+    merge_policy: MergePolicy,
+    allowed_signers: Option<PathBuf>,
+    require_signatures: bool,
+    pending: Mutex<BTreeMap<String, Pending>>,
+}
+
+/// Major and minor version of the installed git
+fn git_version() -> Option<(u32, u32)> {
+    *GIT_VERSION.get_or_init(|| {
+        let version = EnvCommand::new("version").run().ok()?;
+        let mut numbers = version.trim().strip_prefix("git version ")?.split('.').map(|n| n.parse::<u32>().ok());
+        Some((numbers.next()??, numbers.next()??))
+    })
+}
+
+impl BareGitStore {
+    pub fn new(path: &str, branch: &str, commit: GitCommit) -> Self {
+        Self {
+            repo_path: Path::new("").join(path),
+            branch: Mutex::new(String::from(branch)),
+            commit,
+            merge_policy: MergePolicy::default(),
+            allowed_signers: None,
+            require_signatures: false,
+            pending: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Sets the file listing the SSH keys trusted to sign commits, in the format of `ssh-keygen` allowed signers
+    pub fn allowed_signers(self, path: &str) -> Self {
+        Self {
+            allowed_signers: Some(Path::new("").join(path)),
+            ..self
+        }
+    }
+
+    /// Refuses to load documents not last changed by a commit with a trusted signature, or with uncommitted writes
+    pub fn require_signatures(self, require_signatures: bool) -> Self {
+        Self {
+            require_signatures,
+            ..self
+        }
+    }
+
+    /// Sets how key conflicts in JSON documents are resolved when merging branches
+    pub fn merge_policy(self, merge_policy: MergePolicy) -> Self {
+        Self {
+            merge_policy,
+            ..self
+        }
+    }
+
+    pub fn repo_path(&self) -> &Path {
+        self.repo_path.as_path()
+    }
+
+    /// Full name of the branch holding the documents
+    pub fn ref_name(&self) -> String {
+        format!("refs/heads/{}", self.branch())
+    }
+
+    fn branch(&self) -> MutexGuard<'_, String> {
+        self.branch.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn repo_dir(&self) -> &str {
+        self.repo_path.to_str().unwrap()
+    }
+
+    fn git(&self, cmd: &str) -> EnvCommand {
+        let mut entries = vec![];
+        if let Some(signing) = self.commit.commit_signing() {
+            entries.extend(signing.config_entries());
+        }
+        if let Some(allowed_signers) = &self.allowed_signers {
+            entries.push(("gpg.ssh.allowedSignersFile", allowed_signers.to_string_lossy().to_string()));
+        }
+        let cmd = EnvCommand::new(cmd).current_dir(self.repo_dir());
+        if entries.is_empty() {
+            cmd
+        } else {
+            cmd.envs(config_env(&entries))
+        }
+    }
+
+    /// Fails unless the last commit changing `path` up to `commit` has a trusted signature
+    fn verify_document(&self, path: &str, commit: &str) -> Result<(), RepoStoreError> {
+        let cmd = self.git("log")
+            .add_option(args(&[LOG_FORMAT, "-1", commit, "--", path]));
+        match cmd.run() {
+            Ok(o) if parse_log(&o).first().is_some_and(DocumentRevision::is_trusted) => Ok(()),
+            Ok(_) => Err(RepoStoreError::UntrustedDocument(String::from(path))),
+            Err(e) => Err(RepoStoreError::History(Box::new(e))),
+        }
+    }
+
+    fn pending(&self) -> MutexGuard<'_, BTreeMap<String, Pending>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn ref_target(&self, ref_name: &str) -> Result<Option<String>, RepoStoreError> {
+        let cmd = self.git("rev-parse")
+            .add_option(args(&["--verify", "--quiet", ref_name]));
+        match cmd.run() {
+            Ok(o) => Ok(Some(o.trim().to_string())),
+            Err(gitwrap::WrapError::ExitStatus(_, 1)) => Ok(None),
+            Err(e) => Err(RepoStoreError::Read(Box::new(e))),
+        }
+    }
+
+    fn is_ancestor(&self, ancestor: &str, id: &str) -> bool {
+        self.git("merge-base")
+            .add_option(args(&["--is-ancestor", ancestor, id]))
+            .run()
+            .is_ok()
+    }
+
+    fn commit_time(&self, id: &str) -> i64 {
+        let cmd = self.git("log")
+            .add_option(args(&["-1", "--format=%ct", id]));
+        match cmd.run() {
+            Ok(o) => o.trim().parse().unwrap_or_default(),
+            Err(_) => 0,
+        }
+    }
+
+    /// Moves the branch from `old` to `new`, failing when another writer moved it in between
+    fn update_head(&self, new: &str, old: Option<&str>) -> Result<(), gitwrap::WrapError> {
+        self.git("update-ref")
+            .add_option(args(&[self.ref_name().as_str(), new, old.unwrap_or("")]))
+            .run()
+            .map(|_| ())
+    }
+
+    /// Merges `theirs` into `ours` with `git merge-tree`, merging the conflicting JSON documents key by key.
+    /// Returns the merge commit, or the documents left in conflict.
+    fn merge_commits(&self, ours: &str, theirs: &str, msg: &str) -> Result<Result<String, Vec<DocumentConflict>>, RepoStoreError> {
+        if git_version().is_none_or(|version| version < MERGE_TREE_VERSION) {
+            let (major, minor) = MERGE_TREE_VERSION;
+            return Err(RepoStoreError::Branch(format!("merging branches needs git {}.{} or later", major, minor).into()));
+        }
+        let cmd = self.git("merge-tree")
+            .add_option(args(&["--write-tree", "-z", ours, theirs]));
+        let output = match cmd.run() {
+            Ok(o) => o,
+            // conflicts exit with 1, the output listing them
+            Err(gitwrap::WrapError::ExitStatus(o, 1)) => o,
+            Err(e) => return Err(RepoStoreError::Branch(Box::new(e))),
+        };
+        let mut entries = output.split('\0');
+        let tree = entries.next().unwrap_or_default().trim().to_string();
+        // index stages of each conflicted path: 1 the merge base, 2 ours and 3 theirs
+        let mut stages: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+        for entry in entries.take_while(|e| !e.is_empty()) {
+            if let Some((info, path)) = entry.split_once('\t')
+                && let [_, id, stage] = info.split(' ').collect::<Vec<&str>>()[..] {
+                stages.entry(String::from(path)).or_default().insert(String::from(stage), String::from(id));
+            }
+        }
+        let times = MergeTimes { ours: self.commit_time(ours), theirs: self.commit_time(theirs) };
+        let mut conflicts = Vec::new();
+        let mut index_info = String::new();
+        for (path, ids) in &stages {
+            // `None` for content which is not a JSON document, `Some(None)` for a file absent on that side
+            let mut documents = Vec::new();
+            for stage in ["1", "2", "3"] {
+                documents.push(match ids.get(stage) {
+                    Some(id) => map_from_str(&self.blob(id)?).ok().map(Some),
+                    None => Some(None),
+                });
+            }
+            let merged = match &documents[..] {
+                [Some(base), Some(o), Some(t)] if path.ends_with(".json") => merge_document(path, base.as_ref(), o.as_ref(), t.as_ref(), &self.merge_policy, times),
+                _ => Err(vec![]),
+            };
+            match merged {
+                Ok(map) => index_info.push_str(format!("100644 {}\t{}\n", self.write_blob(&map_into_string(&map))?, path).as_str()),
+                Err(keys) => conflicts.push(DocumentConflict::new(path, keys)),
+            }
+        }
+        if !conflicts.is_empty() {
+            return Ok(Err(conflicts));
+        }
+        let parents = [String::from(ours), String::from(theirs)];
+        Ok(Ok(self.write_commit(msg, Some(&tree), &parents, &index_info, &CommitOptions::new())?))
+    }
+
+    /// Blob id of `path` in the tree of `commit`, `None` when absent
+    fn blob_id(&self, commit: Option<&str>, path: &str) -> Option<String> {
+        let commit = commit?;
+        let cmd = self.git("rev-parse")
+            .add_option(args(&["--verify", "--quiet", format!("{}:{}", commit, path).as_str()]));
+        cmd.run().ok().map(|o| o.trim().to_string())
+    }
+
+    fn blob(&self, id: &str) -> Result<String, RepoStoreError> {
+        let cmd = self.git("cat-file")
+            .add_option(args(&["blob", id]));
+        match cmd.run() {
+            Ok(o) => Ok(o),
+            Err(e) => Err(RepoStoreError::Read(Box::new(e))),
+        }
+    }
+
+    /// Id of a blob of `content` in the object format of the repository, without storing it
+    fn hash_blob(&self, content: &str) -> Result<String, RepoStoreError> {
+        let cmd = self.git("hash-object")
+            .add_option(args(&["--stdin"]));
+        match cmd.stdin(content).run() {
+            Ok(o) => Ok(o.trim().to_string()),
+            Err(e) => Err(RepoStoreError::Read(Box::new(e))),
+        }
+    }
+
+    /// Null object id in the object format of the repository, removing a path in `update-index --index-info`
+    fn null_id(&self) -> Result<String, RepoStoreError> {
+        let cmd = self.git("rev-parse")
+            .add_option(args(&["--show-object-format"]));
+        match cmd.run() {
+            Ok(o) if o.trim() == "sha256" => Ok("0".repeat(64)),
+            Ok(_) => Ok("0".repeat(40)),
+            Err(e) => Err(RepoStoreError::Commit(Box::new(e))),
+        }
+    }
+
+    /// Stores a blob of `content`, returning its id
+    fn write_blob(&self, content: &str) -> Result<String, RepoStoreError> {
+        let cmd = self.git("hash-object")
+            .add_option(args(&["-w", "--stdin"]));
+        match cmd.stdin(content).run() {
+            Ok(o) => Ok(o.trim().to_string()),
+            Err(e) => Err(RepoStoreError::Write(Box::new(e))),
+        }
+    }
+
+    /// Current revision of a document as seen by this store, pending writes included
+    fn current(&self, pending: &BTreeMap<String, Pending>, path: &str) -> Result<Option<String>, RepoStoreError> {
+        match pending.get(path) {
+            Some(Pending { content: Some(content), .. }) => Ok(Some(self.hash_blob(content)?)),
+            Some(Pending { content: None, .. }) => Ok(None),
+            None => Ok(self.blob_id(self.head()?.as_deref(), path)),
+        }
+    }
+
+    fn stage(&self, path: &str, content: Option<String>, expected: Option<Option<&str>>) -> Result<(), RepoStoreError> {
+        let mut pending = self.pending();
+        let mut base = pending.get(path).and_then(|p| p.base.clone());
+        if let Some(expected) = expected {
+            if self.current(&pending, path)?.as_deref() != expected {
+                return Err(RepoStoreError::RevisionConflict(String::from(path)));
+            }
+            if base.is_none() {
+                base = Some(self.blob_id(self.head()?.as_deref(), path));
+            }
+        }
+        pending.insert(String::from(path), Pending { content, base });
+        Ok(())
+    }
+
+    /// Stages `document`, conditionally on its current revision when `expected` is given
+    fn write_staged(&self, path: &str, document: &mut Document<Map<String, Value>>, expected: Option<Option<&str>>) -> Result<(), RepoStoreError> {
+        let mut buffer = Vec::new();
+        if let Err(e) = document.write(&mut buffer, map_into_string) {
+            return Err(RepoStoreError::Write(Box::new(e)));
+        }
+        let content = String::from_utf8_lossy(&buffer).to_string();
+        let revision = self.hash_blob(&content)?;
+        self.stage(path, Some(content), expected)?;
+        document.set_revision(Some(revision));
+        Ok(())
+    }
+
+    /// Paths under `dir` in the tree of the branch
+    fn tree_paths(&self, dir: &str) -> Result<Vec<String>, RepoStoreError> {
+        let Some(head) = self.head()? else {
+            return Ok(vec![]);
+        };
+        let cmd = self.git("ls-tree")
+            .add_option(args(&["-r", "-z", "--name-only", head.as_str(), "--", dir]));
+        match cmd.run() {
+            Ok(o) => Ok(o.split('\0').filter(|p| !p.is_empty()).map(String::from).collect()),
+            Err(e) => Err(RepoStoreError::Read(Box::new(e))),
+        }
+    }
+
+    /// Content of `path` in the tree of `commit`, `None` when absent
+    fn file(&self, commit: Option<&str>, path: &str) -> Result<Option<String>, RepoStoreError> {
+        match self.blob_id(commit, path) {
+            Some(id) => Ok(Some(self.blob(&id)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Commits the pending writes selected by `filter`, retrying while other writers move the branch
+    fn commit_pending(&self, msg: &str, options: &CommitOptions, filter: &dyn Fn(&str) -> bool) -> Result<(), RepoStoreError> {
+        let mut pending = self.pending();
+        let selected: Vec<(String, Pending)> = pending.iter()
+            .filter(|(path, _)| filter(path))
+            .map(|(path, p)| (path.clone(), p.clone()))
+            .collect();
+        if selected.is_empty() {
+            return Err(RepoStoreError::Commit("nothing to commit".into()));
+        }
+        let mut index_info = String::new();
+        let mut null_id = None;
+        for (path, p) in &selected {
+            match &p.content {
+                Some(content) => index_info.push_str(format!("100644 {}\t{}\n", self.write_blob(content)?, path).as_str()),
+                None => {
+                    let null_id = match &null_id {
+                        Some(id) => id,
+                        None => null_id.insert(self.null_id()?),
+                    };
+                    index_info.push_str(format!("0 {}\t{}\n", null_id, path).as_str())
+                },
+            }
+        }
+        let mut last_error = None;
+        for _ in 0..COMMIT_ATTEMPTS {
+            let old = self.head()?;
+            for (path, p) in &selected {
+                if let Some(base) = &p.base
+                    && self.blob_id(old.as_deref(), path) != *base {
+                    return Err(RepoStoreError::RevisionConflict(path.clone()));
+                }
+            }
+            let mut trailers = options.trailers(Operation::Commit);
+            for (path, p) in &selected {
+                trailers.change(path, self.file(old.as_deref(), path)?.as_deref(), p.content.as_deref());
+            }
+            let parents: Vec<String> = old.iter().cloned().collect();
+            let commit = self.write_commit(trailers.message(msg)?.as_str(), old.as_deref(), &parents, &index_info, options)?;
+            match self.update_head(&commit, old.as_deref()) {
+                Ok(_) => {
+                    for (path, _) in &selected {
+                        pending.remove(path);
+                    }
+                    return Ok(());
+                },
+                // the branch moved since it was read
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(RepoStoreError::Commit(match last_error {
+            Some(e) => Box::new(e),
+            None => "branch kept moving".into(),
+        }))
+    }
+
+    /// Writes a commit of `parents` applying `index_info` on top of the tree of `base`, through an index private to this commit.
+    /// A commit with a single parent must change its tree.
+    fn write_commit(&self, msg: &str, base: Option<&str>, parents: &[String], index_info: &str, options: &CommitOptions) -> Result<String, RepoStoreError> {
+        let index = self.repo_path.join(format!("gitobi-index-{}-{}", std::process::id(), INDEX_COUNTER.fetch_add(1, Ordering::Relaxed)));
+        let written = self.write_commit_with(&index, msg, base, parents, index_info, options);
+        let _ = fs::remove_file(&index);
+        written
+    }
+
+    fn write_commit_with(&self, index: &Path, msg: &str, base: Option<&str>, parents: &[String], index_info: &str, options: &CommitOptions) -> Result<String, RepoStoreError> {
+        let index_env = vec![(String::from("GIT_INDEX_FILE"), index.to_string_lossy().to_string())];
+        let run = |cmd: EnvCommand| -> Result<String, RepoStoreError> {
+            match cmd.envs(index_env.clone()).run() {
+                Ok(o) => Ok(o.trim().to_string()),
+                Err(e) => Err(RepoStoreError::Commit(Box::new(e))),
+            }
+        };
+        run(self.git("read-tree").add_option(args(&[base.unwrap_or("--empty")])))?;
+        run(self.git("update-index").add_option(args(&["--index-info"])).stdin(index_info))?;
+        let tree = run(self.git("write-tree"))?;
+        if let [parent] = parents
+            && run(self.git("rev-parse").add_option(args(&[format!("{}^{{tree}}", parent).as_str()])))? == tree {
+            return Err(RepoStoreError::Commit("nothing to commit".into()));
+        }
+        let mut commit_tree = self.git("commit-tree")
+            .add_option(args(&[tree.as_str(), "-m", msg]));
+        if self.commit.commit_signing().is_some() {
+            commit_tree = commit_tree.add_option(args(&["-S"]));
+        }
+        for parent in parents {
+            commit_tree = commit_tree.add_option(args(&["-p", parent]));
+        }
+        run(commit_tree.envs(self.identity_env(options)))
+    }
+
+    /// Identity of the commits and tags written, there being no repository configuration to hold it.
+    /// The store identity is used unless `options` name an author or committer.
+    fn identity_env(&self, options: &CommitOptions) -> Vec<(String, String)> {
+        let identity = self.commit.identity();
+        let mut env = options.author_identity().unwrap_or(&identity).author_env();
+        env.extend(options.committer_identity().unwrap_or(&identity).committer_env());
+        env
+    }
+}
+
+impl RepoStore<Map<String, Value>> for BareGitStore {
+    fn initialize(&self) -> Result<(), RepoStoreError> {
+        if self.repo_path.exists() {
+            let cmd = self.git("rev-parse")
+                .add_option(args(&["--is-bare-repository"]));
+            return match cmd.run() {
+                Ok(o) if o.trim() == "true" => Ok(()),
+                Ok(_) => Err(RepoStoreError::Initialize(format!("{} is not a bare repository", self.repo_dir()).into())),
+                Err(e) => Err(RepoStoreError::Initialize(Box::new(e))),
+            };
+        }
+        if let Err(e) = fs::create_dir_all(&self.repo_path) {
+            return Err(RepoStoreError::Initialize(Box::new(e)));
+        }
+        let cmd = self.git("init")
+            .add_option(args(&["--bare", format!("--initial-branch={}", self.branch()).as_str()]));
+        match cmd.run() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(RepoStoreError::Initialize(Box::new(e))),
+        }
+    }
+
+    fn document(&self, path: &str) -> Result<Document<Map<String, Value>>, RepoStoreError> {
+        let pending = self.pending();
+        let (content, revision) = match pending.get(path) {
+            Some(_) if self.require_signatures => return Err(RepoStoreError::UntrustedDocument(String::from(path))),
+            Some(Pending { content: Some(content), .. }) => (content.clone(), self.hash_blob(content)?),
+            Some(Pending { content: None, .. }) => return Err(RepoStoreError::Read(format!("{} removed", path).into())),
+            None => {
+                let head = self.head()?;
+                match (self.blob_id(head.as_deref(), path), head) {
+                    (Some(id), Some(head)) => {
+                        if self.require_signatures {
+                            self.verify_document(path, &head)?;
+                        }
+                        (self.blob(&id)?, id)
+                    },
+                    _ => return Err(RepoStoreError::Read(format!("{} not found", path).into())),
+                }
+            },
+        };
+        match Document::load(&mut content.as_bytes(), map_from_str) {
+            Ok(mut doc) => {
+                doc.set_revision(Some(revision));
+                Ok(doc)
+            },
+            Err(e) => Err(RepoStoreError::Read(Box::new(e))),
+        }
+    }
+
+    fn write_document(&self, path: &str, document: &mut Document<Map<String, Value>>) -> Result<(), RepoStoreError> {
+        self.write_staged(path, document, None)
+    }
+
+    fn remove_document(&self, path: &str) -> Result<(), RepoStoreError> {
+        if self.revision(path)?.is_none() {
+            return Err(RepoStoreError::Write(format!("{} not found", path).into()));
+        }
+        self.stage(path, None, None)
+    }
+
+    fn write_document_if(&self, path: &str, document: &mut Document<Map<String, Value>>, expected: Option<&str>) -> Result<(), RepoStoreError> {
+        self.write_staged(path, document, Some(expected))
+    }
+
+    fn remove_document_if(&self, path: &str, expected: &str) -> Result<(), RepoStoreError> {
+        self.stage(path, None, Some(Some(expected)))
+    }
+
+    fn revision(&self, path: &str) -> Result<Option<String>, RepoStoreError> {
+        self.current(&self.pending(), path)
+    }
+
+    fn documents(&self, dir: &str) -> Result<Vec<String>, RepoStoreError> {
+        let pending = self.pending();
+        let mut paths = self.tree_paths(dir)?;
+        let exists = dir.is_empty() || !paths.is_empty() || pending.keys().any(|p| under(p, dir));
+        if !exists {
+            return Err(RepoStoreError::Read(format!("directory {} not found", dir).into()));
+        }
+        paths.extend(pending.iter().filter(|(_, p)| p.content.is_some()).map(|(path, _)| path.clone()));
+        paths.retain(|path| !matches!(pending.get(path), Some(Pending { content: None, .. })));
+        let prefix = if dir.is_empty() { String::new() } else { format!("{}/", dir.trim_end_matches('/')) };
+        let mut names: Vec<String> = paths.iter()
+            .filter_map(|path| path.strip_prefix(prefix.as_str()))
+            .filter(|name| !name.contains('/') && !name.starts_with('.'))
+            .map(String::from)
+            .collect();
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    fn create_dir(&self, dir: &str) -> Result<(), RepoStoreError> {
+        // git does not track empty directories
+        self.stage(format!("{}/.gitkeep", dir.trim_end_matches('/')).as_str(), Some(String::new()), None)
+    }
+
+    fn remove_dir(&self, dir: &str) -> Result<(), RepoStoreError> {
+        let mut paths = self.tree_paths(dir)?;
+        paths.extend(self.pending().iter().filter(|(p, _)| under(p, dir)).map(|(path, _)| path.clone()));
+        if paths.is_empty() {
+            return Err(RepoStoreError::Write(format!("directory {} not found", dir).into()));
+        }
+        for path in paths {
+            self.stage(&path, None, None)?;
+        }
+        Ok(())
+    }
+
+    fn document_at(&self, path: &str, revision: &Revision) -> Result<Document<Map<String, Value>>, RepoStoreError> {
+        let commit = self.resolve(revision)?;
+        if self.require_signatures {
+            self.verify_document(path, &commit)?;
+        }
+        let Some(id) = self.blob_id(Some(&commit), path) else {
+            return Err(RepoStoreError::Read(format!("{} not found at {}", path, commit).into()));
+        };
+        match Document::load(&mut self.blob(&id)?.as_bytes(), map_from_str) {
+            Ok(mut doc) => {
+                doc.set_revision(Some(id));
+                Ok(doc)
+            },
+            Err(e) => Err(RepoStoreError::Read(Box::new(e))),
+        }
+    }
+
+    fn revision_at(&self, path: &str, revision: &Revision) -> Result<Option<String>, RepoStoreError> {
+        let commit = self.resolve(revision)?;
+        Ok(self.blob_id(Some(&commit), path))
+    }
+
+    /// Resolves `revision` into a commit id, `HEAD` and timestamps on the history of the store branch
+    fn resolve(&self, revision: &Revision) -> Result<String, RepoStoreError> {
+        let cmd = match revision {
+            // HEAD of the repository is its default branch
+            Revision::Rev(rev) if rev == "HEAD" => self.git("rev-parse")
+                .add_option(args(&["--verify", "--quiet", format!("{}^{{commit}}", self.ref_name()).as_str()])),
+            Revision::Rev(rev) => self.git("rev-parse")
+                .add_option(args(&["--verify", "--quiet", format!("{}^{{commit}}", rev).as_str()])),
+            Revision::AsOf(timestamp) => self.git("rev-list")
+                .add_option(args(&["-1", format!("--before=@{}", timestamp).as_str(), self.ref_name().as_str()])),
+        };
+        match cmd.run() {
+            Ok(o) if !o.trim().is_empty() => Ok(o.trim().to_string()),
+            Ok(_) => Err(RepoStoreError::History(format!("no commit found for {:?}", revision).into())),
+            Err(e) => Err(RepoStoreError::History(Box::new(e))),
+        }
+    }
+
+    fn revisions(&self, path: &str) -> Result<Vec<DocumentRevision>, RepoStoreError> {
+        if self.head()?.is_none() {
+            return Ok(vec![]);
+        }
+        let cmd = self.git("log")
+            .add_option(args(&[LOG_FORMAT, self.ref_name().as_str(), "--", path]));
+        match cmd.run() {
+            Ok(o) => Ok(parse_log(&o)),
+            Err(e) => Err(RepoStoreError::History(Box::new(e))),
+        }
+    }
+
+    fn commits(&self, from: Option<&Revision>, to: &Revision) -> Result<Vec<DocumentRevision>, RepoStoreError> {
+        if *to == Revision::rev("HEAD") && self.head()?.is_none() {
+            return Ok(vec![]);
+        }
+        let mut range = self.resolve(to)?;
+        if let Some(from) = from {
+            range = format!("{}..{}", self.resolve(from)?, range);
+        }
+        let cmd = self.git("log")
+            .add_option(args(&[LOG_FORMAT, "--first-parent", "--reverse", range.as_str()]));
+        match cmd.run() {
+            Ok(o) => Ok(parse_log(&o)),
+            Err(e) => Err(RepoStoreError::History(Box::new(e))),
+        }
+    }
+
+    fn document_commits(&self, path: &str, to: &Revision) -> Result<Vec<DocumentRevision>, RepoStoreError> {
+        if *to == Revision::rev("HEAD") && self.head()?.is_none() {
+            return Ok(vec![]);
+        }
+        let to = self.resolve(to)?;
+        let cmd = self.git("log")
+            .add_option(args(&[LOG_FORMAT, "--first-parent", "--reverse", to.as_str(), "--", path]));
+        match cmd.run() {
+            Ok(o) => Ok(parse_log(&o)),
+            Err(e) => Err(RepoStoreError::History(Box::new(e))),
+        }
+    }
+
+    /// Does nothing, the bare repository being the shared copy
+    fn pull(&self, _rebase: bool) -> Result<(), RepoStoreError> {
+        Ok(())
+    }
+
+    /// Does nothing, the commits being made in the shared copy
+    fn push(&self) -> Result<(), RepoStoreError> {
+        Ok(())
+    }
+
+    fn commit_with(&self, msg: &str, paths: Option<&[String]>, options: &CommitOptions) -> Result<(), RepoStoreError> {
+        match paths {
+            Some(paths) => self.commit_pending(msg, options, &|path| paths.iter().any(|p| under(path, p))),
+            None => self.commit_pending(msg, options, &|_| true),
+        }
+    }
+
+    fn restore_documents(&self, paths: &[String]) -> Result<(), RepoStoreError> {
+        self.pending().retain(|path, _| !paths.iter().any(|p| under(path, p)));
+        Ok(())
+    }
+
+    /// Discards the uncommitted writes, whatever the mode as there are no unpushed commits
+    fn clean(&self, _mode: CleanMode) -> Result<Vec<String>, RepoStoreError> {
+        let mut pending = self.pending();
+        let discarded = pending.keys().cloned().collect();
+        pending.clear();
+        Ok(discarded)
+    }
+
+    fn current_branch(&self) -> Result<String, RepoStoreError> {
+        Ok(self.branch().clone())
+    }
+
+    fn branches(&self) -> Result<Vec<String>, RepoStoreError> {
+        let cmd = self.git("for-each-ref")
+            .add_option(args(&["--format=%(refname:short)", "refs/heads/"]));
+        match cmd.run() {
+            Ok(o) => {
+                let mut branches: Vec<String> = o.lines().filter(|b| !b.is_empty()).map(String::from).collect();
+                branches.sort();
+                Ok(branches)
+            },
+            Err(e) => Err(RepoStoreError::Branch(Box::new(e))),
+        }
+    }
+
+    fn create_branch(&self, name: &str, from: &Revision) -> Result<(), RepoStoreError> {
+        let commit = self.resolve(from)?;
+        if let Err(e) = self.git("check-ref-format").add_option(args(&["--branch", name])).run() {
+            return Err(RepoStoreError::Branch(Box::new(e)));
+        }
+        // an empty old value only creates the branch
+        let cmd = self.git("update-ref")
+            .add_option(args(&[format!("refs/heads/{}", name).as_str(), commit.as_str(), ""]));
+        match cmd.run() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(RepoStoreError::Branch(Box::new(e))),
+        }
+    }
+
+    fn switch_branch(&self, name: &str) -> Result<(), RepoStoreError> {
+        let pending = self.pending();
+        if !pending.is_empty() {
+            return Err(RepoStoreError::Branch("uncommitted writes would be lost, commit or clean them first".into()));
+        }
+        if self.ref_target(format!("refs/heads/{}", name).as_str())?.is_none() {
+            return Err(RepoStoreError::Branch(format!("branch {} not found", name).into()));
+        }
+        *self.branch() = String::from(name);
+        Ok(())
+    }
+
+    fn delete_branch(&self, name: &str) -> Result<(), RepoStoreError> {
+        if *self.branch() == name {
+            return Err(RepoStoreError::Branch(format!("cannot delete the active branch {}", name).into()));
+        }
+        let ref_name = format!("refs/heads/{}", name);
+        let Some(old) = self.ref_target(&ref_name)? else {
+            return Err(RepoStoreError::Branch(format!("branch {} not found", name).into()));
+        };
+        let cmd = self.git("update-ref")
+            .add_option(args(&["-d", ref_name.as_str(), old.as_str()]));
+        match cmd.run() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(RepoStoreError::Branch(Box::new(e))),
+        }
+    }
+
+    fn merge_branch(&self, name: &str) -> Result<(), RepoStoreError> {
+        let Some(theirs) = self.ref_target(format!("refs/heads/{}", name).as_str())? else {
+            return Err(RepoStoreError::Branch(format!("branch {} not found", name).into()));
+        };
+        let msg = format!("Merge branch '{}'", name);
+        let mut last_error = None;
+        for _ in 0..COMMIT_ATTEMPTS {
+            let ours = self.head()?;
+            let target = match &ours {
+                Some(ours) if self.is_ancestor(&theirs, ours) => return Ok(()),
+                Some(ours) if !self.is_ancestor(ours, &theirs) => match self.merge_commits(ours, &theirs, &msg)? {
+                    Ok(commit) => commit,
+                    Err(conflicts) => return Err(RepoStoreError::MergeConflict(conflicts)),
+                },
+                _ => theirs.clone(),
+            };
+            match self.update_head(&target, ours.as_deref()) {
+                Ok(_) => return Ok(()),
+                // the branch moved since it was read
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(RepoStoreError::Branch(match last_error {
+            Some(e) => Box::new(e),
+            None => "branch kept moving".into(),
+        }))
+    }
+
+    fn create_snapshot(&self, name: &str, msg: &str) -> Result<(), RepoStoreError> {
+        let Some(head) = self.head()? else {
+            return Err(RepoStoreError::Snapshot("nothing committed yet".into()));
+        };
+        let cmd = self.git("tag")
+            .add_option(args(&["--annotate", "--message", msg, name, head.as_str()]))
+            .envs(self.identity_env(&CommitOptions::new()));
+        match cmd.run() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(RepoStoreError::Snapshot(Box::new(e))),
+        }
+    }
+
+    fn snapshots(&self) -> Result<Vec<Snapshot>, RepoStoreError> {
+        let cmd = self.git("for-each-ref")
+            .add_option(args(&[TAG_FORMAT, "refs/tags/"]));
+        match cmd.run() {
+            Ok(o) => Ok(parse_tags(&o)),
+            Err(e) => Err(RepoStoreError::Snapshot(Box::new(e))),
+        }
+    }
+
+    fn restore_snapshot(&self, name: &str, msg: &str) -> Result<(), RepoStoreError> {
+        let Some(snapshot) = self.snapshots()?.into_iter().find(|s| s.name() == name) else {
+            return Err(RepoStoreError::Snapshot(format!("snapshot {} not found", name).into()));
+        };
+        self.pending().clear();
+        let mut last_error = None;
+        for _ in 0..COMMIT_ATTEMPTS {
+            let old = self.head()?;
+            let mut trailers = CommitOptions::new().trailers(Operation::Restore);
+            let from = old.as_deref().map(Revision::rev);
+            for change in self.changed_paths(from.as_ref(), &Revision::rev(snapshot.commit()))? {
+                let path = change.path();
+                trailers.change(path, self.file(old.as_deref(), path)?.as_deref(), self.file(Some(snapshot.commit()), path)?.as_deref());
+            }
+            let parents: Vec<String> = old.iter().cloned().collect();
+            let commit = self.write_commit(trailers.message(msg)?.as_str(), Some(snapshot.commit()), &parents, "", &CommitOptions::new())?;
+            match self.update_head(&commit, old.as_deref()) {
+                Ok(_) => return Ok(()),
+                // the branch moved since it was read
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(RepoStoreError::Commit(match last_error {
+            Some(e) => Box::new(e),
+            None => "branch kept moving".into(),
+        }))
+    }
+
+    fn paths_at(&self, revision: &Revision) -> Result<Vec<String>, RepoStoreError> {
+        let commit = self.resolve(revision)?;
+        let cmd = self.git("ls-tree")
+            .add_option(args(&["-r", "-z", "--name-only", commit.as_str()]));
+        match cmd.run() {
+            Ok(o) => {
+                let mut paths: Vec<String> = o.split('\0').filter(|p| !p.is_empty()).map(String::from).collect();
+                paths.sort();
+                Ok(paths)
+            },
+            Err(e) => Err(RepoStoreError::History(Box::new(e))),
+        }
+    }
+
+    fn changed_paths(&self, from: Option<&Revision>, to: &Revision) -> Result<Vec<PathChange>, RepoStoreError> {
+        let Some(from) = from else {
+            return Ok(self.paths_at(to)?.into_iter().map(PathChange::Added).collect());
+        };
+        let (from, to) = (self.resolve(from)?, self.resolve(to)?);
+        let cmd = self.git("diff-tree")
+            .add_option(args(&["-r", "-z", "--name-status", "--no-renames", from.as_str(), to.as_str()]));
+        match cmd.run() {
+            Ok(o) => Ok(parse_name_status(&o)),
+            Err(e) => Err(RepoStoreError::History(Box::new(e))),
+        }
+    }
+
+    fn head(&self) -> Result<Option<String>, RepoStoreError> {
+        self.ref_target(self.ref_name().as_str())
+    }
+}
@@ -0,0 +1,67 @@
+use crate::history::{DocumentRevision, Revision};
+use crate::json_document::{get_key, leaf_keys};
+use crate::repo_store::{RepoStore, RepoStoreError};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// The revision that last changed the value of a key
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyBlame {
+    key: String,
+    value: Value,
+    revision: DocumentRevision,
+}
+
+impl KeyBlame {
+    /// Dotted key
+    pub fn key(&self) -> &str {
+        self.key.as_str()
+    }
+
+    /// Current value of the key
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    pub fn revision(&self) -> &DocumentRevision {
+        &self.revision
+    }
+}
+
+/// Blames every leaf key of the current version of the document at `path`, sorted by key.
+/// The document is compared with [get_key] along the first-parent history of the active branch, loading it only
+/// at the commits [RepoStore::document_commits] lists, so a key blamed to a revision got the value it still holds in that revision.
+/// Keys changed on a merged branch are blamed to the merge commit that brought them into the active branch.
+/// Revisions deleting the document count as an empty document, failing to load it at any other revision fails the blame.
+pub fn blame<S: RepoStore<Map<String, Value>>>(store: &S, path: &str) -> Result<Vec<KeyBlame>, RepoStoreError> {
+    let mut blamed: BTreeMap<String, KeyBlame> = BTreeMap::new();
+    let mut previous = Map::new();
+    for revision in store.document_commits(path, &Revision::rev("HEAD"))? {
+        let at = Revision::rev(revision.id());
+        let current = match store.revision_at(path, &at)? {
+            Some(_) => store.document_at(path, &at)?.content().clone(),
+            None => Map::new(),
+        };
+        if current == previous {
+            continue;
+        }
+        let keys = leaf_keys(&current);
+        blamed.retain(|key, _| keys.contains(key));
+        for key in keys {
+            let value = get_key(&key, &current).unwrap_or(Value::Null);
+            let changed = match get_key(&key, &previous) {
+                Ok(previous_value) => previous_value != value,
+                Err(_) => true,
+            };
+            if changed || !blamed.contains_key(&key) {
+                blamed.insert(key.clone(), KeyBlame {
+                    key,
+                    value,
+                    revision: revision.clone(),
+                });
+            }
+        }
+        previous = current;
+    }
+    Ok(blamed.into_values().collect())
+}
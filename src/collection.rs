@@ -0,0 +1,101 @@
+use crate::json_document::Document;
+use crate::query::{QueryClause, QueryData};
+use crate::query_key::QCKey;
+use crate::repo_store::{RepoStore, RepoStoreError};
+use serde_json::{Map, Value};
+
+pub const DOCUMENT_EXTENSION: &str = ".json";
+
+/// A named group of JSON documents stored as the files of one directory.
+/// Each document id maps to the file `<collection>/<id>.json`.
+pub struct Collection<'a, S: RepoStore<Map<String, Value>>> {
+    store: &'a S,
+    name: String,
+}
+
+impl<'a, S: RepoStore<Map<String, Value>>> Collection<'a, S> {
+    /// Opens an existing collection
+    pub fn open(store: &'a S, name: &str) -> Result<Self, RepoStoreError> {
+        validate_name(name)?;
+        Ok(Self {
+            store,
+            name: String::from(name),
+        })
+    }
+
+    /// Creates the collection directory, returning the opened collection
+    pub fn create(store: &'a S, name: &str) -> Result<Self, RepoStoreError> {
+        let collection = Self::open(store, name)?;
+        store.create_dir(name)?;
+        Ok(collection)
+    }
+
+    /// Removes the collection directory together with all its documents
+    pub fn drop_collection(self) -> Result<(), RepoStoreError> {
+        self.store.remove_dir(self.name.as_str())
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Lists the ids of all the documents in the collection
+    pub fn ids(&self) -> Result<Vec<String>, RepoStoreError> {
+        let names = self.store.documents(self.name.as_str())?;
+        Ok(names.iter()
+            .filter_map(|n| n.strip_suffix(DOCUMENT_EXTENSION))
+            .map(String::from)
+            .collect())
+    }
+
+    pub fn get(&self, id: &str) -> Result<Document<Map<String, Value>>, RepoStoreError> {
+        self.store.document(self.document_path(id)?.as_str())
+    }
+
+    pub fn put(&self, id: &str, document: &mut Document<Map<String, Value>>) -> Result<(), RepoStoreError> {
+        self.store.write_document(self.document_path(id)?.as_str(), document)
+    }
+
+    pub fn remove(&self, id: &str) -> Result<(), RepoStoreError> {
+        self.store.remove_document(self.document_path(id)?.as_str())
+    }
+
+    /// Writes the document only if its stored revision is still `expected`, see [RepoStore::write_document_if]
+    pub fn put_if(&self, id: &str, document: &mut Document<Map<String, Value>>, expected: Option<&str>) -> Result<(), RepoStoreError> {
+        self.store.write_document_if(self.document_path(id)?.as_str(), document, expected)
+    }
+
+    /// Removes the document only if its stored revision is still `expected`
+    pub fn remove_if(&self, id: &str, expected: &str) -> Result<(), RepoStoreError> {
+        self.store.remove_document_if(self.document_path(id)?.as_str(), expected)
+    }
+
+    /// Evaluates `clause` against every document in the collection, returning the ids of the matching ones.
+    /// Documents missing a key referenced by the clause do not match.
+    pub fn find<K: QCKey>(&self, clause: &QueryClause<K>) -> Result<Vec<String>, RepoStoreError> {
+        let mut found = Vec::new();
+        for id in self.ids()? {
+            let doc = self.get(id.as_str())?;
+            let value = Value::Object(doc.content().clone());
+            let qd = QueryData::load::<String>(&value);
+            if let Ok(true) = clause.eval(&qd) {
+                found.push(id);
+            }
+        }
+        Ok(found)
+    }
+
+    /// Path of the document `id` relative to the store root
+    pub fn document_path(&self, id: &str) -> Result<String, RepoStoreError> {
+        validate_name(id)?;
+        Ok(format!("{}/{}{}", self.name, id, DOCUMENT_EXTENSION))
+    }
+}
+
+fn validate_name(name: &str) -> Result<(), RepoStoreError> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        Err(RepoStoreError::InvalidPath(String::from(name)))
+    } else {
+        Ok(())
+    }
+}
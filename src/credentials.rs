@@ -0,0 +1,248 @@
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use std::error::Error;
+use std::fmt::{Debug, Formatter};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Credentials used to reach a remote repository
+#[derive(Clone, PartialEq, Eq)]
+pub enum Credential {
+    /// HTTP basic authentication with user and password
+    Basic(String, String),
+    /// HTTP bearer token
+    Bearer(String),
+    /// Command git runs instead of `ssh`, usually selecting a private key
+    SshCommand(String),
+}
+
+impl Credential {
+    /// The `Authorization` header for HTTP credentials
+    pub fn http_header(&self) -> Option<String> {
+        match self {
+            Credential::Basic(user, password) => {
+                let basic_token = BASE64_STANDARD.encode(format!("{}:{}", user, password));
+                Some(format!("Authorization: Basic {}", basic_token))
+            },
+            Credential::Bearer(token) => Some(format!("Authorization: Bearer {}", token)),
+            Credential::SshCommand(_) => None,
+        }
+    }
+
+    /// The git configuration entry applying these credentials
+    pub fn config_entry(&self) -> (&'static str, String) {
+        match self {
+            Credential::SshCommand(cmd) => ("core.sshCommand", cmd.clone()),
+            _ => ("http.extraHeader", self.http_header().unwrap_or_default()),
+        }
+    }
+}
+
+impl Debug for Credential {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Credential::Basic(user, _) => write!(f, "Basic({}, ***)", user),
+            Credential::Bearer(_) => write!(f, "Bearer(***)"),
+            Credential::SshCommand(cmd) => write!(f, "SshCommand({})", cmd),
+        }
+    }
+}
+
+/// A source of credentials for a repository url, queried every time they are needed
+pub trait CredentialProvider: Debug + Send + Sync {
+    /// The credentials for `url`, `None` when the provider has none for it
+    fn credential(&self, url: &str) -> Result<Option<Credential>, Box<dyn Error>>;
+}
+
+/// Fixed credentials
+#[derive(Debug, Clone)]
+pub struct StaticCredentials {
+    credential: Credential,
+}
+
+impl StaticCredentials {
+    pub fn new(credential: Credential) -> Self {
+        Self { credential }
+    }
+}
+
+impl CredentialProvider for StaticCredentials {
+    fn credential(&self, _url: &str) -> Result<Option<Credential>, Box<dyn Error>> {
+        Ok(Some(self.credential.clone()))
+    }
+}
+
+/// Credentials read from environment variables.
+/// A token variable takes precedence over the user and password ones.
+#[derive(Debug, Clone, Default)]
+pub struct EnvCredentials {
+    user_var: Option<String>,
+    password_var: Option<String>,
+    token_var: Option<String>,
+}
+
+impl EnvCredentials {
+    pub fn basic(user_var: &str, password_var: &str) -> Self {
+        Self {
+            user_var: Some(String::from(user_var)),
+            password_var: Some(String::from(password_var)),
+            ..Default::default()
+        }
+    }
+
+    pub fn bearer(token_var: &str) -> Self {
+        Self {
+            token_var: Some(String::from(token_var)),
+            ..Default::default()
+        }
+    }
+}
+
+impl CredentialProvider for EnvCredentials {
+    fn credential(&self, _url: &str) -> Result<Option<Credential>, Box<dyn Error>> {
+        let var = |name: &Option<String>| name.as_ref().and_then(|n| std::env::var(n).ok());
+        if let Some(token) = var(&self.token_var) {
+            return Ok(Some(Credential::Bearer(token)));
+        }
+        match (var(&self.user_var), var(&self.password_var)) {
+            (Some(user), Some(password)) => Ok(Some(Credential::Basic(user, password))),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// A secret read from a file, such as a mounted secret volume.
+/// The trimmed content is the password when a user is given, a bearer token otherwise.
+#[derive(Debug, Clone)]
+pub struct SecretFileCredentials {
+    user: Option<String>,
+    path: PathBuf,
+}
+
+impl SecretFileCredentials {
+    pub fn basic(user: &str, password_path: &Path) -> Self {
+        Self {
+            user: Some(String::from(user)),
+            path: password_path.to_path_buf(),
+        }
+    }
+
+    pub fn bearer(token_path: &Path) -> Self {
+        Self {
+            user: None,
+            path: token_path.to_path_buf(),
+        }
+    }
+}
+
+impl CredentialProvider for SecretFileCredentials {
+    fn credential(&self, _url: &str) -> Result<Option<Credential>, Box<dyn Error>> {
+        let secret = fs::read_to_string(&self.path)?.trim().to_string();
+        match &self.user {
+            Some(user) => Ok(Some(Credential::Basic(user.clone(), secret))),
+            None => Ok(Some(Credential::Bearer(secret))),
+        }
+    }
+}
+
+/// Credentials obtained through `git credential fill`, using either the helpers configured for git
+/// or a specific helper, following the git credential helper protocol
+#[derive(Debug, Clone, Default)]
+pub struct GitCredentialHelper {
+    helper: Option<String>,
+}
+
+impl GitCredentialHelper {
+    /// Uses the credential helpers configured for git
+    pub fn configured() -> Self {
+        Self { helper: None }
+    }
+
+    /// Uses only `helper`, given as a `credential.helper` value
+    pub fn new(helper: &str) -> Self {
+        Self { helper: Some(String::from(helper)) }
+    }
+}
+
+impl CredentialProvider for GitCredentialHelper {
+    fn credential(&self, url: &str) -> Result<Option<Credential>, Box<dyn Error>> {
+        let mut cmd = Command::new("git");
+        if let Some(helper) = &self.helper {
+            // an empty value resets the helpers configured elsewhere
+            cmd.args(["-c", "credential.helper=", "-c", format!("credential.helper={}", helper).as_str()]);
+        }
+        let mut child = cmd.args(["credential", "fill"])
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(format!("url={}\n\n", url).as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            // no helper provided credentials and prompting is disabled
+            return Ok(None);
+        }
+        let mut user = None;
+        let mut password = None;
+        for line in String::from_utf8(output.stdout)?.lines() {
+            match line.split_once('=') {
+                Some(("username", v)) => user = Some(v.to_string()),
+                Some(("password", v)) => password = Some(v.to_string()),
+                _ => {},
+            }
+        }
+        match (user, password) {
+            (Some(user), Some(password)) => Ok(Some(Credential::Basic(user, password))),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// SSH authentication with a private key file
+#[derive(Debug, Clone)]
+pub struct SshKeyCredentials {
+    key_path: PathBuf,
+    known_hosts: Option<PathBuf>,
+}
+
+impl SshKeyCredentials {
+    pub fn new(key_path: &Path) -> Self {
+        Self {
+            key_path: key_path.to_path_buf(),
+            known_hosts: None,
+        }
+    }
+
+    /// Verifies host keys strictly against `known_hosts` instead of the user's default file
+    pub fn known_hosts(self, known_hosts: &Path) -> Self {
+        Self {
+            known_hosts: Some(known_hosts.to_path_buf()),
+            ..self
+        }
+    }
+
+    /// The ssh command line, run by git through the shell, its paths quoted
+    pub fn ssh_command(&self) -> String {
+        let mut cmd = format!("ssh -i {} -o IdentitiesOnly=yes", shell_quote(&self.key_path));
+        if let Some(known_hosts) = &self.known_hosts {
+            cmd.push_str(format!(" -o UserKnownHostsFile={} -o StrictHostKeyChecking=yes", shell_quote(known_hosts)).as_str());
+        }
+        cmd
+    }
+}
+
+/// `path` as a single shell word, between single quotes, the quotes it contains closed, escaped and reopened
+fn shell_quote(path: &Path) -> String {
+    format!("'{}'", path.display().to_string().replace('\'', r"'\''"))
+}
+
+impl CredentialProvider for SshKeyCredentials {
+    fn credential(&self, _url: &str) -> Result<Option<Credential>, Box<dyn Error>> {
+        Ok(Some(Credential::SshCommand(self.ssh_command())))
+    }
+}
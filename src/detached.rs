@@ -0,0 +1,55 @@
+use crate::merge::{DocumentConflict, KeyConflict};
+use crate::repo_store::RepoStoreError;
+use std::error::Error;
+
+/// A [RepoStoreError] sent to another thread, the errors it wraps kept as their messages
+pub(crate) enum Detached {
+    Wrapped(fn(Box<dyn Error>) -> RepoStoreError, String),
+    InvalidPath(String),
+    Conflict(Vec<KeyConflict>),
+    RevisionConflict(String),
+    SyncFailed(u32, Box<Detached>),
+    MergeConflict(Vec<DocumentConflict>),
+    UntrustedDocument(String),
+}
+
+impl Detached {
+    pub(crate) fn detach(e: RepoStoreError) -> Self {
+        let wrapped = |variant: fn(Box<dyn Error>) -> RepoStoreError, e: Box<dyn Error>| Detached::Wrapped(variant, e.to_string());
+        match e {
+            RepoStoreError::Initialize(e) => wrapped(RepoStoreError::Initialize, e),
+            RepoStoreError::Clone(e) => wrapped(RepoStoreError::Clone, e),
+            RepoStoreError::Pull(e) => wrapped(RepoStoreError::Pull, e),
+            RepoStoreError::Push(e) => wrapped(RepoStoreError::Push, e),
+            RepoStoreError::Commit(e) => wrapped(RepoStoreError::Commit, e),
+            RepoStoreError::Clean(e) => wrapped(RepoStoreError::Clean, e),
+            RepoStoreError::Read(e) => wrapped(RepoStoreError::Read, e),
+            RepoStoreError::Write(e) => wrapped(RepoStoreError::Write, e),
+            RepoStoreError::History(e) => wrapped(RepoStoreError::History, e),
+            RepoStoreError::PushRejected(e) => wrapped(RepoStoreError::PushRejected, e),
+            RepoStoreError::Credentials(e) => wrapped(RepoStoreError::Credentials, e),
+            RepoStoreError::Branch(e) => wrapped(RepoStoreError::Branch, e),
+            RepoStoreError::Snapshot(e) => wrapped(RepoStoreError::Snapshot, e),
+            RepoStoreError::Locked(e) => wrapped(RepoStoreError::Locked, e),
+            RepoStoreError::Interrupted(e) => wrapped(RepoStoreError::Interrupted, e),
+            RepoStoreError::InvalidPath(p) => Detached::InvalidPath(p),
+            RepoStoreError::Conflict(c) => Detached::Conflict(c),
+            RepoStoreError::RevisionConflict(p) => Detached::RevisionConflict(p),
+            RepoStoreError::SyncFailed(n, e) => Detached::SyncFailed(n, Box::new(Self::detach(*e))),
+            RepoStoreError::MergeConflict(c) => Detached::MergeConflict(c),
+            RepoStoreError::UntrustedDocument(p) => Detached::UntrustedDocument(p),
+        }
+    }
+
+    pub(crate) fn attach(self) -> RepoStoreError {
+        match self {
+            Detached::Wrapped(variant, msg) => variant(msg.into()),
+            Detached::InvalidPath(p) => RepoStoreError::InvalidPath(p),
+            Detached::Conflict(c) => RepoStoreError::Conflict(c),
+            Detached::RevisionConflict(p) => RepoStoreError::RevisionConflict(p),
+            Detached::SyncFailed(n, e) => RepoStoreError::SyncFailed(n, Box::new(e.attach())),
+            Detached::MergeConflict(c) => RepoStoreError::MergeConflict(c),
+            Detached::UntrustedDocument(p) => RepoStoreError::UntrustedDocument(p),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use crate::history::{PathChange, Revision};
+use crate::json_document::{get_key, leaf_keys};
+use crate::repo_store::{RepoStore, RepoStoreError};
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+
+/// A leaf key whose value differs between two versions of a document.
+/// Missing values mean the key is absent from that version.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyChange {
+    key: String,
+    before: Option<Value>,
+    after: Option<Value>,
+}
+
+impl KeyChange {
+    /// Dotted key
+    pub fn key(&self) -> &str {
+        self.key.as_str()
+    }
+
+    pub fn before(&self) -> Option<&Value> {
+        self.before.as_ref()
+    }
+
+    pub fn after(&self) -> Option<&Value> {
+        self.after.as_ref()
+    }
+}
+
+/// A document modified between two revisions, with its changed keys
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentDiff {
+    path: String,
+    keys: Vec<KeyChange>,
+}
+
+impl DocumentDiff {
+    /// Document path
+    pub fn path(&self) -> &str {
+        self.path.as_str()
+    }
+
+    pub fn keys(&self) -> &[KeyChange] {
+        self.keys.as_slice()
+    }
+}
+
+/// The documents differing between two revisions of a store, each list sorted by path
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StoreDiff {
+    added: Vec<String>,
+    removed: Vec<String>,
+    modified: Vec<DocumentDiff>,
+}
+
+impl StoreDiff {
+    pub fn added(&self) -> &[String] {
+        self.added.as_slice()
+    }
+
+    pub fn removed(&self) -> &[String] {
+        self.removed.as_slice()
+    }
+
+    pub fn modified(&self) -> &[DocumentDiff] {
+        self.modified.as_slice()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// Compares two versions of a document leaf key by leaf key, as listed by [leaf_keys], sorted by key
+pub fn key_changes(old: &Map<String, Value>, new: &Map<String, Value>) -> Vec<KeyChange> {
+    let keys: BTreeSet<String> = leaf_keys(old).into_iter().chain(leaf_keys(new)).collect();
+    keys.into_iter()
+        .filter_map(|key| {
+            let before = get_key(&key, old).ok();
+            let after = get_key(&key, new).ok();
+            (before != after).then_some(KeyChange { key, before, after })
+        })
+        .collect()
+}
+
+/// Lists the JSON documents added, removed and modified from `from` to `to`, other files being ignored.
+/// Only the documents [RepoStore::changed_paths] reports as modified are loaded.
+pub fn diff<S: RepoStore<Map<String, Value>>>(store: &S, from: &Revision, to: &Revision) -> Result<StoreDiff, RepoStoreError> {
+    diff_from(store, Some(from), to)
+}
+
+/// Lists the JSON documents differing from `from` to `to`, every document of `to` being added when `from` is `None`
+pub(crate) fn diff_from<S: RepoStore<Map<String, Value>>>(store: &S, from: Option<&Revision>, to: &Revision) -> Result<StoreDiff, RepoStoreError> {
+    let mut diff = StoreDiff::default();
+    for change in store.changed_paths(from, to)? {
+        if !change.path().ends_with(".json") {
+            continue;
+        }
+        match (change, from) {
+            (PathChange::Added(path), _) => diff.added.push(path),
+            (PathChange::Removed(path), _) => diff.removed.push(path),
+            (PathChange::Modified(path), Some(from)) => {
+                let old = store.document_at(&path, from)?;
+                let new = store.document_at(&path, to)?;
+                let keys = key_changes(old.content(), new.content());
+                if !keys.is_empty() {
+                    diff.modified.push(DocumentDiff { path, keys });
+                }
+            },
+            (PathChange::Modified(path), None) => diff.added.push(path),
+        }
+    }
+    Ok(diff)
+}
+
+/// Lists the JSON documents added, removed and modified from the snapshot `from` to the snapshot `to`
+pub fn diff_snapshots<S: RepoStore<Map<String, Value>>>(store: &S, from: &str, to: &str) -> Result<StoreDiff, RepoStoreError> {
+    diff(store, &Revision::rev(from), &Revision::rev(to))
+}
@@ -0,0 +1,136 @@
+use crate::diff::{diff_from, key_changes, KeyChange};
+use crate::history::{DocumentRevision, Revision};
+use crate::repo_store::{RepoStore, RepoStoreError};
+use serde_json::{Map, Value};
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// A document created, updated or deleted by a commit, with its changed leaf keys.
+/// Created documents list all their keys without old values, deleted ones without new values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeEvent {
+    kind: ChangeKind,
+    path: String,
+    commit: DocumentRevision,
+    keys: Vec<KeyChange>,
+}
+
+impl ChangeEvent {
+    pub fn kind(&self) -> ChangeKind {
+        self.kind
+    }
+
+    /// Document path
+    pub fn path(&self) -> &str {
+        self.path.as_str()
+    }
+
+    /// The commit making the change
+    pub fn commit(&self) -> &DocumentRevision {
+        &self.commit
+    }
+
+    pub fn keys(&self) -> &[KeyChange] {
+        self.keys.as_slice()
+    }
+}
+
+/// Position in the history of a store up to which changes were consumed.
+/// It is the id of the last commit seen, to be persisted as a string and read back with [FeedCursor::new].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FeedCursor(String);
+
+impl FeedCursor {
+    pub fn new(commit: &str) -> Self {
+        Self(String::from(commit))
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl Display for FeedCursor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The changes between two revisions in commit order, and the cursor to resume from
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeFeed {
+    events: Vec<ChangeEvent>,
+    cursor: Option<FeedCursor>,
+}
+
+impl ChangeFeed {
+    pub fn events(&self) -> &[ChangeEvent] {
+        self.events.as_slice()
+    }
+
+    /// Cursor after the last commit read, `None` when the store has no commit yet
+    pub fn cursor(&self) -> Option<&FeedCursor> {
+        self.cursor.as_ref()
+    }
+
+    pub fn into_events(self) -> Vec<ChangeEvent> {
+        self.events
+    }
+}
+
+/// Lists the changes made to JSON documents from `from` to `to`, commit by commit along the first parent
+/// history of `to`, oldest first. Within a commit events are sorted by path.
+/// Replaying the events on the documents at `from` gives the documents at `to`, `None` starting from an empty store.
+/// Each commit is compared with the previous one through [RepoStore::changed_paths], loading only the documents it changed.
+pub fn changes<S: RepoStore<Map<String, Value>>>(store: &S, from: Option<&Revision>, to: &Revision) -> Result<ChangeFeed, RepoStoreError> {
+    let commits = store.commits(from, to)?;
+    let mut events = Vec::new();
+    let mut previous = from.cloned();
+    for commit in &commits {
+        let current = Revision::rev(commit.id());
+        let changes = diff_from(store, previous.as_ref(), &current)?;
+        let mut commit_events = Vec::new();
+        for path in changes.added().iter().cloned() {
+            let keys = key_changes(&Map::new(), store.document_at(&path, &current)?.content());
+            commit_events.push(ChangeEvent { kind: ChangeKind::Created, path, commit: commit.clone(), keys });
+        }
+        if let Some(previous) = &previous {
+            for path in changes.removed().iter().cloned() {
+                let keys = key_changes(store.document_at(&path, previous)?.content(), &Map::new());
+                commit_events.push(ChangeEvent { kind: ChangeKind::Deleted, path, commit: commit.clone(), keys });
+            }
+        }
+        for document in changes.modified() {
+            commit_events.push(ChangeEvent {
+                kind: ChangeKind::Updated,
+                path: document.path().to_string(),
+                commit: commit.clone(),
+                keys: document.keys().to_vec(),
+            });
+        }
+        commit_events.sort_by(|a, b| a.path.cmp(&b.path));
+        events.append(&mut commit_events);
+        previous = Some(current);
+    }
+    let cursor = match (commits.last(), from) {
+        (Some(commit), _) => Some(FeedCursor::new(commit.id())),
+        // nothing new, the cursor stays on the commit of `from`, HEAD having none before the first commit
+        (None, Some(from)) if *from == Revision::rev("HEAD") => store.head()?.map(|id| FeedCursor::new(id.as_str())),
+        (None, Some(from)) => Some(FeedCursor::new(store.resolve(from)?.as_str())),
+        (None, None) => None,
+    };
+    Ok(ChangeFeed { events, cursor })
+}
+
+/// Lists the changes committed on the active branch since `cursor`, from the beginning of its history when `None`.
+/// The cursor of the feed resumes after the last change returned.
+pub fn changes_since<S: RepoStore<Map<String, Value>>>(store: &S, cursor: Option<&FeedCursor>) -> Result<ChangeFeed, RepoStoreError> {
+    let from = cursor.map(|c| Revision::rev(c.as_str()));
+    changes(store, from.as_ref(), &Revision::rev("HEAD"))
+}
@@ -0,0 +1,333 @@
+use crate::backend::{CloneOptions, GitBackend, GitBackendError, GitwrapBackend, Remote};
+use crate::credentials::Credential;
+use crate::repo_store::Identity;
+use git2::build::{CheckoutBuilder, RepoBuilder};
+use git2::{AnnotatedCommit, AttrCheckFlags, BranchType, CertificateCheckStatus, ErrorCode, FetchOptions, Index, IndexAddOption, ObjectType, Oid, PushOptions, RebaseOptions, RemoteCallbacks, Repository, Signature, StatusOptions};
+use std::path::Path;
+
+/// Runs the operations of [GitBackend] in process with libgit2: cloning, status, commits with their trailers,
+/// fetches, pulls and pushes. The other operations of a [crate::repo_store::GitStore] still run the `git` command line,
+/// which must be installed: reading history and documents at past revisions, branches, snapshots,
+/// cleaning and the repository configuration. Reading, writing and committing the current documents spawns no process,
+/// unless their signatures are verified, the repository uses sha256 or its attributes or `core.autocrlf`
+/// convert the documents on checkin: their revisions are then hashed by `git hash-object`.
+/// Only the transports libgit2 was built with are available, enabling the `https` or `ssh` features
+/// of the `git2` crate adds them. Credentials given as an ssh command and commit signing are not supported.
+/// Of the [CloneOptions], only the depth is, with network transports.
+/// Conflicts are left for the git command line to resolve: a rebase stopping on a conflict
+/// falls back to a merge, which is left in progress like `git pull --no-rebase` does.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Git2Backend;
+
+impl From<git2::Error> for GitBackendError {
+    fn from(e: git2::Error) -> Self {
+        GitBackendError::Git(Box::new(e))
+    }
+}
+
+fn open(path: &Path) -> Result<Repository, GitBackendError> {
+    match Repository::open(path) {
+        Ok(repo) if !repo.is_bare() => Ok(repo),
+        _ => Err(GitBackendError::NotARepository(path.to_path_buf())),
+    }
+}
+
+fn callbacks(remote: &Remote) -> RemoteCallbacks<'static> {
+    let mut callbacks = RemoteCallbacks::new();
+    if remote.is_insecure() {
+        callbacks.certificate_check(|_, _| Ok(CertificateCheckStatus::CertificateOk));
+    }
+    callbacks
+}
+
+/// The `Authorization` header sent with every request, as the command line backend does
+fn headers(remote: &Remote) -> Result<Vec<String>, GitBackendError> {
+    match remote.credential() {
+        Some(Credential::SshCommand(_)) => Err(GitBackendError::Unsupported(String::from("ssh command credentials"))),
+        Some(credential) => Ok(credential.http_header().into_iter().collect()),
+        None => Ok(vec![]),
+    }
+}
+
+fn fetch_options(remote: &Remote, headers: &[String]) -> FetchOptions<'static> {
+    let headers: Vec<&str> = headers.iter().map(String::as_str).collect();
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(callbacks(remote));
+    options.custom_headers(&headers);
+    options
+}
+
+/// Whether the blob of `file` may differ from its content: the object format is not sha1,
+/// or the attributes or configuration of the repository set filters or end of line conversions for it
+fn converts(repo: &Repository, file: &str) -> Result<bool, GitBackendError> {
+    let config = repo.config()?;
+    if config.get_string("extensions.objectFormat").is_ok_and(|format| !format.eq_ignore_ascii_case("sha1"))
+        || config.get_string("core.autocrlf").is_ok_and(|autocrlf| !matches!(autocrlf.as_str(), "false" | "")) {
+        return Ok(true);
+    }
+    for attr in ["filter", "text", "eol", "crlf", "ident", "working-tree-encoding"] {
+        if repo.get_attr(Path::new(file), attr, AttrCheckFlags::FILE_THEN_INDEX)?.is_some() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn conflicted_paths(index: &Index) -> Result<Vec<String>, GitBackendError> {
+    let mut paths = Vec::new();
+    for conflict in index.conflicts()? {
+        let conflict = conflict?;
+        if let Some(entry) = conflict.our.or(conflict.their).or(conflict.ancestor) {
+            paths.push(String::from_utf8_lossy(&entry.path).to_string());
+        }
+    }
+    Ok(paths)
+}
+
+/// Moves the current branch to `target`, updating the working tree without overwriting local changes
+fn fast_forward(repo: &Repository, target: Oid, msg: &str) -> Result<(), GitBackendError> {
+    let tree = repo.find_commit(target)?.into_object();
+    repo.checkout_tree(&tree, Some(CheckoutBuilder::new().safe()))?;
+    repo.head()?.set_target(target, msg)?;
+    Ok(())
+}
+
+/// Replays the local commits onto `upstream` in memory, returning the new tip or `None` on a conflict
+fn rebase(repo: &Repository, local: &AnnotatedCommit, upstream: &AnnotatedCommit, committer: &Signature) -> Result<Option<Oid>, GitBackendError> {
+    let mut options = RebaseOptions::new();
+    options.inmemory(true);
+    let mut rebase = repo.rebase(Some(local), Some(upstream), None, Some(&mut options))?;
+    let mut tip = upstream.id();
+    while let Some(operation) = rebase.next() {
+        operation?;
+        if rebase.inmemory_index()?.has_conflicts() {
+            rebase.abort()?;
+            return Ok(None);
+        }
+        match rebase.commit(None, committer, None) {
+            Ok(id) => tip = id,
+            // the change is already upstream
+            Err(e) if e.code() == ErrorCode::Applied => {},
+            Err(e) => return Err(e.into()),
+        }
+    }
+    rebase.finish(Some(committer))?;
+    Ok(Some(tip))
+}
+
+fn merge(repo: &Repository, upstream: &AnnotatedCommit, committer: &Signature) -> Result<(), GitBackendError> {
+    repo.merge(&[upstream], None, Some(CheckoutBuilder::new().safe().allow_conflicts(true)))?;
+    let mut index = repo.index()?;
+    if index.has_conflicts() {
+        return Err(GitBackendError::Conflict(conflicted_paths(&index)?));
+    }
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let head = repo.head()?.peel_to_commit()?;
+    let theirs = repo.find_commit(upstream.id())?;
+    let msg = repo.message().unwrap_or_else(|_| format!("Merge {}", upstream.id()));
+    repo.commit(Some("HEAD"), committer, committer, msg.as_str(), &tree, &[&head, &theirs])?;
+    repo.cleanup_state()?;
+    Ok(())
+}
+
+impl GitBackend for Git2Backend {
+    fn clone_repo(&self, remote: &Remote, path: &Path, branch: Option<&str>, options: &CloneOptions) -> Result<(), GitBackendError> {
+        if options.clone_filter().is_some() || options.sparse_dirs().is_some() {
+            return Err(GitBackendError::Unsupported(String::from("partial clone and sparse checkout")));
+        }
+        let headers = headers(remote)?;
+        let mut fetch = fetch_options(remote, &headers);
+        if let Some(depth) = options.clone_depth() {
+            fetch.depth(depth as i32);
+        }
+        let mut builder = RepoBuilder::new();
+        builder.fetch_options(fetch);
+        if let Some(branch) = branch {
+            builder.branch(branch);
+        }
+        builder.clone(remote.url(), path)?;
+        Ok(())
+    }
+
+    fn is_repo(&self, path: &Path) -> bool {
+        open(path).is_ok()
+    }
+
+    fn status(&self, path: &Path) -> Result<Vec<String>, GitBackendError> {
+        let repo = open(path)?;
+        let mut options = StatusOptions::new();
+        options.include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .renames_head_to_index(true);
+        let mut paths = Vec::new();
+        for entry in repo.statuses(Some(&mut options))?.iter() {
+            if let Some(path) = entry.path() {
+                paths.push(path.to_string());
+            }
+            if let Some(orig) = entry.head_to_index().and_then(|d| d.old_file().path().map(|p| p.to_string_lossy().to_string()))
+                && !paths.contains(&orig) {
+                paths.push(orig);
+            }
+        }
+        Ok(paths)
+    }
+
+    fn commit(&self, path: &Path, msg: &str, paths: Option<&[String]>, author: Option<&Identity>, committer: Option<&Identity>) -> Result<(), GitBackendError> {
+        let repo = open(path)?;
+        if repo.config()?.get_bool("commit.gpgSign").unwrap_or(false) {
+            return Err(GitBackendError::Unsupported(String::from("commit signing")));
+        }
+        let pathspec: Vec<&str> = paths.map(|p| p.iter().map(String::as_str).collect()).unwrap_or(vec!["*"]);
+        let mut index = repo.index()?;
+        index.add_all(&pathspec, IndexAddOption::DEFAULT, None)?;
+        index.update_all(&pathspec, None)?;
+        index.write()?;
+
+        let parent = match repo.head() {
+            Ok(head) => Some(head.peel_to_commit()?),
+            Err(e) if e.code() == ErrorCode::UnbornBranch => None,
+            Err(e) => return Err(e.into()),
+        };
+        let tree_id = match paths {
+            None => index.write_tree()?,
+            Some(paths) => {
+                // only the given paths change, whatever else is staged
+                let mut partial = Index::new()?;
+                if let Some(parent) = &parent {
+                    partial.read_tree(&parent.tree()?)?;
+                }
+                partial.remove_all(paths, None)?;
+                for entry in index.iter() {
+                    let entry_path = String::from_utf8_lossy(&entry.path).to_string();
+                    if paths.iter().any(|p| entry_path == *p || entry_path.starts_with(format!("{}/", p).as_str())) {
+                        partial.add(&entry)?;
+                    }
+                }
+                partial.write_tree_to(&repo)?
+            },
+        };
+        if parent.as_ref().is_some_and(|p| p.tree_id() == tree_id) {
+            return Err(GitBackendError::NothingToCommit);
+        }
+        let tree = repo.find_tree(tree_id)?;
+        let signature = |identity: Option<&Identity>| match identity {
+            Some(identity) => Signature::now(identity.name(), identity.email()),
+            None => repo.signature(),
+        };
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature(author)?, &signature(committer)?, msg, &tree, &parents)?;
+        Ok(())
+    }
+
+    fn head_file(&self, path: &Path, file: &str, _remote: Option<&Remote>) -> Result<Option<String>, GitBackendError> {
+        let repo = open(path)?;
+        let tree = match repo.head() {
+            Ok(head) => head.peel_to_tree()?,
+            Err(e) if e.code() == ErrorCode::UnbornBranch => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        match tree.get_path(Path::new(file)) {
+            Ok(entry) => {
+                let blob = entry.to_object(&repo)?.peel_to_blob()?;
+                Ok(Some(String::from_utf8_lossy(blob.content()).to_string()))
+            },
+            Err(e) if e.code() == ErrorCode::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Hashes in process unless the repository uses sha256 or converts the file on checkin,
+    /// which only the git command line supports
+    fn hash_blob(&self, path: &Path, file: &str, content: &[u8]) -> Result<String, GitBackendError> {
+        let repo = open(path)?;
+        if !converts(&repo, file)? {
+            return Ok(Oid::hash_object(ObjectType::Blob, content)?.to_string());
+        }
+        GitwrapBackend.hash_blob(path, file, content)
+    }
+
+    fn fetch(&self, path: &Path, remote: &Remote) -> Result<(), GitBackendError> {
+        let repo = open(path)?;
+        let headers = headers(remote)?;
+        let mut origin = repo.find_remote("origin")?;
+        origin.fetch::<&str>(&[], Some(&mut fetch_options(remote, &headers)), None)?;
+        Ok(())
+    }
+
+    fn fetch_history(&self, path: &Path, remote: &Remote, depth: Option<u32>) -> Result<(), GitBackendError> {
+        let repo = open(path)?;
+        if depth.is_none() && !repo.is_shallow() {
+            return Ok(());
+        }
+        let headers = headers(remote)?;
+        let mut options = fetch_options(remote, &headers);
+        // the largest depth asks libgit2 for the whole history
+        options.depth(depth.map_or(i32::MAX, |d| d as i32));
+        repo.find_remote("origin")?.fetch::<&str>(&[], Some(&mut options), None)?;
+        Ok(())
+    }
+
+    fn pull(&self, path: &Path, remote: &Remote, rebase_local: bool) -> Result<(), GitBackendError> {
+        self.fetch(path, remote)?;
+        let repo = open(path)?;
+        let head = repo.head()?;
+        let branch_name = head.shorthand().unwrap_or_default().to_string();
+        let upstream_id = match repo.find_branch(branch_name.as_str(), BranchType::Local)?.upstream()?.get().target() {
+            Some(id) => id,
+            None => return Err(GitBackendError::Unsupported(format!("symbolic upstream of {}", branch_name))),
+        };
+        let upstream = repo.find_annotated_commit(upstream_id)?;
+        let (analysis, _) = repo.merge_analysis(&[&upstream])?;
+        if analysis.is_up_to_date() {
+            return Ok(());
+        }
+        if analysis.is_fast_forward() {
+            return fast_forward(&repo, upstream_id, "pull: fast-forward");
+        }
+        let committer = repo.signature()?;
+        if rebase_local {
+            let local = repo.reference_to_annotated_commit(&head)?;
+            if let Some(tip) = rebase(&repo, &local, &upstream, &committer)? {
+                return fast_forward(&repo, tip, "pull: rebase");
+            }
+        }
+        merge(&repo, &upstream, &committer)
+    }
+
+    fn push(&self, path: &Path, remote: &Remote) -> Result<(), GitBackendError> {
+        let repo = open(path)?;
+        let headers: Vec<String> = headers(remote)?;
+        let header_refs: Vec<&str> = headers.iter().map(String::as_str).collect();
+        let head = repo.head()?;
+        let refname = head.name().unwrap_or_default().to_string();
+
+        let mut rejected = None;
+        let pushed = {
+            let mut callbacks = callbacks(remote);
+            callbacks.push_update_reference(|_, status| {
+                if let Some(status) = status {
+                    rejected = Some(status.to_string());
+                }
+                Ok(())
+            });
+            let mut options = PushOptions::new();
+            options.remote_callbacks(callbacks);
+            options.custom_headers(&header_refs);
+            repo.find_remote("origin")?.push(&[format!("{}:{}", refname, refname)], Some(&mut options))
+        };
+        match (pushed, rejected) {
+            (Err(e), _) if e.code() == ErrorCode::NotFastForward => Err(GitBackendError::Rejected(e.message().to_string())),
+            (Err(e), _) => Err(e.into()),
+            (Ok(_), Some(status)) => Err(GitBackendError::Rejected(status)),
+            (Ok(_), None) => {
+                // a branch new to the remote tracks it from now on, as `git push --set-upstream` does
+                let name = head.shorthand().unwrap_or_default();
+                let mut branch = repo.find_branch(name, BranchType::Local)?;
+                if branch.upstream().is_err() {
+                    branch.set_upstream(Some(format!("origin/{}", name).as_str()))?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use gitwrap::wrap_command::FnOptionArg;
+use gitwrap::WrapError;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+/// Plain positional arguments for git commands not covered by gitwrap options
+pub(crate) fn args(values: &[&str]) -> FnOptionArg {
+    let l_values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    FnOptionArg(Arc::new(move || l_values.clone()))
+}
+
+/// A git command taking gitwrap options that, unlike [gitwrap::wrap_command::WrapCommand],
+/// can pass extra environment variables to the child process
+#[derive(Clone)]
+pub(crate) struct EnvCommand {
+    options: Vec<FnOptionArg>,
+    envs: Vec<(String, String)>,
+    current_dir: Option<String>,
+    stdin: Option<Vec<u8>>,
+}
+
+impl EnvCommand {
+    pub(crate) fn new(cmd: &str) -> Self {
+        Self {
+            options: vec![args(&[cmd])],
+            envs: vec![],
+            current_dir: None,
+            stdin: None,
+        }
+    }
+
+    pub(crate) fn add_option(mut self, arg: FnOptionArg) -> Self {
+        self.options.push(arg);
+        self
+    }
+
+    pub(crate) fn add_options<I: IntoIterator<Item = FnOptionArg>>(mut self, args: I) -> Self {
+        self.options.extend(args);
+        self
+    }
+
+    pub(crate) fn envs<I: IntoIterator<Item = (String, String)>>(mut self, envs: I) -> Self {
+        self.envs.extend(envs);
+        self
+    }
+
+    pub(crate) fn current_dir(mut self, dir: &str) -> Self {
+        self.current_dir = Some(dir.to_string());
+        self
+    }
+
+    pub(crate) fn stdin<B: AsRef<[u8]>>(mut self, input: B) -> Self {
+        self.stdin = Some(input.as_ref().to_vec());
+        self
+    }
+
+    /// Runs the command like [gitwrap::wrap_command::WrapCommand::run], returning only its stdout so that
+    /// progress, warnings or signature checks reported on stderr never mix with the content or ids read.
+    /// The error of a failed command holds both stdout and stderr.
+    pub(crate) fn run(&self) -> Result<String, WrapError> {
+        let mut cmd = Command::new("git");
+        if let Some(dir) = &self.current_dir {
+            cmd.current_dir(dir);
+        }
+        for option in &self.options {
+            cmd.args(option.0());
+        }
+        cmd.envs(self.envs.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+        match self.output(&mut cmd) {
+            Ok(o) => Self::stdout(o),
+            Err(_) => Err(WrapError::FailedExecuteProcess(format!("{:?}", cmd))),
+        }
+    }
+
+    /// Runs the command like [EnvCommand::run] as a child process of tokio, without blocking the thread while git runs.
+    /// The command is consumed before the returned future first runs, so that the future can move to another thread.
+    /// Dropping the future does not kill a child already spawned.
+    #[cfg(feature = "async")]
+    pub(crate) fn run_async(self) -> impl Future<Output = Result<String, WrapError>> + Send + 'static {
+        use tokio::io::AsyncWriteExt;
+        let mut cmd = tokio::process::Command::new("git");
+        if let Some(dir) = &self.current_dir {
+            cmd.current_dir(dir);
+        }
+        for option in &self.options {
+            cmd.args(option.0());
+        }
+        cmd.envs(self.envs.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+        cmd.stdin(if self.stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let input = self.stdin;
+        let described = format!("{:?}", cmd.as_std());
+        async move {
+            let output = async {
+                let mut child = cmd.spawn()?;
+                if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+                    stdin.write_all(&input).await?;
+                }
+                child.wait_with_output().await
+            };
+            match output.await {
+                Ok(o) => Self::stdout(o),
+                Err(_) => Err(WrapError::FailedExecuteProcess(described)),
+            }
+        }
+    }
+
+    /// The stdout of a successful command, or the error holding its stdout and stderr
+    fn stdout(o: std::process::Output) -> Result<String, WrapError> {
+        let out = String::from_utf8_lossy(&o.stdout).to_string();
+        if o.status.success() {
+            Ok(out)
+        } else {
+            Err(WrapError::ExitStatus(out + &String::from_utf8_lossy(&o.stderr), o.status.code().unwrap_or(0)))
+        }
+    }
+
+    fn output(&self, cmd: &mut Command) -> std::io::Result<std::process::Output> {
+        let Some(input) = &self.stdin else {
+            return cmd.output();
+        };
+        let mut child = cmd.stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(input)?;
+        }
+        child.wait_with_output()
+    }
+}
+
+/// Environment variables passing configuration entries to a single git invocation,
+/// appended after any `GIT_CONFIG_COUNT` entries already present in the environment
+pub(crate) fn config_env(entries: &[(&str, String)]) -> Vec<(String, String)> {
+    let offset = std::env::var("GIT_CONFIG_COUNT").ok().and_then(|c| c.parse::<usize>().ok()).unwrap_or(0);
+    let mut envs = Vec::with_capacity(entries.len() * 2 + 1);
+    for (i, (key, value)) in entries.iter().enumerate() {
+        envs.push((format!("GIT_CONFIG_KEY_{}", offset + i), key.to_string()));
+        envs.push((format!("GIT_CONFIG_VALUE_{}", offset + i), value.clone()));
+    }
+    envs.push((String::from("GIT_CONFIG_COUNT"), (offset + entries.len()).to_string()));
+    envs
+}
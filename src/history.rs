@@ -0,0 +1,221 @@
+use crate::audit::CommitTrailers;
+use crate::signing::CommitSignature;
+
+/// A point in the history of a store
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Revision {
+    /// Any git revision: a commit id, a branch or a tag name
+    Rev(String),
+    /// The latest commit made at or before the given unix timestamp, in seconds, by the commit date
+    /// [DocumentRevision::date] reports rather than the author date, which rebases keep
+    AsOf(i64),
+}
+
+impl Revision {
+    pub fn rev(rev: &str) -> Self {
+        Revision::Rev(String::from(rev))
+    }
+
+    pub fn as_of(timestamp: i64) -> Self {
+        Revision::AsOf(timestamp)
+    }
+}
+
+impl From<&str> for Revision {
+    fn from(rev: &str) -> Self {
+        Revision::rev(rev)
+    }
+}
+
+/// A commit that changed a document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentRevision {
+    id: String,
+    author: String,
+    email: String,
+    date: i64,
+    message: String,
+    trailers: Option<CommitTrailers>,
+    signature: Option<CommitSignature>,
+}
+
+impl DocumentRevision {
+    /// Describes a commit, splitting the trailers added by the stores off `message`
+    pub fn new(id: &str, author: &str, email: &str, date: i64, message: &str) -> Self {
+        let (message, trailers) = CommitTrailers::parse(message);
+        Self {
+            id: String::from(id),
+            author: String::from(author),
+            email: String::from(email),
+            date,
+            message: String::from(message),
+            trailers,
+            signature: None,
+        }
+    }
+
+    pub fn with_signature(self, signature: Option<CommitSignature>) -> Self {
+        Self {
+            signature,
+            ..self
+        }
+    }
+
+    /// Commit id
+    pub fn id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    pub fn author(&self) -> &str {
+        self.author.as_str()
+    }
+
+    pub fn email(&self) -> &str {
+        self.email.as_str()
+    }
+
+    /// Commit date as a unix timestamp, in seconds, the date [Revision::AsOf] compares
+    pub fn date(&self) -> i64 {
+        self.date
+    }
+
+    /// Commit message, without its trailers
+    pub fn message(&self) -> &str {
+        self.message.as_str()
+    }
+
+    /// Trailers recording the operation that created the commit, `None` for commits made outside of the stores
+    pub fn trailers(&self) -> Option<&CommitTrailers> {
+        self.trailers.as_ref()
+    }
+
+    /// Signature of the commit, `None` when it is unsigned
+    pub fn signature(&self) -> Option<&CommitSignature> {
+        self.signature.as_ref()
+    }
+
+    /// Whether the commit carries a valid signature made by a trusted key
+    pub fn is_trusted(&self) -> bool {
+        self.signature.as_ref().is_some_and(CommitSignature::is_trusted)
+    }
+}
+
+/// A file differing between two revisions of a store
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathChange {
+    Added(String),
+    Removed(String),
+    Modified(String),
+}
+
+impl PathChange {
+    /// File path
+    pub fn path(&self) -> &str {
+        match self {
+            PathChange::Added(path) | PathChange::Removed(path) | PathChange::Modified(path) => path.as_str(),
+        }
+    }
+}
+
+/// Parses the output of `git diff-tree -r -z --name-status --no-renames` into the changed files, sorted by path
+pub(crate) fn parse_name_status(output: &str) -> Vec<PathChange> {
+    let mut changes = Vec::new();
+    let mut entries = output.split('\0').filter(|e| !e.is_empty());
+    while let (Some(status), Some(path)) = (entries.next(), entries.next()) {
+        let path = String::from(path);
+        changes.push(match status {
+            "A" => PathChange::Added(path),
+            "D" => PathChange::Removed(path),
+            _ => PathChange::Modified(path),
+        });
+    }
+    changes.sort_by(|a, b| a.path().cmp(b.path()));
+    changes
+}
+
+/// `git log --format` producing records parsed by [parse_log], signatures included
+pub(crate) const LOG_FORMAT: &str = "--format=%H%x1f%an%x1f%ae%x1f%ct%x1f%G?%x1f%GS%x1f%GK%x1f%B%x1e";
+
+pub(crate) fn parse_log(output: &str) -> Vec<DocumentRevision> {
+    output.split('\x1e')
+        .filter_map(|record| {
+            let fields: Vec<&str> = record.trim_start_matches('\n').splitn(8, '\x1f').collect();
+            if fields.len() < 8 {
+                return None;
+            }
+            let date = fields[3].parse::<i64>().unwrap_or_default();
+            let signature = CommitSignature::parse(fields[4], fields[5], fields[6]);
+            Some(DocumentRevision::new(fields[0], fields[1], fields[2], date, fields[7].trim_end()).with_signature(signature))
+        })
+        .collect()
+}
+
+/// A named snapshot of the whole store, kept as an annotated tag
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    name: String,
+    commit: String,
+    tagger: String,
+    email: String,
+    date: i64,
+    message: String,
+}
+
+impl Snapshot {
+    pub fn new(name: &str, commit: &str, tagger: &str, email: &str, date: i64, message: &str) -> Self {
+        Self {
+            name: String::from(name),
+            commit: String::from(commit),
+            tagger: String::from(tagger),
+            email: String::from(email),
+            date,
+            message: String::from(message),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Id of the commit tagged
+    pub fn commit(&self) -> &str {
+        self.commit.as_str()
+    }
+
+    pub fn tagger(&self) -> &str {
+        self.tagger.as_str()
+    }
+
+    pub fn email(&self) -> &str {
+        self.email.as_str()
+    }
+
+    /// Tag date as a unix timestamp, in seconds
+    pub fn date(&self) -> i64 {
+        self.date
+    }
+
+    pub fn message(&self) -> &str {
+        self.message.as_str()
+    }
+}
+
+/// `git for-each-ref --format` producing records parsed by [parse_tags]
+pub(crate) const TAG_FORMAT: &str = "--format=%(refname:short)%1f%(*objectname)%1f%(taggername)%1f%(taggeremail:trim)%1f%(taggerdate:unix)%1f%(contents)%1e";
+
+/// Parses the annotated tags of commits listed with [TAG_FORMAT] into snapshots, newest first
+pub(crate) fn parse_tags(output: &str) -> Vec<Snapshot> {
+    let mut snapshots: Vec<Snapshot> = output.split('\x1e')
+        .filter_map(|record| {
+            let fields: Vec<&str> = record.trim_start_matches('\n').splitn(6, '\x1f').collect();
+            // lightweight tags have no tagged object
+            if fields.len() < 6 || fields[1].is_empty() {
+                return None;
+            }
+            let date = fields[4].parse::<i64>().unwrap_or_default();
+            Some(Snapshot::new(fields[0], fields[1], fields[2], fields[3], date, fields[5].trim_end()))
+        })
+        .collect();
+    snapshots.sort_by_key(|s| std::cmp::Reverse(s.date()));
+    snapshots
+}
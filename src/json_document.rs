@@ -43,9 +43,17 @@ impl Display for DocumentError {
 
 pub struct Document<T> where T: Debug + Clone + serde::Serialize + for<'a> serde::Deserialize<'a> {
     content: T,
+    revision: Option<String>,
 }
 
 impl<T: Clone + Debug + serde::Serialize + for<'a> serde::Deserialize<'a>> Document<T> {
+    pub fn new(content: T) -> Document<T> {
+        Self {
+            content,
+            revision: None,
+        }
+    }
+
     pub fn load(reader: &mut dyn io::Read, map_from: fn(&str) -> Result<T, Box<dyn Error>>) -> Result<Document<T>, DocumentError> {
         let mut contents = String::new();
         match reader.read_to_string(&mut contents) {
@@ -53,7 +61,8 @@ impl<T: Clone + Debug + serde::Serialize + for<'a> serde::Deserialize<'a>> Docum
                 match map_from(&contents) {
                     Ok(value) => Ok(
                         Self {
-                            content: value
+                            content: value,
+                            revision: None,
                         }
                     ),
                     Err(e) => Err(DocumentError::Load(e)),
@@ -70,9 +79,45 @@ impl<T: Clone + Debug + serde::Serialize + for<'a> serde::Deserialize<'a>> Docum
         }
     }
 
+    /// Reads the document from `reader` without blocking the thread, see [Document::load].
+    /// When the future is dropped before completing, the content read so far is lost and the reader left part way.
+    #[cfg(feature = "async")]
+    pub async fn load_async<R: tokio::io::AsyncRead + Unpin>(reader: &mut R, map_from: fn(&str) -> Result<T, Box<dyn Error>>) -> Result<Document<T>, DocumentError> {
+        use tokio::io::AsyncReadExt;
+        let mut contents = String::new();
+        match reader.read_to_string(&mut contents).await {
+            Ok(_) => Self::load(&mut contents.as_bytes(), map_from),
+            Err(s) => Err(DocumentError::Load(Box::new(s))),
+        }
+    }
+
+    /// Writes the document to `writer` without blocking the thread, flushing it, see [Document::write].
+    /// When the future is dropped before completing, part of the content may have been written.
+    #[cfg(feature = "async")]
+    pub async fn write_async<W: tokio::io::AsyncWrite + Unpin>(&mut self, writer: &mut W, map_into: fn(&T) -> String) -> Result<(), DocumentError> where serde_json::Value: std::convert::From<T> {
+        use tokio::io::AsyncWriteExt;
+        let content = map_into(&self.content);
+        match writer.write_all(content.as_bytes()).await {
+            Ok(_) => match writer.flush().await {
+                Ok(_) => Ok(()),
+                Err(s) => Err(DocumentError::Write(Box::new(s))),
+            },
+            Err(s) => Err(DocumentError::Write(Box::new(s))),
+        }
+    }
+
     pub fn content(&self) -> &T {
         &self.content
     }
+
+    /// Token identifying the stored version this document was read from or last written as
+    pub fn revision(&self) -> Option<&str> {
+        self.revision.as_deref()
+    }
+
+    pub fn set_revision(&mut self, revision: Option<String>) {
+        self.revision = revision;
+    }
 }
 
 
@@ -241,7 +286,7 @@ pub fn delete_key(key: &str, current: &Map<String, Value>) -> Result<Map<String,
                                 vc.insert(key.to_string(), Value::from(vv));
                                 Ok(vc.clone())
                             }
-                            Err(e) => return Err(e),
+                            Err(e) => Err(e),
                         }
                     } else {
                         Err(DocumentError::Delete(format!("{} is not and object", &key)))
@@ -302,6 +347,23 @@ pub fn get_key(key: &str, current: &Map<String, Value>) -> Result<Value, Documen
     }
 }
 
+/// Lists the dotted keys of all the leaf values in `current`.
+/// Arrays and empty objects are leaves.
+pub fn leaf_keys(current: &Map<String, Value>) -> Vec<String> {
+    let mut keys = Vec::new();
+    for (key, value) in current {
+        match value.as_object() {
+            Some(obj) if !obj.is_empty() => {
+                for sub_key in leaf_keys(obj) {
+                    keys.push(format!("{}.{}", key, sub_key));
+                }
+            },
+            _ => keys.push(key.clone()),
+        }
+    }
+    keys
+}
+
 pub fn build_key(keys: &VecDeque<String>) -> String {
     let (front, back) = keys.as_slices();
     let kj = [front, back].concat();
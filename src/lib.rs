@@ -1,6 +1,27 @@
-
 pub mod json_document;
 pub mod query;
 pub mod query_value;
 pub mod repo_store;
 pub mod query_key;
+pub mod collection;
+pub mod history;
+pub mod blame;
+pub mod merge;
+pub mod diff;
+pub mod feed;
+pub mod audit;
+pub mod transaction;
+pub mod write_behind;
+pub mod shared_store;
+#[cfg(feature = "async")]
+pub mod async_store;
+pub mod memory_store;
+pub mod bare_store;
+pub mod credentials;
+pub mod signing;
+pub mod backend;
+#[cfg(feature = "git2")]
+pub mod git2_backend;
+mod lock;
+mod git_args;
+mod detached;
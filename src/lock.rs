@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, TryLockError};
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+/// The thread holding a lock or `None` for a task, how many times it was acquired and the locked file
+type Holder = (Option<ThreadId>, usize, File);
+
+/// Lock files held by this process
+static HELD: Mutex<BTreeMap<PathBuf, Holder>> = Mutex::new(BTreeMap::new());
+
+/// An exclusive advisory lock on a file, taken with the locking of the operating system and released when dropped.
+/// The system releases the lock of a process that dies, so a lock is never left stale; the file itself stays,
+/// holding the id of the last process that took it for error messages.
+/// The thread holding a lock acquires it again without waiting, the lock being released with the last guard.
+pub struct FileLock {
+    path: PathBuf,
+}
+
+impl FileLock {
+    /// Locks the file at `path`, creating it if needed, retrying every `retry` until `timeout` elapses while another holder has it
+    pub fn acquire(path: &Path, timeout: Duration, retry: Duration) -> io::Result<FileLock> {
+        let started = Instant::now();
+        loop {
+            if Self::reenter(path) {
+                return Ok(FileLock { path: path.to_path_buf() });
+            }
+            match Self::try_acquire(path, Some(thread::current().id()))? {
+                Some(lock) => return Ok(lock),
+                None if started.elapsed() < timeout => thread::sleep(retry.min(timeout.saturating_sub(started.elapsed()))),
+                None => return Err(Self::held_elsewhere(path)),
+            }
+        }
+    }
+
+    /// Locks the file at `path` like [FileLock::acquire], waiting without blocking the thread.
+    /// The lock is held by a task rather than a thread, so it is never acquired again without waiting, by its holder included.
+    #[cfg(feature = "async")]
+    pub async fn acquire_async(path: &Path, timeout: Duration, retry: Duration) -> io::Result<FileLock> {
+        let started = Instant::now();
+        loop {
+            match Self::try_acquire(path, None)? {
+                Some(lock) => return Ok(lock),
+                None if started.elapsed() < timeout => tokio::time::sleep(retry.min(timeout.saturating_sub(started.elapsed()))).await,
+                None => return Err(Self::held_elsewhere(path)),
+            }
+        }
+    }
+
+    /// Takes the lock for the thread `owner` when no other holder has it, `None` for a lock never reentered
+    fn try_acquire(path: &Path, owner: Option<ThreadId>) -> io::Result<Option<FileLock>> {
+        let mut file = fs::OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        match file.try_lock() {
+            Ok(_) => {
+                file.set_len(0)?;
+                file.write_all(std::process::id().to_string().as_bytes())?;
+                Self::held().insert(path.to_path_buf(), (owner, 1, file));
+                Ok(Some(FileLock { path: path.to_path_buf() }))
+            },
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(e)) => Err(e),
+        }
+    }
+
+    fn held_elsewhere(path: &Path) -> io::Error {
+        let owner = fs::read_to_string(path).unwrap_or_default();
+        io::Error::new(io::ErrorKind::WouldBlock, format!("{} held by process {}", path.display(), owner.trim()))
+    }
+
+    fn held() -> std::sync::MutexGuard<'static, BTreeMap<PathBuf, Holder>> {
+        HELD.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Acquires again a lock the current thread holds
+    fn reenter(path: &Path) -> bool {
+        match Self::held().get_mut(path) {
+            Some((owner, count, _)) if *owner == Some(thread::current().id()) => {
+                *count += 1;
+                true
+            },
+            _ => false,
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let mut held = Self::held();
+        if let Some((_, count, _)) = held.get_mut(&self.path) {
+            *count -= 1;
+            if *count > 0 {
+                return;
+            }
+        }
+        // closing the file releases the lock
+        held.remove(&self.path);
+    }
+}
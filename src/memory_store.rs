@@ -0,0 +1,819 @@
+use crate::audit::{CommitOptions, CommitTrailers, Operation};
+use crate::history::{DocumentRevision, PathChange, Revision, Snapshot};
+use crate::json_document::{map_from_str, map_into_string, Document};
+use crate::merge::{merge_document, DocumentConflict, MergePolicy, MergeTimes};
+use crate::repo_store::{pull_conflicts, under, CleanMode, GitCommit, Identity, RepoStore, RepoStoreError};
+use serde_json::{Map, Value};
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// File contents by path
+type Tree = BTreeMap<String, String>;
+
+#[derive(Debug, Clone)]
+struct MemCommit {
+    parents: Vec<String>,
+    tree: Tree,
+    author: String,
+    email: String,
+    date: i64,
+    message: String,
+}
+
+/// An annotated tag
+#[derive(Debug, Clone)]
+struct MemTag {
+    commit: String,
+    tagger: String,
+    email: String,
+    date: i64,
+    message: String,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Repo {
+    commits: HashMap<String, MemCommit>,
+    /// Branch tips by name
+    refs: BTreeMap<String, String>,
+    tags: BTreeMap<String, MemTag>,
+}
+
+static COMMIT_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn hex_hash<H: Hash>(value: &H) -> String {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or_default()
+}
+
+impl Repo {
+    fn add_commit(&mut self, commit: MemCommit) -> String {
+        let id = hex_hash(&(&commit.parents, &commit.tree, &commit.message, &commit.author, commit.date, COMMIT_COUNTER.fetch_add(1, Ordering::Relaxed)));
+        self.commits.insert(id.clone(), commit);
+        id
+    }
+
+    fn tree(&self, id: Option<&str>) -> Tree {
+        id.and_then(|id| self.commits.get(id)).map(|c| c.tree.clone()).unwrap_or_default()
+    }
+
+    fn date(&self, id: &str) -> i64 {
+        self.commits.get(id).map(|c| c.date).unwrap_or_default()
+    }
+
+    /// `id` and all the commits reachable from it, breadth first
+    fn ancestors(&self, id: &str) -> Vec<String> {
+        let mut seen = BTreeSet::new();
+        let mut queue = VecDeque::from([id.to_string()]);
+        let mut ancestors = Vec::new();
+        while let Some(id) = queue.pop_front() {
+            if let Some(commit) = self.commits.get(&id)
+                && seen.insert(id.clone()) {
+                queue.extend(commit.parents.iter().cloned());
+                ancestors.push(id);
+            }
+        }
+        ancestors
+    }
+
+    fn is_ancestor(&self, ancestor: &str, id: &str) -> bool {
+        self.ancestors(id).iter().any(|a| a == ancestor)
+    }
+
+    fn merge_base(&self, a: &str, b: &str) -> Option<String> {
+        let ancestors: BTreeSet<String> = self.ancestors(a).into_iter().collect();
+        self.ancestors(b).into_iter().find(|id| ancestors.contains(id))
+    }
+
+    /// Copies the commits reachable from `id` in `other` that this repository lacks
+    fn fetch_from(&mut self, other: &Repo, id: &str) {
+        for ancestor in other.ancestors(id) {
+            if !self.commits.contains_key(&ancestor) {
+                self.commits.insert(ancestor.clone(), other.commits[&ancestor].clone());
+            }
+        }
+    }
+}
+
+/// A shared repository standing in for the remote of one or more [InMemoryStore]s
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryRemote {
+    repo: Arc<Mutex<Repo>>,
+}
+
+impl InMemoryRemote {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn repo(&self) -> MutexGuard<'_, Repo> {
+        self.repo.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    repo: Repo,
+    branch: String,
+    working: Tree,
+    /// Tips of the remote branches as last seen by pull or push
+    upstreams: BTreeMap<String, String>,
+    initialized: bool,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            repo: Repo::default(),
+            branch: String::from("main"),
+            working: Tree::new(),
+            upstreams: BTreeMap::new(),
+            initialized: false,
+        }
+    }
+}
+
+impl State {
+    fn head(&self) -> Option<String> {
+        self.repo.refs.get(&self.branch).cloned()
+    }
+
+    fn set_head(&mut self, id: Option<String>) {
+        match id {
+            Some(id) => self.repo.refs.insert(self.branch.clone(), id),
+            None => self.repo.refs.remove(&self.branch),
+        };
+    }
+
+    fn head_tree(&self) -> Tree {
+        self.repo.tree(self.head().as_deref())
+    }
+
+    /// Moves the head to `id`, updating the working tree unless that would overwrite uncommitted changes
+    fn checkout(&mut self, id: &str) -> Result<(), String> {
+        self.update_working(Some(id))?;
+        self.set_head(Some(id.to_string()));
+        Ok(())
+    }
+
+    /// Replaces the head tree in the working tree by the tree of `id`, keeping the uncommitted changes
+    /// and failing when they would be overwritten
+    fn update_working(&mut self, id: Option<&str>) -> Result<(), String> {
+        let old = self.head_tree();
+        let new = self.repo.tree(id);
+        let changed: BTreeSet<&String> = old.keys().chain(new.keys()).filter(|p| old.get(*p) != new.get(*p)).collect();
+        if let Some(path) = changed.iter().find(|p| self.working.get(**p) != old.get(**p)) {
+            return Err(format!("local changes to {} would be overwritten", path));
+        }
+        for path in changed {
+            match new.get(path) {
+                Some(content) => self.working.insert(path.clone(), content.clone()),
+                None => self.working.remove(path),
+            };
+        }
+        Ok(())
+    }
+
+    fn commit(&mut self, tree: Tree, msg: &str, mut trailers: CommitTrailers, author: &Identity) -> Result<(), RepoStoreError> {
+        let head = self.head();
+        let head_tree = self.head_tree();
+        if head.is_some() && tree == head_tree {
+            return Err(RepoStoreError::Commit("nothing to commit".into()));
+        }
+        let paths: BTreeSet<&String> = head_tree.keys().chain(tree.keys()).collect();
+        for path in paths {
+            trailers.change(path, head_tree.get(path).map(String::as_str), tree.get(path).map(String::as_str));
+        }
+        let id = self.repo.add_commit(MemCommit {
+            parents: head.into_iter().collect(),
+            tree,
+            author: author.name().to_string(),
+            email: author.email().to_string(),
+            date: now(),
+            message: trailers.message(msg)?,
+        });
+        self.set_head(Some(id));
+        Ok(())
+    }
+}
+
+/// A [RepoStore] keeping documents and their history in memory, for tests and ephemeral caches.
+/// Clones share their state, and stores pushing to and pulling from the same [InMemoryRemote]
+/// behave like clones of one repository, JSON documents being merged key by key on pull.
+/// Commits record their author only, committers given in [CommitOptions] are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryStore {
+    name: String,
+    commit: GitCommit,
+    merge_policy: MergePolicy,
+    remote: Option<InMemoryRemote>,
+    state: Arc<Mutex<State>>,
+}
+
+impl InMemoryStore {
+    pub fn new(name: &str, commit: GitCommit) -> Self {
+        Self {
+            name: String::from(name),
+            commit,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the remote cloned on initialize and used by pull and push
+    pub fn remote(self, remote: &InMemoryRemote) -> Self {
+        Self {
+            remote: Some(remote.clone()),
+            ..self
+        }
+    }
+
+    /// Sets the branch checked out on initialize, `main` by default
+    pub fn branch(self, branch: &str) -> Self {
+        self.state().branch = String::from(branch);
+        self
+    }
+
+    /// Sets how key conflicts in JSON documents are resolved when pulling or merging branches
+    pub fn merge_policy(self, merge_policy: MergePolicy) -> Self {
+        Self {
+            merge_policy,
+            ..self
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Resolves `revision` into a commit id: `HEAD`, a branch or snapshot name, a commit id or a unique prefix of one, or a timestamp
+    fn resolve_in(state: &State, revision: &Revision) -> Result<String, RepoStoreError> {
+        let repo = &state.repo;
+        let found = match (revision, state.head()) {
+            (Revision::Rev(rev), _) if repo.refs.contains_key(rev) => repo.refs.get(rev).cloned(),
+            (Revision::Rev(rev), _) if repo.tags.contains_key(rev) => repo.tags.get(rev).map(|t| t.commit.clone()),
+            (_, None) => None,
+            (Revision::Rev(rev), Some(head)) if rev == "HEAD" => Some(head),
+            (Revision::Rev(rev), Some(_)) => {
+                let matches: Vec<&String> = repo.commits.keys().filter(|id| id.starts_with(rev.as_str())).collect();
+                match matches.as_slice() {
+                    [id] if !rev.is_empty() => Some((*id).clone()),
+                    _ => None,
+                }
+            },
+            (Revision::AsOf(timestamp), Some(head)) => {
+                let mut current = Some(head);
+                while let Some(id) = current.clone() && repo.date(&id) > *timestamp {
+                    current = repo.commits.get(&id).and_then(|c| c.parents.first().cloned());
+                }
+                current
+            },
+        };
+        found.ok_or_else(|| RepoStoreError::History(format!("no commit found for {:?}", revision).into()))
+    }
+
+    /// Merges the trees of two lines of history forked from `base`, file by file and JSON documents key by key.
+    /// Files changed on both sides which are not JSON documents cannot be merged and conflict without keys.
+    fn merge_trees(&self, base: &Tree, ours: &Tree, theirs: &Tree, times: MergeTimes) -> Result<Tree, Vec<DocumentConflict>> {
+        let paths: BTreeSet<&String> = base.keys().chain(ours.keys()).chain(theirs.keys()).collect();
+        // `None` for content which is not a JSON document, `Some(None)` for a file absent on that side
+        let parse = |content: Option<&String>| match content {
+            Some(c) => map_from_str(c).ok().map(Some),
+            None => Some(None),
+        };
+        let mut merged = Tree::new();
+        let mut conflicts = Vec::new();
+        for path in paths {
+            let (b, o, t) = (base.get(path), ours.get(path), theirs.get(path));
+            let value = if o == t || t == b {
+                o.cloned()
+            } else if o == b {
+                t.cloned()
+            } else {
+                let documents = (parse(b), parse(o), parse(t));
+                match documents {
+                    (Some(b), Some(o), Some(t)) if path.ends_with(".json") => match merge_document(path, b.as_ref(), o.as_ref(), t.as_ref(), &self.merge_policy, times) {
+                        Ok(map) => Some(map_into_string(&map)),
+                        Err(keys) => {
+                            conflicts.push(DocumentConflict::new(path, keys));
+                            None
+                        },
+                    },
+                    _ => {
+                        conflicts.push(DocumentConflict::new(path, vec![]));
+                        None
+                    },
+                }
+            };
+            if let Some(content) = value {
+                merged.insert(path.clone(), content);
+            }
+        }
+        if conflicts.is_empty() {
+            Ok(merged)
+        } else {
+            Err(conflicts)
+        }
+    }
+
+    /// Replays the local commits since the fork point on top of `upstream`, returning the new tip
+    fn rebase(&self, repo: &mut Repo, head: &str, upstream: &str) -> Result<String, Vec<DocumentConflict>> {
+        let base = repo.merge_base(head, upstream);
+        let mut local = Vec::new();
+        let mut current = Some(head.to_string());
+        while let Some(id) = current && Some(&id) != base.as_ref() {
+            current = repo.commits.get(&id).and_then(|c| c.parents.first().cloned());
+            local.push(id);
+        }
+        let mut tip = upstream.to_string();
+        for id in local.iter().rev() {
+            let commit = repo.commits[id].clone();
+            let parent_tree = repo.tree(commit.parents.first().map(String::as_str));
+            let tip_tree = repo.tree(Some(&tip));
+            let times = MergeTimes { ours: commit.date, theirs: repo.date(&tip) };
+            let tree = self.merge_trees(&parent_tree, &commit.tree, &tip_tree, times)?;
+            if tree == tip_tree {
+                // the change is already upstream
+                continue;
+            }
+            tip = repo.add_commit(MemCommit {
+                parents: vec![tip],
+                tree,
+                ..commit
+            });
+        }
+        Ok(tip)
+    }
+
+    fn merge(&self, repo: &mut Repo, head: &str, other: &str, msg: &str) -> Result<String, Vec<DocumentConflict>> {
+        let base = repo.merge_base(head, other);
+        let times = MergeTimes { ours: repo.date(head), theirs: repo.date(other) };
+        let tree = self.merge_trees(&repo.tree(base.as_deref()), &repo.tree(Some(head)), &repo.tree(Some(other)), times)?;
+        let (author, email) = self.commit.pair();
+        Ok(repo.add_commit(MemCommit {
+            parents: vec![head.to_string(), other.to_string()],
+            tree,
+            author,
+            email,
+            date: now(),
+            message: String::from(msg),
+        }))
+    }
+
+    fn no_remote() -> Box<dyn std::error::Error> {
+        "no remote configured".into()
+    }
+}
+
+impl RepoStore<Map<String, Value>> for InMemoryStore {
+    fn initialize(&self) -> Result<(), RepoStoreError> {
+        let mut state = self.state();
+        if state.initialized {
+            return Ok(());
+        }
+        if let Some(remote) = &self.remote {
+            state.repo = remote.repo().clone();
+            state.working = state.head_tree();
+            state.upstreams = state.repo.refs.clone();
+        }
+        state.initialized = true;
+        Ok(())
+    }
+
+    fn document(&self, path: &str) -> Result<Document<Map<String, Value>>, RepoStoreError> {
+        let content = match self.state().working.get(path) {
+            Some(content) => content.clone(),
+            None => return Err(RepoStoreError::Read(format!("{} not found", path).into())),
+        };
+        match Document::load(&mut content.as_bytes(), map_from_str) {
+            Ok(mut doc) => {
+                doc.set_revision(Some(hex_hash(&content)));
+                Ok(doc)
+            },
+            Err(e) => Err(RepoStoreError::Read(Box::new(e))),
+        }
+    }
+
+    fn write_document(&self, path: &str, document: &mut Document<Map<String, Value>>) -> Result<(), RepoStoreError> {
+        let mut buffer = Vec::new();
+        if let Err(e) = document.write(&mut buffer, map_into_string) {
+            return Err(RepoStoreError::Write(Box::new(e)));
+        }
+        let content = String::from_utf8_lossy(&buffer).to_string();
+        document.set_revision(Some(hex_hash(&content)));
+        self.state().working.insert(String::from(path), content);
+        Ok(())
+    }
+
+    fn remove_document(&self, path: &str) -> Result<(), RepoStoreError> {
+        match self.state().working.remove(path) {
+            Some(_) => Ok(()),
+            None => Err(RepoStoreError::Write(format!("{} not found", path).into())),
+        }
+    }
+
+    fn write_document_if(&self, path: &str, document: &mut Document<Map<String, Value>>, expected: Option<&str>) -> Result<(), RepoStoreError> {
+        let mut buffer = Vec::new();
+        if let Err(e) = document.write(&mut buffer, map_into_string) {
+            return Err(RepoStoreError::Write(Box::new(e)));
+        }
+        let content = String::from_utf8_lossy(&buffer).to_string();
+        let mut state = self.state();
+        if state.working.get(path).map(hex_hash).as_deref() != expected {
+            return Err(RepoStoreError::RevisionConflict(String::from(path)));
+        }
+        document.set_revision(Some(hex_hash(&content)));
+        state.working.insert(String::from(path), content);
+        Ok(())
+    }
+
+    fn remove_document_if(&self, path: &str, expected: &str) -> Result<(), RepoStoreError> {
+        let mut state = self.state();
+        if state.working.get(path).map(hex_hash).as_deref() != Some(expected) {
+            return Err(RepoStoreError::RevisionConflict(String::from(path)));
+        }
+        state.working.remove(path);
+        Ok(())
+    }
+
+    fn revision(&self, path: &str) -> Result<Option<String>, RepoStoreError> {
+        Ok(self.state().working.get(path).map(hex_hash))
+    }
+
+    fn documents(&self, dir: &str) -> Result<Vec<String>, RepoStoreError> {
+        let state = self.state();
+        let prefix = if dir.is_empty() { String::new() } else { format!("{}/", dir.trim_end_matches('/')) };
+        let mut exists = dir.is_empty();
+        let mut names = Vec::new();
+        for path in state.working.keys() {
+            if let Some(rest) = path.strip_prefix(prefix.as_str()) {
+                exists = true;
+                if !rest.contains('/') && !rest.starts_with('.') {
+                    names.push(rest.to_string());
+                }
+            }
+        }
+        if !exists {
+            return Err(RepoStoreError::Read(format!("directory {} not found", dir).into()));
+        }
+        Ok(names)
+    }
+
+    fn create_dir(&self, dir: &str) -> Result<(), RepoStoreError> {
+        let keep = format!("{}/.gitkeep", dir.trim_end_matches('/'));
+        self.state().working.insert(keep, String::new());
+        Ok(())
+    }
+
+    fn remove_dir(&self, dir: &str) -> Result<(), RepoStoreError> {
+        let mut state = self.state();
+        let before = state.working.len();
+        state.working.retain(|path, _| !under(path, dir));
+        if state.working.len() == before {
+            return Err(RepoStoreError::Write(format!("directory {} not found", dir).into()));
+        }
+        Ok(())
+    }
+
+    fn document_at(&self, path: &str, revision: &Revision) -> Result<Document<Map<String, Value>>, RepoStoreError> {
+        let state = self.state();
+        let commit = Self::resolve_in(&state, revision)?;
+        let content = match state.repo.tree(Some(&commit)).get(path) {
+            Some(content) => content.clone(),
+            None => return Err(RepoStoreError::Read(format!("{} not found at {}", path, commit).into())),
+        };
+        match Document::load(&mut content.as_bytes(), map_from_str) {
+            Ok(mut doc) => {
+                doc.set_revision(Some(hex_hash(&content)));
+                Ok(doc)
+            },
+            Err(e) => Err(RepoStoreError::Read(Box::new(e))),
+        }
+    }
+
+    fn revision_at(&self, path: &str, revision: &Revision) -> Result<Option<String>, RepoStoreError> {
+        let state = self.state();
+        let commit = Self::resolve_in(&state, revision)?;
+        Ok(state.repo.tree(Some(&commit)).get(path).map(hex_hash))
+    }
+
+    fn resolve(&self, revision: &Revision) -> Result<String, RepoStoreError> {
+        Self::resolve_in(&self.state(), revision)
+    }
+
+    fn revisions(&self, path: &str) -> Result<Vec<DocumentRevision>, RepoStoreError> {
+        let state = self.state();
+        let repo = &state.repo;
+        let mut revisions = Vec::new();
+        if let Some(head) = state.head() {
+            for id in repo.ancestors(&head) {
+                let commit = &repo.commits[&id];
+                let content = commit.tree.get(path);
+                // a commit changed the document when it differs from every parent, as git log simplifies history
+                let changed = if commit.parents.is_empty() {
+                    content.is_some()
+                } else {
+                    commit.parents.iter().all(|p| repo.tree(Some(p)).get(path) != content)
+                };
+                if changed {
+                    revisions.push(DocumentRevision::new(&id, &commit.author, &commit.email, commit.date, &commit.message));
+                }
+            }
+        }
+        revisions.sort_by_key(|r| Reverse(r.date()));
+        Ok(revisions)
+    }
+
+    fn commits(&self, from: Option<&Revision>, to: &Revision) -> Result<Vec<DocumentRevision>, RepoStoreError> {
+        let state = self.state();
+        if *to == Revision::rev("HEAD") && state.head().is_none() {
+            return Ok(vec![]);
+        }
+        let repo = &state.repo;
+        let excluded: BTreeSet<String> = match from {
+            Some(from) => repo.ancestors(&Self::resolve_in(&state, from)?).into_iter().collect(),
+            None => BTreeSet::new(),
+        };
+        let mut commits = Vec::new();
+        let mut current = Some(Self::resolve_in(&state, to)?);
+        while let Some(id) = current && !excluded.contains(&id) {
+            let commit = &repo.commits[&id];
+            commits.push(DocumentRevision::new(&id, &commit.author, &commit.email, commit.date, &commit.message));
+            current = commit.parents.first().cloned();
+        }
+        commits.reverse();
+        Ok(commits)
+    }
+
+    fn document_commits(&self, path: &str, to: &Revision) -> Result<Vec<DocumentRevision>, RepoStoreError> {
+        let state = self.state();
+        if *to == Revision::rev("HEAD") && state.head().is_none() {
+            return Ok(vec![]);
+        }
+        let repo = &state.repo;
+        let mut commits = Vec::new();
+        let mut current = Some(Self::resolve_in(&state, to)?);
+        while let Some(id) = current {
+            let commit = &repo.commits[&id];
+            let parent = commit.parents.first().cloned();
+            if repo.tree(parent.as_deref()).get(path) != commit.tree.get(path) {
+                commits.push(DocumentRevision::new(&id, &commit.author, &commit.email, commit.date, &commit.message));
+            }
+            current = parent;
+        }
+        commits.reverse();
+        Ok(commits)
+    }
+
+    fn pull(&self, rebase: bool) -> Result<(), RepoStoreError> {
+        let remote = self.remote.as_ref().ok_or_else(|| RepoStoreError::Pull(Self::no_remote()))?;
+        let mut state = self.state();
+        let remote_repo = remote.repo().clone();
+        for (branch, id) in &remote_repo.refs {
+            state.repo.fetch_from(&remote_repo, id);
+            state.upstreams.insert(branch.clone(), id.clone());
+        }
+        let Some(upstream) = remote_repo.refs.get(&state.branch).cloned() else {
+            return Ok(());
+        };
+        let merged = match state.head() {
+            None => Ok(upstream),
+            Some(head) if state.repo.is_ancestor(&upstream, &head) => return Ok(()),
+            Some(head) if state.repo.is_ancestor(&head, &upstream) => Ok(upstream),
+            Some(head) if rebase => self.rebase(&mut state.repo, &head, &upstream),
+            Some(head) => self.merge(&mut state.repo, &head, &upstream, "Merge remote branch"),
+        };
+        let target = match merged {
+            Ok(target) => target,
+            Err(conflicts) => return pull_conflicts(conflicts),
+        };
+        match state.checkout(&target) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(RepoStoreError::Pull(e.into())),
+        }
+    }
+
+    fn push(&self) -> Result<(), RepoStoreError> {
+        let remote = self.remote.as_ref().ok_or_else(|| RepoStoreError::Push(Self::no_remote()))?;
+        let mut state = self.state();
+        let Some(head) = state.head() else {
+            return Ok(());
+        };
+        let mut remote_repo = remote.repo();
+        if let Some(remote_head) = remote_repo.refs.get(&state.branch)
+            && !state.repo.is_ancestor(remote_head, &head) {
+            return Err(RepoStoreError::PushRejected("non-fast-forward".into()));
+        }
+        remote_repo.fetch_from(&state.repo, &head);
+        remote_repo.refs.insert(state.branch.clone(), head.clone());
+        let branch = state.branch.clone();
+        state.upstreams.insert(branch, head);
+        Ok(())
+    }
+
+    fn commit_with(&self, msg: &str, paths: Option<&[String]>, options: &CommitOptions) -> Result<(), RepoStoreError> {
+        let mut state = self.state();
+        let trailers = options.trailers(Operation::Commit);
+        let author = options.author_identity().cloned().unwrap_or_else(|| self.commit.identity());
+        let Some(paths) = paths else {
+            let tree = state.working.clone();
+            return state.commit(tree, msg, trailers, &author);
+        };
+        let mut tree = state.head_tree();
+        tree.retain(|path, _| !paths.iter().any(|p| under(path, p)));
+        for (path, content) in &state.working {
+            if paths.iter().any(|p| under(path, p)) {
+                tree.insert(path.clone(), content.clone());
+            }
+        }
+        state.commit(tree, msg, trailers, &author)
+    }
+
+    fn restore_documents(&self, paths: &[String]) -> Result<(), RepoStoreError> {
+        let mut state = self.state();
+        let head_tree = state.head_tree();
+        state.working.retain(|path, _| !paths.iter().any(|p| under(path, p)));
+        for (path, content) in head_tree {
+            if paths.iter().any(|p| under(&path, p)) {
+                state.working.insert(path, content);
+            }
+        }
+        Ok(())
+    }
+
+    fn clean(&self, mode: CleanMode) -> Result<Vec<String>, RepoStoreError> {
+        let mut state = self.state();
+        let head_tree = state.head_tree();
+        let target = match mode {
+            CleanMode::WorkingTree => state.head(),
+            CleanMode::Remote => match state.upstreams.get(&state.branch) {
+                Some(upstream) => Some(upstream.clone()),
+                None => return Err(RepoStoreError::Clean("no upstream branch".into())),
+            },
+        };
+        let target_tree = state.repo.tree(target.as_deref());
+        let mut discarded: BTreeSet<String> = BTreeSet::new();
+        for tree in [&state.working, &target_tree] {
+            for path in tree.keys().chain(head_tree.keys()) {
+                if tree.get(path) != head_tree.get(path) {
+                    discarded.insert(path.clone());
+                }
+            }
+        }
+        state.set_head(target);
+        state.working = target_tree;
+        Ok(discarded.into_iter().collect())
+    }
+
+    fn current_branch(&self) -> Result<String, RepoStoreError> {
+        Ok(self.state().branch.clone())
+    }
+
+    fn branches(&self) -> Result<Vec<String>, RepoStoreError> {
+        Ok(self.state().repo.refs.keys().cloned().collect())
+    }
+
+    fn create_branch(&self, name: &str, from: &Revision) -> Result<(), RepoStoreError> {
+        let mut state = self.state();
+        let commit = Self::resolve_in(&state, from)?;
+        if name.is_empty() || state.repo.refs.contains_key(name) {
+            return Err(RepoStoreError::Branch(format!("invalid or existing branch name {:?}", name).into()));
+        }
+        state.repo.refs.insert(String::from(name), commit);
+        Ok(())
+    }
+
+    fn switch_branch(&self, name: &str) -> Result<(), RepoStoreError> {
+        let mut state = self.state();
+        if !state.repo.refs.contains_key(name) {
+            // a branch only known to the remote is created at its remote tip
+            let remote_tip = self.remote.as_ref().and_then(|r| {
+                let remote_repo = r.repo().clone();
+                remote_repo.refs.get(name).cloned().map(|id| (remote_repo, id))
+            });
+            match remote_tip {
+                Some((remote_repo, id)) => {
+                    state.repo.fetch_from(&remote_repo, &id);
+                    state.repo.refs.insert(String::from(name), id.clone());
+                    state.upstreams.insert(String::from(name), id);
+                },
+                None => return Err(RepoStoreError::Branch(format!("branch {} not found", name).into())),
+            }
+        }
+        let target = state.repo.refs.get(name).cloned();
+        if let Err(e) = state.update_working(target.as_deref()) {
+            return Err(RepoStoreError::Branch(e.into()));
+        }
+        state.branch = String::from(name);
+        Ok(())
+    }
+
+    fn delete_branch(&self, name: &str) -> Result<(), RepoStoreError> {
+        let mut state = self.state();
+        if state.branch == name {
+            return Err(RepoStoreError::Branch(format!("cannot delete the active branch {}", name).into()));
+        }
+        match state.repo.refs.remove(name) {
+            Some(_) => Ok(()),
+            None => Err(RepoStoreError::Branch(format!("branch {} not found", name).into())),
+        }
+    }
+
+    fn merge_branch(&self, name: &str) -> Result<(), RepoStoreError> {
+        let mut state = self.state();
+        let Some(other) = state.repo.refs.get(name).cloned() else {
+            return Err(RepoStoreError::Branch(format!("branch {} not found", name).into()));
+        };
+        let target = match state.head() {
+            None => other,
+            Some(head) if state.repo.is_ancestor(&other, &head) => return Ok(()),
+            Some(head) if state.repo.is_ancestor(&head, &other) => other,
+            Some(head) => match self.merge(&mut state.repo, &head, &other, format!("Merge branch '{}'", name).as_str()) {
+                Ok(id) => id,
+                Err(conflicts) => return Err(RepoStoreError::MergeConflict(conflicts)),
+            },
+        };
+        match state.checkout(&target) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(RepoStoreError::Branch(e.into())),
+        }
+    }
+
+    fn create_snapshot(&self, name: &str, msg: &str) -> Result<(), RepoStoreError> {
+        let mut state = self.state();
+        let Some(commit) = state.head() else {
+            return Err(RepoStoreError::Snapshot("nothing committed yet".into()));
+        };
+        if name.is_empty() || state.repo.tags.contains_key(name) {
+            return Err(RepoStoreError::Snapshot(format!("invalid or existing snapshot name {:?}", name).into()));
+        }
+        let (tagger, email) = self.commit.pair();
+        state.repo.tags.insert(String::from(name), MemTag {
+            commit,
+            tagger,
+            email,
+            date: now(),
+            message: String::from(msg),
+        });
+        Ok(())
+    }
+
+    fn snapshots(&self) -> Result<Vec<Snapshot>, RepoStoreError> {
+        let state = self.state();
+        let mut snapshots: Vec<Snapshot> = state.repo.tags.iter()
+            .map(|(name, t)| Snapshot::new(name, &t.commit, &t.tagger, &t.email, t.date, &t.message))
+            .collect();
+        snapshots.sort_by_key(|s| Reverse(s.date()));
+        Ok(snapshots)
+    }
+
+    fn restore_snapshot(&self, name: &str, msg: &str) -> Result<(), RepoStoreError> {
+        let mut state = self.state();
+        let Some(tag) = state.repo.tags.get(name) else {
+            return Err(RepoStoreError::Snapshot(format!("snapshot {} not found", name).into()));
+        };
+        let tree = state.repo.tree(Some(&tag.commit));
+        state.working = tree.clone();
+        state.commit(tree, msg, CommitOptions::new().trailers(Operation::Restore), &self.commit.identity())
+    }
+
+    fn paths_at(&self, revision: &Revision) -> Result<Vec<String>, RepoStoreError> {
+        let state = self.state();
+        let commit = Self::resolve_in(&state, revision)?;
+        Ok(state.repo.tree(Some(&commit)).into_keys().collect())
+    }
+
+    fn changed_paths(&self, from: Option<&Revision>, to: &Revision) -> Result<Vec<PathChange>, RepoStoreError> {
+        let state = self.state();
+        let old = match from {
+            Some(from) => state.repo.tree(Some(&Self::resolve_in(&state, from)?)),
+            None => Tree::new(),
+        };
+        let new = state.repo.tree(Some(&Self::resolve_in(&state, to)?));
+        let paths: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+        Ok(paths.into_iter()
+            .filter_map(|path| match (old.get(path), new.get(path)) {
+                (None, Some(_)) => Some(PathChange::Added(path.clone())),
+                (Some(_), None) => Some(PathChange::Removed(path.clone())),
+                (Some(o), Some(n)) if o != n => Some(PathChange::Modified(path.clone())),
+                _ => None,
+            })
+            .collect())
+    }
+
+    fn head(&self) -> Result<Option<String>, RepoStoreError> {
+        Ok(self.state().head())
+    }
+}
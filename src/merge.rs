@@ -0,0 +1,186 @@
+use serde_json::{Map, Value};
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+/// Resolves a conflicting key into its merged value, `None` removing the key
+pub type ConflictResolver = Arc<dyn Fn(&KeyConflict) -> Option<Value> + Send + Sync>;
+
+/// How key conflicts found by a three-way merge are resolved
+#[derive(Clone, Default)]
+pub enum MergePolicy {
+    /// Leave conflicts unresolved and report them
+    #[default]
+    Report,
+    /// Keep the local value
+    Ours,
+    /// Keep the incoming value
+    Theirs,
+    /// Keep the value of the side changed last, the incoming one on ties
+    Newest,
+    Callback(ConflictResolver),
+}
+
+impl Debug for MergePolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MergePolicy::Report => write!(f, "Report"),
+            MergePolicy::Ours => write!(f, "Ours"),
+            MergePolicy::Theirs => write!(f, "Theirs"),
+            MergePolicy::Newest => write!(f, "Newest"),
+            MergePolicy::Callback(_) => write!(f, "Callback"),
+        }
+    }
+}
+
+/// A key changed in different ways by both sides of a merge.
+/// Missing values mean the key is absent on that side.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyConflict {
+    path: String,
+    key: String,
+    base: Option<Value>,
+    ours: Option<Value>,
+    theirs: Option<Value>,
+}
+
+impl KeyConflict {
+    /// Document path
+    pub fn path(&self) -> &str {
+        self.path.as_str()
+    }
+
+    /// Dotted key
+    pub fn key(&self) -> &str {
+        self.key.as_str()
+    }
+
+    pub fn base(&self) -> Option<&Value> {
+        self.base.as_ref()
+    }
+
+    pub fn ours(&self) -> Option<&Value> {
+        self.ours.as_ref()
+    }
+
+    pub fn theirs(&self) -> Option<&Value> {
+        self.theirs.as_ref()
+    }
+}
+
+/// A document changed in incompatible ways by both sides of a merge.
+/// The conflicting keys are empty when the document could not be merged key by key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentConflict {
+    path: String,
+    keys: Vec<KeyConflict>,
+}
+
+impl DocumentConflict {
+    pub fn new(path: &str, keys: Vec<KeyConflict>) -> Self {
+        Self {
+            path: String::from(path),
+            keys,
+        }
+    }
+
+    /// Document path
+    pub fn path(&self) -> &str {
+        self.path.as_str()
+    }
+
+    pub fn keys(&self) -> &[KeyConflict] {
+        self.keys.as_slice()
+    }
+}
+
+/// Which side of a merge changed last, used by [MergePolicy::Newest]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MergeTimes {
+    pub ours: i64,
+    pub theirs: i64,
+}
+
+/// Merges `ours` and `theirs`, both derived from `base`, key by key.
+/// Keys changed on one side only take that side's value, objects changed on both sides are merged recursively,
+/// and any other key changed on both sides is resolved with `policy`.
+/// Returns the unresolved conflicts when the policy leaves any.
+pub fn three_way_merge(path: &str, base: &Map<String, Value>, ours: &Map<String, Value>, theirs: &Map<String, Value>, policy: &MergePolicy, times: MergeTimes) -> Result<Map<String, Value>, Vec<KeyConflict>> {
+    let resolve = |conflict: &KeyConflict| -> Option<Option<Value>> {
+        match policy {
+            MergePolicy::Report => None,
+            MergePolicy::Ours => Some(conflict.ours.clone()),
+            MergePolicy::Theirs => Some(conflict.theirs.clone()),
+            MergePolicy::Newest => if times.ours > times.theirs {
+                Some(conflict.ours.clone())
+            } else {
+                Some(conflict.theirs.clone())
+            },
+            MergePolicy::Callback(f) => Some(f(conflict)),
+        }
+    };
+    let mut conflicts = Vec::new();
+    let merged = merge_level(path, "", Some(base), ours, theirs, &resolve, &mut conflicts);
+    if conflicts.is_empty() {
+        Ok(merged)
+    } else {
+        Err(conflicts)
+    }
+}
+
+/// Merges the versions of a document at `path` in a merge base and both sides, `None` where the document is absent.
+/// A document added on both sides is merged against an empty base, while a side deleting it as the other changes it
+/// conflicts without keys, whatever the policy, as git leaves modify/delete conflicts to be resolved by hand.
+pub(crate) fn merge_document(path: &str, base: Option<&Map<String, Value>>, ours: Option<&Map<String, Value>>, theirs: Option<&Map<String, Value>>, policy: &MergePolicy, times: MergeTimes) -> Result<Map<String, Value>, Vec<KeyConflict>> {
+    match (ours, theirs) {
+        (Some(ours), Some(theirs)) => three_way_merge(path, base.unwrap_or(&Map::new()), ours, theirs, policy, times),
+        _ => Err(vec![]),
+    }
+}
+
+fn merge_level(path: &str, prefix: &str, base: Option<&Map<String, Value>>, ours: &Map<String, Value>, theirs: &Map<String, Value>, resolve: &dyn Fn(&KeyConflict) -> Option<Option<Value>>, conflicts: &mut Vec<KeyConflict>) -> Map<String, Value> {
+    let mut keys: Vec<&String> = ours.keys().chain(theirs.keys()).collect();
+    if let Some(b) = base {
+        keys.extend(b.keys());
+    }
+    keys.sort();
+    keys.dedup();
+
+    let mut merged = Map::new();
+    for key in keys {
+        let b = base.and_then(|m| m.get(key));
+        let o = ours.get(key);
+        let t = theirs.get(key);
+        let value = if o == t || t == b {
+            o.cloned()
+        } else if o == b {
+            t.cloned()
+        } else {
+            let dotted = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+            match (o.and_then(Value::as_object), t.and_then(Value::as_object)) {
+                (Some(om), Some(tm)) if b.is_none_or(Value::is_object) => {
+                    Some(Value::Object(merge_level(path, dotted.as_str(), b.and_then(Value::as_object), om, tm, resolve, conflicts)))
+                },
+                _ => {
+                    let conflict = KeyConflict {
+                        path: String::from(path),
+                        key: dotted,
+                        base: b.cloned(),
+                        ours: o.cloned(),
+                        theirs: t.cloned(),
+                    };
+                    match resolve(&conflict) {
+                        Some(resolved) => resolved,
+                        None => {
+                            conflicts.push(conflict);
+                            o.cloned()
+                        },
+                    }
+                },
+            }
+        };
+        if let Some(v) = value {
+            merged.insert(key.clone(), v);
+        }
+    }
+    merged
+}
@@ -179,6 +179,7 @@ impl<K: QCKey> QueryClause<K>  {
         QueryClause::Or(Box::new(left), Box::new(right))
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn not(clause: QueryClause<K>) -> QueryClause<K> {
         QueryClause::Not(Box::new(clause))
     }
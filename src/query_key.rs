@@ -14,21 +14,22 @@ impl<K> QueryKey<K> where K: Display + for<'a> From<&'a str> + Clone + Hash + Eq
         QueryKey { key_chain: vec![] }
     }
     
+    #[allow(clippy::should_implement_trait)]
     pub fn clone(&self) -> QueryKey<K> {
         QueryKey { key_chain: self.key_chain.clone() }
     }
 
     pub fn push(&mut self, key: K) {
-        self.key_chain.push(key.into());
+        self.key_chain.push(key);
     }
 
-    pub fn suffix(&mut self, suffix: &Vec<K>) {
+    pub fn suffix(&mut self, suffix: &[K]) {
         for mk in suffix.iter() {
             self.key_chain.push(mk.clone());
         }
     }
 
-    pub fn prefix(&mut self, prefix: &Vec<K>) {
+    pub fn prefix(&mut self, prefix: &[K]) {
         let mut vjk : Vec<K> = Vec::new();
         for mk in prefix.iter() {
             vjk.push(mk.clone());
@@ -40,6 +41,12 @@ impl<K> QueryKey<K> where K: Display + for<'a> From<&'a str> + Clone + Hash + Eq
     }
 }
 
+impl<K> Default for QueryKey<K> where K: Display + for<'a> From<&'a str> + Clone + Hash + Eq {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<K> QCKey for QueryKey<K> where K: Display + for<'a> From<&'a str> + Clone + Hash + Eq {
     fn key(&self) -> String {
         self.to_string()
@@ -56,7 +63,7 @@ impl<K: Display + for<'a> From<&'a str> + Clone + Hash + Eq> Display for QueryKe
 impl<K: Display + for<'a> From<&'a str> + Clone + Hash + Eq> From<&str> for QueryKey<K> {
     fn from(key: &str) -> Self {
         let mut vjk: Vec<K> = vec!();
-        for ks in key.to_string().split('.') {
+        for ks in key.split('.') {
             let kks: K = ks.into();
             vjk.push(kks);
         }
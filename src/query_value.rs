@@ -56,7 +56,7 @@ impl PartialOrd<Self> for N {
             (N::NegInt(a), N::PosInt(b)) => {
                 if *a >= 0 {
                     let c = *a as u64;
-                    c.partial_cmp(&b)
+                    c.partial_cmp(b)
                 } else {
                     Some(Ordering::Less)
                 }
@@ -173,7 +173,7 @@ impl From<DocumentValue> for Value {
             DocumentValue::Number(v) => Value::from(v),
             DocumentValue::Boolean(v) => Value::from(v),
             DocumentValue::Array(v) => Value::from(v),
-            DocumentValue::Null => Value::from(Value::Null),
+            DocumentValue::Null => Value::Null,
         }
     }
 }
@@ -1,13 +1,23 @@
-/*use crate::repo_document::{JsonDocument, RepoDocument};
-use base64::prelude::BASE64_STANDARD;
-use base64::Engine;
-use gitwrap::{add, clone, commit, config, pull, push, rev_parse};
-use serde_json::Value;
+use crate::audit::{CommitOptions, CommitTrailers, Operation};
+use crate::credentials::{Credential, CredentialProvider, StaticCredentials};
+use crate::backend::{is_shallow, remote_env, CloneOptions, GitBackend, GitBackendError, GitwrapBackend, Remote};
+use crate::git_args::{args, EnvCommand};
+use crate::history::{parse_log, parse_name_status, parse_tags, DocumentRevision, PathChange, Revision, Snapshot, LOG_FORMAT, TAG_FORMAT};
+use crate::lock::FileLock;
+use crate::json_document::{map_from_str, map_into_string, Document};
+use crate::merge::{merge_document, DocumentConflict, KeyConflict, MergePolicy, MergeTimes};
+use crate::signing::CommitSigning;
+use gitwrap::wrap_command::WrapCommand;
+use gitwrap::{add, clean, commit, config, generic, git, reset};
+use serde_json::{Map, Value};
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
 use std::fs;
-use std::path::{Path, PathBuf};
-use gitwrap::wrap_command::FnOptionArg;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 pub enum RepoStoreError {
     Initialize(Box<dyn Error>),
@@ -16,6 +26,21 @@ pub enum RepoStoreError {
     Push(Box<dyn Error>),
     Commit(Box<dyn Error>),
     Clean(Box<dyn Error>),
+    Read(Box<dyn Error>),
+    Write(Box<dyn Error>),
+    InvalidPath(String),
+    History(Box<dyn Error>),
+    Conflict(Vec<KeyConflict>),
+    RevisionConflict(String),
+    PushRejected(Box<dyn Error>),
+    SyncFailed(u32, Box<RepoStoreError>),
+    Credentials(Box<dyn Error>),
+    Branch(Box<dyn Error>),
+    MergeConflict(Vec<DocumentConflict>),
+    Snapshot(Box<dyn Error>),
+    UntrustedDocument(String),
+    Locked(Box<dyn Error>),
+    Interrupted(Box<dyn Error>),
 }
 
 impl Error for RepoStoreError {}
@@ -29,6 +54,27 @@ impl RepoStoreError {
             RepoStoreError::Push(e) => write!(f, "failed to push repo: {}", e),
             RepoStoreError::Commit(e) => write!(f, "failed to commit repo: {}", e),
             RepoStoreError::Clean(e) => write!(f, "failed to clean repo: {}", e),
+            RepoStoreError::Read(e) => write!(f, "failed to read document: {}", e),
+            RepoStoreError::Write(e) => write!(f, "failed to write document: {}", e),
+            RepoStoreError::InvalidPath(p) => write!(f, "invalid document path: {}", p),
+            RepoStoreError::History(e) => write!(f, "failed to read history: {}", e),
+            RepoStoreError::PushRejected(e) => write!(f, "push rejected by remote: {}", e),
+            RepoStoreError::SyncFailed(n, e) => write!(f, "failed to sync repo after {} attempts: {}", n, e),
+            RepoStoreError::Credentials(e) => write!(f, "failed to get credentials: {}", e),
+            RepoStoreError::Branch(e) => write!(f, "failed to manage branch: {}", e),
+            RepoStoreError::Snapshot(e) => write!(f, "failed to manage snapshot: {}", e),
+            RepoStoreError::RevisionConflict(p) => write!(f, "document {} changed since it was read", p),
+            RepoStoreError::UntrustedDocument(p) => write!(f, "document {} is not committed with a trusted signature", p),
+            RepoStoreError::Locked(e) => write!(f, "repo is locked: {}", e),
+            RepoStoreError::Interrupted(e) => write!(f, "operation interrupted: {}", e),
+            RepoStoreError::Conflict(c) => {
+                let keys: Vec<String> = c.iter().map(|k| format!("{}:{}", k.path(), k.key())).collect();
+                write!(f, "unresolved merge conflicts: {}", keys.join(", "))
+            },
+            RepoStoreError::MergeConflict(c) => {
+                let paths: Vec<&str> = c.iter().map(DocumentConflict::path).collect();
+                write!(f, "conflicting documents: {}", paths.join(", "))
+            },
         }
     }
 }
@@ -45,30 +91,263 @@ impl Debug for RepoStoreError {
     }
 }
 
-//pub type FnModify<T> = dyn Fn(&dyn RepoStore<T>) -> Result<(), Box<dyn Error>>;
+/// How far [RepoStore::clean] goes when discarding local state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CleanMode {
+    /// Discards uncommitted edits and untracked documents, keeping local commits
+    WorkingTree,
+    /// Also hard-resets the branch to its remote tracking branch, dropping unpushed commits
+    Remote,
+}
+
+/// Retry schedule of [RepoStore::sync]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncPolicy {
+    attempts: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl SyncPolicy {
+    /// Pushes at most `attempts` times, waiting `backoff` before the first retry and doubling it up to `max_backoff`
+    pub fn new(attempts: u32, backoff: Duration, max_backoff: Duration) -> Self {
+        Self {
+            attempts: attempts.max(1),
+            backoff,
+            max_backoff,
+        }
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Wait before the given retry, starting at 1
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+impl Default for SyncPolicy {
+    fn default() -> Self {
+        Self::new(5, Duration::from_millis(100), Duration::from_secs(2))
+    }
+}
+
+/// How long a [GitStore] waits for the lock on its repository, held by other stores and processes while they change it.
+/// The lock is an advisory lock of the operating system, released when the process holding it dies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockPolicy {
+    timeout: Duration,
+    retry: Duration,
+}
+
+impl LockPolicy {
+    /// Waits at most `timeout` for the lock, checking it again every `retry`
+    pub fn new(timeout: Duration, retry: Duration) -> Self {
+        Self {
+            timeout,
+            retry,
+        }
+    }
 
-pub trait RepoStore<T: PartialEq> {
+    /// Fails at once when the lock is held
+    pub fn no_wait() -> Self {
+        Self::new(Duration::ZERO, Duration::ZERO)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn retry(&self) -> Duration {
+        self.retry
+    }
+}
+
+impl Default for LockPolicy {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(5), Duration::from_millis(10))
+    }
+}
+
+/// The lock a store takes while it changes its repository, held until dropped, see [RepoStore::lock_store]
+pub struct StoreLock {
+    _lock: Option<FileLock>,
+}
+
+/// A document store backed by a repository.
+/// Documents are addressed by their path relative to the repository root.
+pub trait RepoStore<T> where T: Debug + Clone + serde::Serialize + for<'a> serde::Deserialize<'a> {
     fn initialize(&self) -> Result<(), RepoStoreError>;
-    fn document(&self, name: &str) -> impl RepoDocument<T>;
+    fn document(&self, path: &str) -> Result<Document<T>, RepoStoreError>;
+    fn write_document(&self, path: &str, document: &mut Document<T>) -> Result<(), RepoStoreError>;
+    fn remove_document(&self, path: &str) -> Result<(), RepoStoreError>;
+    /// Writes the document only if its stored revision is still `expected`, `None` meaning it must not exist yet.
+    /// On success the document carries its new revision.
+    fn write_document_if(&self, path: &str, document: &mut Document<T>, expected: Option<&str>) -> Result<(), RepoStoreError>;
+    /// Removes the document only if its stored revision is still `expected`
+    fn remove_document_if(&self, path: &str, expected: &str) -> Result<(), RepoStoreError>;
+    /// Current revision of the stored document, `None` when it does not exist
+    fn revision(&self, path: &str) -> Result<Option<String>, RepoStoreError>;
+    /// Lists the file names of the documents directly under `dir`, sorted and skipping hidden files
+    fn documents(&self, dir: &str) -> Result<Vec<String>, RepoStoreError>;
+    fn create_dir(&self, dir: &str) -> Result<(), RepoStoreError>;
+    fn remove_dir(&self, dir: &str) -> Result<(), RepoStoreError>;
+    /// Loads the document as it was at `revision`
+    fn document_at(&self, path: &str, revision: &Revision) -> Result<Document<T>, RepoStoreError>;
+    /// Revision of the document at `revision`, `None` when it does not exist there
+    fn revision_at(&self, path: &str, revision: &Revision) -> Result<Option<String>, RepoStoreError>;
+    /// Lists the commits that changed the document, newest first
+    fn revisions(&self, path: &str) -> Result<Vec<DocumentRevision>, RepoStoreError>;
+    /// Resolves `revision` into a commit id
+    fn resolve(&self, revision: &Revision) -> Result<String, RepoStoreError>;
+    /// Lists the commits of the first parent history of `to` not reachable from `from`, oldest first.
+    /// `HEAD` has no commits before the first commit of the active branch.
+    fn commits(&self, from: Option<&Revision>, to: &Revision) -> Result<Vec<DocumentRevision>, RepoStoreError>;
+    /// Lists the commits of the first parent history of `to` changing the file at `path` from their first parent, oldest first
+    fn document_commits(&self, path: &str, to: &Revision) -> Result<Vec<DocumentRevision>, RepoStoreError>;
     fn pull(&self, rebase: bool) -> Result<(), RepoStoreError>;
     fn push(&self) -> Result<(), RepoStoreError>;
-    fn commit(&self, msg: &str) -> Result<(), RepoStoreError>;
-    fn clean(&self) -> Result<(), RepoStoreError>;
+    /// Commits the changes made to the given documents, all of them when `None`,
+    /// appending to `msg` the [CommitTrailers](crate::audit::CommitTrailers) built from `options` and the changes
+    fn commit_with(&self, msg: &str, paths: Option<&[String]>, options: &CommitOptions) -> Result<(), RepoStoreError>;
+    /// Restores the given documents to their last committed state, removing the ones never committed
+    fn restore_documents(&self, paths: &[String]) -> Result<(), RepoStoreError>;
+    /// Discards local changes according to `mode`, returning the paths of the documents thrown away
+    fn clean(&self, mode: CleanMode) -> Result<Vec<String>, RepoStoreError>;
+    /// Name of the active branch
+    fn current_branch(&self) -> Result<String, RepoStoreError>;
+    /// Lists the local branches, sorted
+    fn branches(&self) -> Result<Vec<String>, RepoStoreError>;
+    /// Creates the branch `name` at `from`, without switching to it
+    fn create_branch(&self, name: &str, from: &Revision) -> Result<(), RepoStoreError>;
+    /// Makes `name` the active branch, its documents replacing the current ones
+    fn switch_branch(&self, name: &str) -> Result<(), RepoStoreError>;
+    /// Deletes the local branch `name`, which must not be the active one
+    fn delete_branch(&self, name: &str) -> Result<(), RepoStoreError>;
+    /// Merges the branch `name` into the active one, JSON documents key by key with the store merge policy.
+    /// Fails with [RepoStoreError::MergeConflict] listing the conflicting documents, leaving the active branch unchanged.
+    fn merge_branch(&self, name: &str) -> Result<(), RepoStoreError>;
+    /// Takes the snapshot `name` of the last commit of the active branch, as an annotated tag with `msg`
+    fn create_snapshot(&self, name: &str, msg: &str) -> Result<(), RepoStoreError>;
+    /// Lists the snapshots, newest first
+    fn snapshots(&self) -> Result<Vec<Snapshot>, RepoStoreError>;
+    /// Commits the documents of the snapshot `name` on top of the active branch, keeping the history since.
+    /// Uncommitted changes are discarded.
+    fn restore_snapshot(&self, name: &str, msg: &str) -> Result<(), RepoStoreError>;
+    /// Lists the paths of all the files at `revision`, sorted
+    fn paths_at(&self, revision: &Revision) -> Result<Vec<String>, RepoStoreError>;
+    /// Lists the files differing between `from` and `to`, sorted by path, all the files of `to` being added when `from` is `None`
+    fn changed_paths(&self, from: Option<&Revision>, to: &Revision) -> Result<Vec<PathChange>, RepoStoreError>;
+    /// Id of the last commit of the active branch, `None` before the first commit
+    fn head(&self) -> Result<Option<String>, RepoStoreError>;
+
+    /// Takes the lock serializing the changes made to the repository by stores of any process, so that a sequence
+    /// of calls is not interleaved with the changes of others. The calls made by the thread holding it take it again
+    /// without waiting. Stores holding their changes in process until they commit them atomically take no lock.
+    fn lock_store(&self) -> Result<StoreLock, RepoStoreError> {
+        Ok(StoreLock { _lock: None })
+    }
+
+    fn commit(&self, msg: &str) -> Result<(), RepoStoreError> {
+        self.commit_with(msg, None, &CommitOptions::default())
+    }
+
+    /// Commits only the changes made to the given documents
+    fn commit_documents(&self, msg: &str, paths: &[String]) -> Result<(), RepoStoreError> {
+        self.commit_with(msg, Some(paths), &CommitOptions::default())
+    }
+
+    /// Pushes the local commits, rebasing them onto the remote and retrying while the push is rejected.
+    /// Fails with [RepoStoreError::SyncFailed] wrapping the last error once the attempts run out
+    /// or when the rebase leaves a conflict.
+    fn sync(&self, policy: &SyncPolicy) -> Result<(), RepoStoreError> {
+        let mut attempt = 1;
+        loop {
+            match self.push() {
+                Ok(_) => return Ok(()),
+                Err(RepoStoreError::PushRejected(_)) if attempt < policy.attempts() => {
+                    thread::sleep(policy.delay(attempt));
+                    if let Err(e) = self.pull(true) {
+                        return Err(RepoStoreError::SyncFailed(attempt, Box::new(e)));
+                    }
+                    attempt += 1;
+                },
+                Err(e @ RepoStoreError::PushRejected(_)) => return Err(RepoStoreError::SyncFailed(attempt, Box::new(e))),
+                Err(e) => return Err(e),
+            }
+        }
+    }
 }
 
+/// Fails with [RepoStoreError::InvalidPath] unless `path` is relative, has no `..` component and is not under `.git`
+fn check_path(path: &str) -> Result<(), RepoStoreError> {
+    let mut components = Path::new(path).components().filter(|c| *c != Component::CurDir).peekable();
+    let in_git_dir = matches!(components.peek(), Some(Component::Normal(name)) if name.eq_ignore_ascii_case(".git"));
+    if in_git_dir || components.any(|c| !matches!(c, Component::Normal(_))) {
+        return Err(RepoStoreError::InvalidPath(String::from(path)));
+    }
+    Ok(())
+}
+
+/// Whether `path` is `dir` itself or lies under it, `dir` being given with or without a trailing `/`.
+/// Every store filters the paths of a directory with it.
+pub(crate) fn under(path: &str, dir: &str) -> bool {
+    path == dir || path.starts_with(format!("{}/", dir.trim_end_matches('/')).as_str())
+}
 
 #[derive(Debug, Clone, Default)]
 pub struct GitAuth {
-    user: Option<String>,
-    password: Option<String>,
-    token: Option<String>,
+    provider: Option<Arc<dyn CredentialProvider>>,
     insecure: bool
 }
 
+impl GitAuth {
+    /// Basic authentication with user and password
+    pub fn basic(user: &str, password: &str) -> Self {
+        Self::provider(StaticCredentials::new(Credential::Basic(String::from(user), String::from(password))))
+    }
+
+    /// Bearer token authentication
+    pub fn bearer(token: &str) -> Self {
+        Self::provider(StaticCredentials::new(Credential::Bearer(String::from(token))))
+    }
+
+    /// Authentication with the credentials supplied by `provider`
+    pub fn provider<P: CredentialProvider + 'static>(provider: P) -> Self {
+        Self {
+            provider: Some(Arc::new(provider)),
+            ..Default::default()
+        }
+    }
+
+    /// Resolves the credentials for `url`, if any
+    pub fn credential(&self, url: &str) -> Result<Option<Credential>, RepoStoreError> {
+        match &self.provider {
+            Some(provider) => match provider.credential(url) {
+                Ok(credential) => Ok(credential),
+                Err(e) => Err(RepoStoreError::Credentials(e)),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Disables TLS certificate verification when set
+    pub fn insecure(self, insecure: bool) -> Self {
+        Self {
+            insecure,
+            ..self
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct GitCommit {
     commit_user: String,
     commit_email: String,
+    signing: Option<CommitSigning>,
 }
 
 impl GitCommit {
@@ -76,15 +355,73 @@ impl GitCommit {
         Self {
             commit_user: String::from(commit_user),
             commit_email: String::from(commit_email),
+            signing: None,
+        }
+    }
+
+    /// Signs the commits made by the store
+    pub fn signing(self, signing: CommitSigning) -> Self {
+        Self {
+            signing: Some(signing),
+            ..self
         }
     }
 
     pub fn pair(&self) -> (String, String) {
         (self.commit_user.clone(), self.commit_email.clone())
     }
+
+    pub fn commit_signing(&self) -> Option<&CommitSigning> {
+        self.signing.as_ref()
+    }
+
+    /// Identity of the store, recorded as author and committer unless a commit names others
+    pub fn identity(&self) -> Identity {
+        Identity::new(self.commit_user.as_str(), self.commit_email.as_str())
+    }
 }
 
-#[derive(Debug, Clone, Default)]
+/// Name and email of the author or committer of a commit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    name: String,
+    email: String,
+}
+
+impl Identity {
+    pub fn new(name: &str, email: &str) -> Self {
+        Self {
+            name: String::from(name),
+            email: String::from(email),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn email(&self) -> &str {
+        self.email.as_str()
+    }
+
+    /// Environment variables making git record this identity as the author of a commit
+    pub(crate) fn author_env(&self) -> Vec<(String, String)> {
+        vec![
+            (String::from("GIT_AUTHOR_NAME"), self.name.clone()),
+            (String::from("GIT_AUTHOR_EMAIL"), self.email.clone()),
+        ]
+    }
+
+    /// Environment variables making git record this identity as the committer of a commit, or the tagger of a tag
+    pub(crate) fn committer_env(&self) -> Vec<(String, String)> {
+        vec![
+            (String::from("GIT_COMMITTER_NAME"), self.name.clone()),
+            (String::from("GIT_COMMITTER_EMAIL"), self.email.clone()),
+        ]
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct GitStore {
     name: String,
     repo_url: String,
@@ -92,6 +429,31 @@ pub struct GitStore {
     repo_path: PathBuf,
     branch: Option<String>,
     commit: GitCommit,
+    merge_policy: MergePolicy,
+    backend: Arc<dyn GitBackend>,
+    allowed_signers: Option<PathBuf>,
+    require_signatures: bool,
+    lock_policy: LockPolicy,
+    clone_options: CloneOptions,
+}
+
+impl Default for GitStore {
+    fn default() -> Self {
+        Self {
+            name: String::default(),
+            repo_url: String::default(),
+            auth: GitAuth::default(),
+            repo_path: PathBuf::default(),
+            branch: None,
+            commit: GitCommit::default(),
+            merge_policy: MergePolicy::default(),
+            backend: Arc::new(GitwrapBackend),
+            allowed_signers: None,
+            require_signatures: false,
+            lock_policy: LockPolicy::default(),
+            clone_options: CloneOptions::default(),
+        }
+    }
 }
 
 impl GitStore {
@@ -103,74 +465,459 @@ impl GitStore {
             branch: branch.map(String::from),
             auth,
             commit,
+            merge_policy: MergePolicy::default(),
+            backend: Arc::new(GitwrapBackend),
+            allowed_signers: None,
+            require_signatures: false,
+            lock_policy: LockPolicy::default(),
+            clone_options: CloneOptions::default(),
+        }
+    }
+
+    /// Sets how key conflicts in JSON documents are resolved when pulling
+    pub fn merge_policy(self, merge_policy: MergePolicy) -> Self {
+        Self {
+            merge_policy,
+            ..self
+        }
+    }
+
+    /// Sets the backend running the operations of [GitBackend], the git command line by default.
+    /// The other operations run the git command line with any backend.
+    pub fn backend<B: GitBackend + 'static>(self, backend: B) -> Self {
+        Self {
+            backend: Arc::new(backend),
+            ..self
         }
     }
 
-    fn clone(&self) -> Result<(), RepoStoreError> {
-        let mut custom_options : Vec<FnOptionArg> = vec![];
-        if let Some(branch) = self.branch.clone() {
-            custom_options.push(clone::branch(branch.clone().as_str()))
+    /// Sets the file listing the SSH keys trusted to sign commits, in the format of `ssh-keygen` allowed signers.
+    /// OpenPGP signatures are trusted according to the gpg keyring.
+    pub fn allowed_signers(self, path: &str) -> Self {
+        Self {
+            allowed_signers: Some(Path::new("").join(path)),
+            ..self
         }
-        if let Some(auth_header) = self.build_auth_header() {
-            custom_options.push(clone::config("http.extraHeader", &auth_header))
+    }
+
+    /// Refuses to load documents not last changed by a commit with a trusted signature, or with uncommitted changes
+    pub fn require_signatures(self, require_signatures: bool) -> Self {
+        Self {
+            require_signatures,
+            ..self
         }
-        if self.auth.insecure {
-            custom_options.push(clone::config("http.sslVerify", "false"))
+    }
+
+    /// Sets how long changes wait for the lock other stores on the same repository hold while changing it
+    pub fn lock_policy(self, lock_policy: LockPolicy) -> Self {
+        Self {
+            lock_policy,
+            ..self
         }
-        let cmd = clone::clone()
-            .add_option(clone::repository(self.repo_url.as_str()))
-            .add_option(clone::directory(self.repo_path.to_str().unwrap()))
-            .add_options(custom_options);
+    }
 
-        match cmd.run() {
+    /// Limits the history, objects and directories the store clones, see [CloneOptions].
+    /// The store then only reads and writes documents inside its sparse checkout.
+    pub fn clone_options(self, clone_options: CloneOptions) -> Self {
+        Self {
+            clone_options,
+            ..self
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn repo_path(&self) -> &Path {
+        self.repo_path.as_path()
+    }
+
+    /// Whether documents are only loaded when last changed by a commit with a trusted signature, see [GitStore::require_signatures]
+    #[cfg(feature = "async")]
+    pub(crate) fn requires_signatures(&self) -> bool {
+        self.require_signatures
+    }
+
+    fn repo_dir(&self) -> &str {
+        self.repo_path.to_str().unwrap()
+    }
+
+    fn remote(&self) -> Result<Remote, RepoStoreError> {
+        let credential = self.auth.credential(self.repo_url.as_str())?;
+        Ok(Remote::new(self.repo_url.as_str(), credential).insecure(self.auth.insecure))
+    }
+
+    fn clone_repo(&self) -> Result<(), RepoStoreError> {
+        match self.backend.clone_repo(&self.remote()?, &self.repo_path, self.branch.as_deref(), &self.clone_options) {
             Ok(_) => Ok(()),
             Err(e) => Err(RepoStoreError::Clone(Box::new(e))),
         }
     }
 
-    fn build_auth_header(&self) -> Option<String> {
-        match self.auth.token.clone() {
+    /// Fetches the remote branches without integrating them
+    pub fn fetch(&self) -> Result<(), RepoStoreError> {
+        match self.backend.fetch(&self.repo_path, &self.remote()?) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(RepoStoreError::Pull(Box::new(e))),
+        }
+    }
+
+    /// Whether the history stops at the clone depth, missing the commits before it
+    pub fn is_shallow(&self) -> bool {
+        is_shallow(&self.repo_path)
+    }
+
+    /// Fetches the history of the remote branches down to `depth` commits from their tips, all of it when `None`,
+    /// to read past the history of a shallow clone
+    pub fn fetch_history(&self, depth: Option<u32>) -> Result<(), RepoStoreError> {
+        let _lock = self.lock()?;
+        match self.backend.fetch_history(&self.repo_path, &self.remote()?, depth) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(RepoStoreError::History(Box::new(e))),
+        }
+    }
+
+    /// A git command run in the repository, returning only its stdout
+    pub(crate) fn git(&self, cmd: &str) -> EnvCommand {
+        EnvCommand::new(cmd).current_dir(self.repo_dir())
+    }
+
+    /// A git command run in the repository that may fetch missing objects,
+    /// passed the credentials of the remote when the store is a partial clone
+    pub(crate) fn fetching_git(&self, cmd: &str) -> Result<EnvCommand, RepoStoreError> {
+        let cmd = self.git(cmd);
+        match self.clone_options.clone_filter() {
+            Some(_) => Ok(cmd.envs(remote_env(&self.remote()?))),
+            None => Ok(cmd),
+        }
+    }
+
+    /// Location in the working tree of the document or directory `path`, checked with [check_path]
+    pub(crate) fn doc_path(&self, path: &str) -> Result<PathBuf, RepoStoreError> {
+        check_path(path)?;
+        Ok(self.repo_path.join(path))
+    }
+
+    /// Fails with `error` for paths outside the sparse checkout
+    pub(crate) fn check_checked_out(&self, path: &str, error: fn(Box<dyn Error>) -> RepoStoreError) -> Result<(), RepoStoreError> {
+        if self.clone_options.is_checked_out(path) {
+            Ok(())
+        } else {
+            Err(error(format!("{} is outside the sparse checkout", path).into()))
+        }
+    }
+
+    /// Removes the authorization headers earlier versions persisted in the repository configuration,
+    /// leaving any other setting, such as an ssh command, untouched
+    fn remove_persisted_credentials(&self) -> Result<(), RepoStoreError> {
+        let cmd = self.git("config")
+            .add_option(args(&["--local", "--name-only", "--get-regexp", r"^http\..*extraheader$"]));
+        // exits with an error when no entry matches
+        let keys = match cmd.run() {
+            Ok(o) => o,
+            Err(_) => return Ok(()),
+        };
+        for key in keys.lines().filter(|k| !k.is_empty()) {
+            let cmd_unset = self.git("config")
+                .add_option(args(&["--local", "--unset-all", key]));
+            if let Err(e) = cmd_unset.run() {
+                return Err(RepoStoreError::Initialize(Box::new(e)));
+            }
+        }
+        Ok(())
+    }
+
+    fn set_repo_config(&self) -> Result<(), RepoStoreError> {
+        let (user, email) = self.commit.pair();
+        let mut entries = vec![("user.email", email), ("user.name", user)];
+        match self.commit.commit_signing() {
+            Some(signing) => entries.extend(signing.config_entries()),
             None => {
-                if let Some(user) = self.auth.user.clone() {
-                    if let Some(password) = self.auth.password.clone() {
-                        let basic_token = BASE64_STANDARD.encode(format!("{}:{}", user, password));
-                        let basic_auth = format!("Authorization: Basic {}", basic_token);
-                        return Some(basic_auth)
+                // stop signing when a previous configuration did, exiting with an error when it did not
+                let _ = config::config()
+                    .add_option(args(&["--local", "--unset", "commit.gpgSign"]))
+                    .current_dir(self.repo_dir())
+                    .run();
+            },
+        }
+        if let Some(allowed_signers) = &self.allowed_signers {
+            entries.push(("gpg.ssh.allowedSignersFile", allowed_signers.to_string_lossy().to_string()));
+        }
+        for (key, value) in entries {
+            let cmd = config::config()
+                .add_option(config::entry(key, value.as_str()));
+            if let Err(e) = cmd.current_dir(self.repo_dir()).run() {
+                return Err(RepoStoreError::Initialize(Box::new(e)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Id git gives to the blob of `content` stored at `path`, in the object format of the repository
+    /// and after the filters and end of line conversions set for the path
+    fn blob_id(&self, path: &str, content: &[u8]) -> Result<String, RepoStoreError> {
+        match self.backend.hash_blob(&self.repo_path, path, content) {
+            Ok(id) => Ok(id),
+            Err(e) => Err(RepoStoreError::Read(Box::new(e))),
+        }
+    }
+
+    /// Fails unless the last commit changing `path` up to `commit` has a trusted signature
+    /// and, when given, `revision` is the version of the document in that commit
+    fn verify_document(&self, path: &str, commit: &str, revision: Option<&str>) -> Result<(), RepoStoreError> {
+        if let Some(revision) = revision {
+            let cmd = self.git("rev-parse")
+                .add_option(args(&["--verify", "--quiet", format!("{}:{}", commit, path).as_str()]));
+            match cmd.run() {
+                Ok(o) if o.trim() == revision => {},
+                _ => return Err(RepoStoreError::UntrustedDocument(String::from(path))),
+            }
+        }
+        let cmd = self.git("log")
+            .add_option(args(&[LOG_FORMAT, "-1", commit, "--", path]));
+        match cmd.run() {
+            Ok(o) if parse_log(&o).first().is_some_and(DocumentRevision::is_trusted) => Ok(()),
+            Ok(_) => Err(RepoStoreError::UntrustedDocument(String::from(path))),
+            Err(e) => Err(RepoStoreError::History(Box::new(e))),
+        }
+    }
+
+    /// Trailers of a commit of the uncommitted changes to `paths`, all of them when `None`
+    fn trailers(&self, paths: Option<&[String]>, options: &CommitOptions, operation: Operation) -> Result<CommitTrailers, RepoStoreError> {
+        let dirty = match self.backend.status(&self.repo_path) {
+            Ok(dirty) => dirty,
+            Err(e) => return Err(RepoStoreError::Commit(Box::new(e))),
+        };
+        let remote = match self.clone_options.clone_filter() {
+            Some(_) => Some(self.remote()?),
+            None => None,
+        };
+        let mut trailers = options.trailers(operation);
+        for path in dirty {
+            if paths.is_some_and(|paths| !paths.iter().any(|p| under(&path, p))) {
+                continue;
+            }
+            let old = match self.backend.head_file(&self.repo_path, &path, remote.as_ref()) {
+                Ok(old) => old,
+                Err(e) => return Err(RepoStoreError::Commit(Box::new(e))),
+            };
+            let new = fs::read_to_string(self.repo_path.join(&path)).ok();
+            trailers.change(&path, old.as_deref(), new.as_deref());
+        }
+        Ok(trailers)
+    }
+
+    fn dirty_paths(&self) -> Result<Vec<String>, RepoStoreError> {
+        match self.backend.status(&self.repo_path) {
+            Ok(paths) => Ok(paths),
+            Err(e) => Err(RepoStoreError::Clean(Box::new(e))),
+        }
+    }
+
+    fn unpushed_paths(&self) -> Result<Vec<String>, RepoStoreError> {
+        // detecting renames may fetch the missing blobs of a partial clone
+        let cmd = self.fetching_git("diff")?
+            .add_option(args(&["--name-only", "-z", "@{upstream}...HEAD"]));
+        match cmd.run() {
+            Ok(o) => Ok(o.split('\0').filter(|p| !p.is_empty()).map(String::from).collect()),
+            Err(e) => Err(RepoStoreError::Clean(Box::new(e))),
+        }
+    }
+
+    /// Whether the active branch has no commit yet
+    fn is_unborn(&self) -> bool {
+        self.git("rev-parse")
+            .add_option(args(&["--verify", "--quiet", "HEAD"]))
+            .run()
+            .is_err()
+    }
+
+    /// Raw content of the file at `path` in the tree of `commit`, read from stdout alone so that
+    /// the progress of fetching a missing blob or warnings printed by git never end up in it
+    pub(crate) fn file_at(&self, commit: &str, path: &str) -> Result<String, RepoStoreError> {
+        let cmd = self.fetching_git("cat-file")?
+            .add_option(args(&["blob", format!("{}:{}", commit, path).as_str()]));
+        match cmd.run() {
+            Ok(o) => Ok(o),
+            Err(e) => Err(RepoStoreError::Read(Box::new(e))),
+        }
+    }
+
+    fn conflicted_paths(&self) -> Result<Vec<String>, RepoStoreError> {
+        let cmd = self.git("diff")
+            .add_option(args(&["--name-only", "-z", "--diff-filter=U"]));
+        match cmd.run() {
+            Ok(o) => Ok(o.split('\0').filter(|p| !p.is_empty()).map(String::from).collect()),
+            Err(e) => Err(RepoStoreError::Pull(Box::new(e))),
+        }
+    }
+
+    /// Content of a conflicted document at an index stage, `None` when absent on that side
+    fn staged_document(&self, stage: u8, path: &str) -> Result<Option<Map<String, Value>>, RepoStoreError> {
+        let cmd = self.git("cat-file")
+            .add_option(args(&["-e", format!(":{}:{}", stage, path).as_str()]));
+        if cmd.run().is_err() {
+            return Ok(None);
+        }
+        let content = self.file_at(format!(":{}", stage).as_str(), path)?;
+        match map_from_str(&content) {
+            Ok(map) => Ok(Some(map)),
+            Err(e) => Err(RepoStoreError::Pull(e)),
+        }
+    }
+
+    fn commit_time(&self, rev: &str) -> i64 {
+        let cmd = self.git("log")
+            .add_option(args(&["-1", "--format=%ct", rev]));
+        match cmd.run() {
+            Ok(o) => o.trim().parse().unwrap_or_default(),
+            Err(_) => 0,
+        }
+    }
+
+    /// Merges the conflicted JSON documents of an interrupted merge or rebase and stages them,
+    /// returning the documents left in conflict.
+    /// While rebasing the local commit is replayed on top of the remote one,
+    /// so index stage 2 holds the remote side and stage 3 the local one.
+    fn resolve_conflicts(&self, rebase: bool) -> Result<Vec<DocumentConflict>, RepoStoreError> {
+        let (ours_stage, theirs_stage, ours_rev, theirs_rev) = if rebase {
+            (3, 2, "REBASE_HEAD", "HEAD")
+        } else {
+            (2, 3, "HEAD", "MERGE_HEAD")
+        };
+        let times = MergeTimes {
+            ours: self.commit_time(ours_rev),
+            theirs: self.commit_time(theirs_rev),
+        };
+        let mut conflicts = Vec::new();
+        for path in self.conflicted_paths()? {
+            if !path.ends_with(".json") {
+                conflicts.push(DocumentConflict::new(&path, vec![]));
+                continue;
+            }
+            let base = self.staged_document(1, &path)?;
+            let ours = self.staged_document(ours_stage, &path)?;
+            let theirs = self.staged_document(theirs_stage, &path)?;
+            match merge_document(&path, base.as_ref(), ours.as_ref(), theirs.as_ref(), &self.merge_policy, times) {
+                Ok(merged) => {
+                    if let Err(e) = fs::write(self.repo_path.join(&path), map_into_string(&merged)) {
+                        return Err(RepoStoreError::Write(Box::new(e)));
                     }
-                }
-                None
+                    let cmd_add = add::add()
+                        .add_option(add::pathspec(&path));
+                    if let Err(e) = cmd_add.current_dir(self.repo_dir()).run() {
+                        return Err(RepoStoreError::Write(Box::new(e)));
+                    }
+                },
+                Err(keys) => conflicts.push(DocumentConflict::new(&path, keys)),
             }
-            Some(token) => {
-                let bearer_auth = format!("Authorization: Bearer  {}", token);
-                Some(bearer_auth)
+        }
+        Ok(conflicts)
+    }
+
+    /// Merges the conflicted JSON documents of an interrupted pull and stages them
+    fn merge_conflicts(&self, rebase: bool) -> Result<(), RepoStoreError> {
+        pull_conflicts(self.resolve_conflicts(rebase)?)
+    }
+
+    /// Resolves the conflicts of an interrupted pull until the merge or rebase completes
+    fn complete_pull(&self, rebase: bool) -> Result<(), RepoStoreError> {
+        loop {
+            self.merge_conflicts(rebase)?;
+            let cmd = if rebase {
+                let no_changes = git("diff")
+                    .add_option(args(&["--cached", "--quiet"]))
+                    .current_dir(self.repo_dir())
+                    .run()
+                    .is_ok();
+                WrapCommand::new("git")
+                    .add_option(generic::config("core.editor", "true"))
+                    .add_option(args(&["rebase", if no_changes { "--skip" } else { "--continue" }]))
+            } else {
+                commit::commit()
+                    .add_option(commit::no_edit())
+            };
+            match cmd.current_dir(self.repo_dir()).run() {
+                Ok(_) => return Ok(()),
+                Err(e) => {
+                    if !rebase || self.conflicted_paths()?.is_empty() {
+                        return Err(RepoStoreError::Pull(Box::new(e)));
+                    }
+                },
             }
         }
     }
 
-    fn set_repo_config(&self) -> Result<(), RepoStoreError> {
-        let (user, email) = self.commit.pair();
-        let cmd = config::config()
-            .add_option(config::entry("user.email", email.as_str()))
-            .add_option(config::entry("user.name", user.as_str()));
-        match cmd.current_dir(self.repo_path.to_str().unwrap()).run() {
-            Ok(_) => Ok(()),
+    fn abort_pull(&self, rebase: bool) {
+        let cmd = git(if rebase { "rebase" } else { "merge" })
+            .add_option(args(&["--abort"]));
+        let _ = cmd.current_dir(self.repo_dir()).run();
+    }
+
+    fn rebase_in_progress(&self) -> bool {
+        let git_dir = self.repo_path.join(".git");
+        git_dir.join("rebase-merge").exists() || git_dir.join("rebase-apply").exists()
+    }
+
+    /// Takes the advisory lock serializing the changes made to the repository by stores of any process
+    fn lock(&self) -> Result<FileLock, RepoStoreError> {
+        let policy = &self.lock_policy;
+        Self::locked(FileLock::acquire(&self.lock_path(), policy.timeout, policy.retry))
+    }
+
+    /// Takes the lock of [GitStore::lock] for a task, waiting for it without blocking the thread
+    #[cfg(feature = "async")]
+    pub(crate) async fn lock_async(&self) -> Result<FileLock, RepoStoreError> {
+        let policy = &self.lock_policy;
+        Self::locked(FileLock::acquire_async(&self.lock_path(), policy.timeout, policy.retry).await)
+    }
+
+    fn lock_path(&self) -> PathBuf {
+        self.repo_path.join(".git").join("gitobi.lock")
+    }
+
+    fn locked(lock: std::io::Result<FileLock>) -> Result<FileLock, RepoStoreError> {
+        match lock {
+            Ok(lock) => Ok(lock),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Err(RepoStoreError::Locked(Box::new(e))),
+            Err(e) => Err(RepoStoreError::Write(Box::new(e))),
+        }
+    }
+
+    /// Takes the advisory lock serializing the initialization of the repository by stores of any process,
+    /// on a file next to the repository as the lock of [GitStore::lock] lives in `.git`, which a clone creates
+    fn initialize_lock(&self) -> Result<FileLock, RepoStoreError> {
+        let Some(name) = self.repo_path.file_name() else {
+            return Err(RepoStoreError::Initialize(format!("no lock file for {}", self.repo_path.display()).into()));
+        };
+        let lock_path = self.repo_path.with_file_name(format!("{}.gitobi.lock", name.to_string_lossy()));
+        if let Some(parent) = lock_path.parent()
+            && let Err(e) = fs::create_dir_all(parent) {
+            return Err(RepoStoreError::Initialize(Box::new(e)));
+        }
+        let policy = &self.lock_policy;
+        match FileLock::acquire(&lock_path, policy.timeout, policy.retry) {
+            Ok(lock) => Ok(lock),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Err(RepoStoreError::Locked(Box::new(e))),
             Err(e) => Err(RepoStoreError::Initialize(Box::new(e))),
         }
     }
 
-    fn is_valid_repo(&self) -> bool {
-        let mut cmd = rev_parse::rev_parse()
-            .add_option(rev_parse::is_inside_work_tree());
-        match cmd.current_dir(self.repo_path.to_str().unwrap()).run() {
-            Ok(o) => o.contains("true"),
-            Err(_) => false,
+    /// Fails unless the stored revision of `path` is `expected`. Callers must hold the store lock.
+    fn check_revision(&self, path: &str, expected: Option<&str>) -> Result<(), RepoStoreError> {
+        let current = RepoStore::<Map<String, Value>>::revision(self, path)?;
+        if current.as_deref() == expected {
+            Ok(())
+        } else {
+            Err(RepoStoreError::RevisionConflict(String::from(path)))
         }
     }
 
     fn create_dir_and_clone(&self) -> Result<(), RepoStoreError> {
         match fs::create_dir_all(&self.repo_path) {
             Ok(_) => {
-                if let Err(e) = self.clone() {
+                if let Err(e) = self.clone_repo() {
                     return Err(RepoStoreError::Initialize(Box::new(e)));
                 }
                 self.set_repo_config()
@@ -180,13 +927,39 @@ impl GitStore {
     }
 }
 
-impl RepoStore<Value> for GitStore {
+static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Hidden file next to `doc_path` a document is written to before being renamed over it, unique to the process and the write
+pub(crate) fn tmp_path(doc_path: &Path) -> PathBuf {
+    let file_name = doc_path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    doc_path.with_file_name(format!(".{}.{}-{}.tmp", file_name, std::process::id(), TMP_COUNTER.fetch_add(1, Ordering::Relaxed)))
+}
+
+/// Fails a pull with the documents it left in conflict: [RepoStoreError::Conflict] with their keys,
+/// or [RepoStoreError::Pull] when a file could not be merged key by key
+pub(crate) fn pull_conflicts(conflicts: Vec<DocumentConflict>) -> Result<(), RepoStoreError> {
+    if let Some(conflict) = conflicts.iter().find(|c| c.keys().is_empty()) {
+        return Err(RepoStoreError::Pull(format!("conflict in {}, which cannot be merged key by key", conflict.path()).into()));
+    }
+    let keys: Vec<KeyConflict> = conflicts.iter().flat_map(|c| c.keys().to_vec()).collect();
+    if keys.is_empty() {
+        Ok(())
+    } else {
+        Err(RepoStoreError::Conflict(keys))
+    }
+}
+
+impl RepoStore<Map<String, Value>> for GitStore {
     fn initialize(&self) -> Result<(), RepoStoreError> {
+        // held while checking the clone, so that no other store removes or clones it meanwhile
+        let _lock = self.initialize_lock()?;
         match fs::exists(&self.repo_path) {
             Ok(exists) => {
                 if exists {
-                    if self.is_valid_repo() {
-                        Ok(())
+                    if self.backend.is_repo(&self.repo_path) {
+                        let _lock = self.lock()?;
+                        self.remove_persisted_credentials()?;
+                        self.set_repo_config()
                     } else {
                         match fs::remove_dir_all(&self.repo_path) {
                             Ok(_) => self.create_dir_and_clone(),
@@ -201,40 +974,474 @@ impl RepoStore<Value> for GitStore {
         }
     }
 
-    fn document(&self, path: &str) -> impl RepoDocument<Value> {
-        JsonDocument::new(self.repo_path.to_str().unwrap(), path)
+    fn document(&self, path: &str) -> Result<Document<Map<String, Value>>, RepoStoreError> {
+        let doc_path = self.doc_path(path)?;
+        self.check_checked_out(path, RepoStoreError::Read)?;
+        // the revision is computed from the content read, writes replacing documents whole
+        let content = match fs::read(doc_path) {
+            Ok(content) => content,
+            Err(e) => return Err(RepoStoreError::Read(Box::new(e))),
+        };
+        let revision = self.blob_id(path, &content)?;
+        if self.require_signatures {
+            self.verify_document(path, "HEAD", Some(&revision))?;
+        }
+        match Document::load(&mut content.as_slice(), map_from_str) {
+            Ok(mut doc) => {
+                doc.set_revision(Some(revision));
+                Ok(doc)
+            },
+            Err(e) => Err(RepoStoreError::Read(Box::new(e))),
+        }
     }
 
-    fn pull(&self, rebase: bool) -> Result<(), RepoStoreError> {
-        let mut cmd = pull::pull();
-        if rebase {
-            cmd = cmd.add_option(pull::rebase(""));
+    fn write_document(&self, path: &str, document: &mut Document<Map<String, Value>>) -> Result<(), RepoStoreError> {
+        let doc_path = self.doc_path(path)?;
+        self.check_checked_out(path, RepoStoreError::Write)?;
+        let _lock = self.lock()?;
+        if let Some(parent) = doc_path.parent()
+            && let Err(e) = fs::create_dir_all(parent) {
+            return Err(RepoStoreError::Write(Box::new(e)));
+        }
+        // write aside and rename, so readers never see a partially written document
+        let tmp_path = tmp_path(&doc_path);
+        let written = match fs::File::create(&tmp_path) {
+            Ok(mut file) => document.write(&mut file, map_into_string),
+            Err(e) => return Err(RepoStoreError::Write(Box::new(e))),
+        };
+        if let Err(e) = written {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(RepoStoreError::Write(Box::new(e)));
+        }
+        match fs::rename(&tmp_path, &doc_path) {
+            Ok(_) => {
+                document.set_revision(self.revision(path)?);
+                Ok(())
+            },
+            Err(e) => {
+                let _ = fs::remove_file(&tmp_path);
+                Err(RepoStoreError::Write(Box::new(e)))
+            },
+        }
+    }
+
+    fn write_document_if(&self, path: &str, document: &mut Document<Map<String, Value>>, expected: Option<&str>) -> Result<(), RepoStoreError> {
+        let _lock = self.lock()?;
+        self.check_revision(path, expected)?;
+        self.write_document(path, document)
+    }
+
+    fn remove_document_if(&self, path: &str, expected: &str) -> Result<(), RepoStoreError> {
+        let _lock = self.lock()?;
+        self.check_revision(path, Some(expected))?;
+        self.remove_document(path)
+    }
+
+    fn revision(&self, path: &str) -> Result<Option<String>, RepoStoreError> {
+        let doc_path = self.doc_path(path)?;
+        if !doc_path.is_file() {
+            return Ok(None);
+        }
+        match fs::read(doc_path) {
+            Ok(content) => Ok(Some(self.blob_id(path, &content)?)),
+            Err(e) => Err(RepoStoreError::Read(Box::new(e))),
+        }
+    }
+
+    fn remove_document(&self, path: &str) -> Result<(), RepoStoreError> {
+        let doc_path = self.doc_path(path)?;
+        self.check_checked_out(path, RepoStoreError::Write)?;
+        let _lock = self.lock()?;
+        match fs::remove_file(doc_path) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(RepoStoreError::Write(Box::new(e))),
+        }
+    }
+
+    fn documents(&self, dir: &str) -> Result<Vec<String>, RepoStoreError> {
+        let dir_path = self.doc_path(dir)?;
+        self.check_checked_out(format!("{}/", dir.trim_end_matches('/')).as_str(), RepoStoreError::Read)?;
+        match fs::read_dir(dir_path) {
+            Ok(entries) => {
+                let mut names = Vec::new();
+                for entry in entries {
+                    match entry {
+                        Ok(entry) => {
+                            let name = entry.file_name().to_string_lossy().to_string();
+                            if !name.starts_with('.') && entry.path().is_file() {
+                                names.push(name);
+                            }
+                        },
+                        Err(e) => return Err(RepoStoreError::Read(Box::new(e))),
+                    }
+                }
+                names.sort();
+                Ok(names)
+            },
+            Err(e) => Err(RepoStoreError::Read(Box::new(e))),
+        }
+    }
+
+    fn create_dir(&self, dir: &str) -> Result<(), RepoStoreError> {
+        let dir_path = self.doc_path(dir)?;
+        self.check_checked_out(format!("{}/", dir.trim_end_matches('/')).as_str(), RepoStoreError::Write)?;
+        let _lock = self.lock()?;
+        if let Err(e) = fs::create_dir_all(&dir_path) {
+            return Err(RepoStoreError::Write(Box::new(e)));
         }
-        match cmd.current_dir(self.repo_path.to_str().unwrap()).run() {
+        // git does not track empty directories
+        match fs::write(dir_path.join(".gitkeep"), "") {
             Ok(_) => Ok(()),
+            Err(e) => Err(RepoStoreError::Write(Box::new(e))),
+        }
+    }
+
+    fn remove_dir(&self, dir: &str) -> Result<(), RepoStoreError> {
+        let dir_path = self.doc_path(dir)?;
+        // the root holds the repository itself
+        if Path::new(dir).components().all(|c| c == Component::CurDir) {
+            return Err(RepoStoreError::InvalidPath(String::from(dir)));
+        }
+        self.check_checked_out(format!("{}/", dir.trim_end_matches('/')).as_str(), RepoStoreError::Write)?;
+        let _lock = self.lock()?;
+        match fs::remove_dir_all(dir_path) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(RepoStoreError::Write(Box::new(e))),
+        }
+    }
+
+    fn document_at(&self, path: &str, revision: &Revision) -> Result<Document<Map<String, Value>>, RepoStoreError> {
+        let commit = self.resolve(revision)?;
+        if self.require_signatures {
+            self.verify_document(path, commit.as_str(), None)?;
+        }
+        let content = self.file_at(commit.as_str(), path)?;
+        let cmd = self.git("rev-parse")
+            .add_option(args(&[format!("{}:{}", commit, path).as_str()]));
+        let revision = match cmd.run() {
+            Ok(o) => o.trim().to_string(),
+            Err(e) => return Err(RepoStoreError::Read(Box::new(e))),
+        };
+        match Document::load(&mut content.as_bytes(), map_from_str) {
+            Ok(mut doc) => {
+                doc.set_revision(Some(revision));
+                Ok(doc)
+            },
+            Err(e) => Err(RepoStoreError::Read(Box::new(e))),
+        }
+    }
+
+    fn revision_at(&self, path: &str, revision: &Revision) -> Result<Option<String>, RepoStoreError> {
+        let commit = self.resolve(revision)?;
+        let cmd = self.git("rev-parse")
+            .add_option(args(&["--verify", "--quiet", format!("{}:{}", commit, path).as_str()]));
+        // fails quietly when the path does not exist in the commit
+        Ok(cmd.run().ok().map(|o| o.trim().to_string()))
+    }
+
+    fn resolve(&self, revision: &Revision) -> Result<String, RepoStoreError> {
+        let cmd = match revision {
+            Revision::Rev(rev) => self.git("rev-parse")
+                .add_option(args(&["--verify", "--quiet", format!("{}^{{commit}}", rev).as_str()])),
+            Revision::AsOf(timestamp) => self.git("rev-list")
+                .add_option(args(&["-1", format!("--before=@{}", timestamp).as_str(), "HEAD"])),
+        };
+        match cmd.run() {
+            Ok(o) if !o.trim().is_empty() => Ok(o.trim().to_string()),
+            Ok(_) => Err(RepoStoreError::History(format!("no commit found for {:?}", revision).into())),
+            Err(e) => Err(RepoStoreError::History(Box::new(e))),
+        }
+    }
+
+    fn revisions(&self, path: &str) -> Result<Vec<DocumentRevision>, RepoStoreError> {
+        let cmd = self.git("log")
+            .add_option(args(&[LOG_FORMAT, "--", path]));
+        match cmd.run() {
+            Ok(o) => Ok(parse_log(&o)),
+            Err(e) => Err(RepoStoreError::History(Box::new(e))),
+        }
+    }
+
+    fn commits(&self, from: Option<&Revision>, to: &Revision) -> Result<Vec<DocumentRevision>, RepoStoreError> {
+        if *to == Revision::rev("HEAD") && self.is_unborn() {
+            return Ok(vec![]);
+        }
+        let mut range = self.resolve(to)?;
+        if let Some(from) = from {
+            range = format!("{}..{}", self.resolve(from)?, range);
+        }
+        let cmd = self.git("log")
+            .add_option(args(&[LOG_FORMAT, "--first-parent", "--reverse", range.as_str()]));
+        match cmd.run() {
+            Ok(o) => Ok(parse_log(&o)),
+            Err(e) => Err(RepoStoreError::History(Box::new(e))),
+        }
+    }
+
+    fn document_commits(&self, path: &str, to: &Revision) -> Result<Vec<DocumentRevision>, RepoStoreError> {
+        if *to == Revision::rev("HEAD") && self.is_unborn() {
+            return Ok(vec![]);
+        }
+        let to = self.resolve(to)?;
+        let cmd = self.git("log")
+            .add_option(args(&[LOG_FORMAT, "--first-parent", "--reverse", to.as_str(), "--", path]));
+        match cmd.run() {
+            Ok(o) => Ok(parse_log(&o)),
+            Err(e) => Err(RepoStoreError::History(Box::new(e))),
+        }
+    }
+
+    fn pull(&self, rebase: bool) -> Result<(), RepoStoreError> {
+        let _lock = self.lock()?;
+        match self.backend.pull(&self.repo_path, &self.remote()?, rebase) {
+            Ok(_) => Ok(()),
+            Err(GitBackendError::Conflict(_)) => {
+                // a backend may fall back to merging when rebasing stops on a conflict
+                let rebase = self.rebase_in_progress();
+                match self.complete_pull(rebase) {
+                    Ok(_) => Ok(()),
+                    Err(e) => {
+                        self.abort_pull(rebase);
+                        Err(e)
+                    },
+                }
+            },
             Err(e) => Err(RepoStoreError::Pull(Box::new(e))),
         }
     }
 
     fn push(&self) -> Result<(), RepoStoreError> {
-        let cmd = push::push();
-        match cmd.current_dir(self.repo_path.to_str().unwrap()).run() {
+        let _lock = self.lock()?;
+        match self.backend.push(&self.repo_path, &self.remote()?) {
             Ok(_) => Ok(()),
+            Err(e @ GitBackendError::Rejected(_)) => Err(RepoStoreError::PushRejected(Box::new(e))),
             Err(e) => Err(RepoStoreError::Push(Box::new(e))),
         }
     }
 
-    fn commit(&self, msg: &str) -> Result<(), RepoStoreError> {
-        let cmd_commit = commit::commit()
-            .add_option(commit::all())
-            .add_option(commit::message(msg));
-        match cmd_commit.current_dir(self.repo_path.to_str().unwrap()).run() {
+    fn commit_with(&self, msg: &str, paths: Option<&[String]>, options: &CommitOptions) -> Result<(), RepoStoreError> {
+        let _lock = self.lock()?;
+        let trailers = self.trailers(paths, options, Operation::Commit)?;
+        match self.backend.commit(&self.repo_path, trailers.message(msg)?.as_str(), paths, options.author_identity(), options.committer_identity()) {
             Ok(_) => Ok(()),
             Err(e) => Err(RepoStoreError::Commit(Box::new(e))),
         }
     }
 
-    fn clean(&self) -> Result<(), RepoStoreError> {
-        todo!()
+    fn restore_documents(&self, paths: &[String]) -> Result<(), RepoStoreError> {
+        let _lock = self.lock()?;
+        let mut committed: Vec<&str> = Vec::new();
+        for path in paths {
+            let doc_path = self.doc_path(path)?;
+            let in_head = git("cat-file")
+                .add_option(args(&["-e", format!("HEAD:{}", path).as_str()]))
+                .current_dir(self.repo_dir())
+                .run()
+                .is_ok();
+            if in_head {
+                committed.push(path.as_str());
+            } else {
+                let cmd_rm = git("rm")
+                    .add_option(args(&["-q", "--cached", "--ignore-unmatch", "--", path.as_str()]));
+                if let Err(e) = cmd_rm.current_dir(self.repo_dir()).run() {
+                    return Err(RepoStoreError::Write(Box::new(e)));
+                }
+                match fs::remove_file(doc_path) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(RepoStoreError::Write(Box::new(e))),
+                    _ => {},
+                }
+            }
+        }
+        if committed.is_empty() {
+            return Ok(());
+        }
+        let cmd_checkout = self.fetching_git("checkout")?
+            .add_option(args(&["HEAD", "--"]))
+            .add_option(args(&committed));
+        match cmd_checkout.run() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(RepoStoreError::Write(Box::new(e))),
+        }
+    }
+
+    fn clean(&self, mode: CleanMode) -> Result<Vec<String>, RepoStoreError> {
+        let _lock = self.lock()?;
+        let mut discarded = self.dirty_paths()?;
+        let target = match mode {
+            CleanMode::WorkingTree => "HEAD",
+            CleanMode::Remote => {
+                discarded.append(&mut self.unpushed_paths()?);
+                "@{upstream}"
+            },
+        };
+        let cmd_reset = self.fetching_git("reset")?
+            .add_option(reset::hard())
+            .add_option(args(&[target]));
+        if let Err(e) = cmd_reset.run() {
+            return Err(RepoStoreError::Clean(Box::new(e)));
+        }
+        let cmd_clean = clean::clean()
+            .add_option(clean::force())
+            .add_option(clean::recurse_directories());
+        if let Err(e) = cmd_clean.current_dir(self.repo_dir()).run() {
+            return Err(RepoStoreError::Clean(Box::new(e)));
+        }
+        discarded.sort();
+        discarded.dedup();
+        Ok(discarded)
+    }
+
+    fn current_branch(&self) -> Result<String, RepoStoreError> {
+        let cmd = self.git("symbolic-ref")
+            .add_option(args(&["--short", "HEAD"]));
+        match cmd.run() {
+            Ok(o) => Ok(o.trim().to_string()),
+            Err(e) => Err(RepoStoreError::Branch(Box::new(e))),
+        }
+    }
+
+    fn branches(&self) -> Result<Vec<String>, RepoStoreError> {
+        let cmd = self.git("for-each-ref")
+            .add_option(args(&["--format=%(refname:short)", "refs/heads/"]));
+        match cmd.run() {
+            Ok(o) => {
+                let mut branches: Vec<String> = o.lines().filter(|b| !b.is_empty()).map(String::from).collect();
+                branches.sort();
+                Ok(branches)
+            },
+            Err(e) => Err(RepoStoreError::Branch(Box::new(e))),
+        }
+    }
+
+    fn create_branch(&self, name: &str, from: &Revision) -> Result<(), RepoStoreError> {
+        let _lock = self.lock()?;
+        let commit = self.resolve(from)?;
+        let cmd = git("branch")
+            .add_option(args(&["--no-track", "--", name, commit.as_str()]));
+        match cmd.current_dir(self.repo_dir()).run() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(RepoStoreError::Branch(Box::new(e))),
+        }
+    }
+
+    fn switch_branch(&self, name: &str) -> Result<(), RepoStoreError> {
+        let _lock = self.lock()?;
+        // a branch only known to the remote is created tracking it
+        let cmd = self.fetching_git("switch")?
+            .add_option(args(&[name]));
+        match cmd.run() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(RepoStoreError::Branch(Box::new(e))),
+        }
+    }
+
+    fn delete_branch(&self, name: &str) -> Result<(), RepoStoreError> {
+        let _lock = self.lock()?;
+        let cmd = git("branch")
+            .add_option(args(&["-D", "--", name]));
+        match cmd.current_dir(self.repo_dir()).run() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(RepoStoreError::Branch(Box::new(e))),
+        }
+    }
+
+    fn merge_branch(&self, name: &str) -> Result<(), RepoStoreError> {
+        let _lock = self.lock()?;
+        let cmd = self.fetching_git("merge")?
+            .add_option(args(&["--no-edit", name]));
+        let e = match cmd.run() {
+            Ok(_) => return Ok(()),
+            Err(e) => e,
+        };
+        if self.conflicted_paths()?.is_empty() {
+            return Err(RepoStoreError::Branch(Box::new(e)));
+        }
+        let resolved = self.resolve_conflicts(false).and_then(|conflicts| {
+            if !conflicts.is_empty() {
+                return Err(RepoStoreError::MergeConflict(conflicts));
+            }
+            let cmd_commit = commit::commit()
+                .add_option(commit::no_edit());
+            match cmd_commit.current_dir(self.repo_dir()).run() {
+                Ok(_) => Ok(()),
+                Err(e) => Err(RepoStoreError::Commit(Box::new(e))),
+            }
+        });
+        if resolved.is_err() {
+            self.abort_pull(false);
+        }
+        resolved
+    }
+
+    fn create_snapshot(&self, name: &str, msg: &str) -> Result<(), RepoStoreError> {
+        let _lock = self.lock()?;
+        let cmd = git("tag")
+            .add_option(args(&["--annotate", "--message", msg, name, "HEAD"]));
+        match cmd.current_dir(self.repo_dir()).run() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(RepoStoreError::Snapshot(Box::new(e))),
+        }
+    }
+
+    fn snapshots(&self) -> Result<Vec<Snapshot>, RepoStoreError> {
+        let cmd = self.git("for-each-ref")
+            .add_option(args(&[TAG_FORMAT, "refs/tags/"]));
+        match cmd.run() {
+            Ok(o) => Ok(parse_tags(&o)),
+            Err(e) => Err(RepoStoreError::Snapshot(Box::new(e))),
+        }
+    }
+
+    fn restore_snapshot(&self, name: &str, msg: &str) -> Result<(), RepoStoreError> {
+        let _lock = self.lock()?;
+        let Some(snapshot) = self.snapshots()?.into_iter().find(|s| s.name() == name) else {
+            return Err(RepoStoreError::Snapshot(format!("snapshot {} not found", name).into()));
+        };
+        self.clean(CleanMode::WorkingTree)?;
+        // the index and working tree take the snapshot tree, files added since included
+        let cmd = self.fetching_git("read-tree")?
+            .add_option(args(&["-u", "--reset", snapshot.commit()]));
+        if let Err(e) = cmd.run() {
+            return Err(RepoStoreError::Snapshot(Box::new(e)));
+        }
+        self.commit_with(msg, None, &CommitOptions::new().operation(Operation::Restore))
     }
-}*/
\ No newline at end of file
+
+    fn paths_at(&self, revision: &Revision) -> Result<Vec<String>, RepoStoreError> {
+        let commit = self.resolve(revision)?;
+        let cmd = self.git("ls-tree")
+            .add_option(args(&["-r", "-z", "--name-only", commit.as_str()]));
+        match cmd.run() {
+            Ok(o) => {
+                let mut paths: Vec<String> = o.split('\0').filter(|p| !p.is_empty()).map(String::from).collect();
+                paths.sort();
+                Ok(paths)
+            },
+            Err(e) => Err(RepoStoreError::History(Box::new(e))),
+        }
+    }
+
+    fn changed_paths(&self, from: Option<&Revision>, to: &Revision) -> Result<Vec<PathChange>, RepoStoreError> {
+        let Some(from) = from else {
+            return Ok(self.paths_at(to)?.into_iter().map(PathChange::Added).collect());
+        };
+        let (from, to) = (self.resolve(from)?, self.resolve(to)?);
+        let cmd = self.git("diff-tree")
+            .add_option(args(&["-r", "-z", "--name-status", "--no-renames", from.as_str(), to.as_str()]));
+        match cmd.run() {
+            Ok(o) => Ok(parse_name_status(&o)),
+            Err(e) => Err(RepoStoreError::History(Box::new(e))),
+        }
+    }
+
+    fn lock_store(&self) -> Result<StoreLock, RepoStoreError> {
+        Ok(StoreLock { _lock: Some(self.lock()?) })
+    }
+
+    fn head(&self) -> Result<Option<String>, RepoStoreError> {
+        if self.is_unborn() {
+            return Ok(None);
+        }
+        self.resolve(&Revision::rev("HEAD")).map(Some)
+    }
+}
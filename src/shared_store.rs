@@ -0,0 +1,156 @@
+use crate::history::Revision;
+use crate::json_document::Document;
+use crate::query::{QueryClause, QueryData};
+use crate::query_key::QCKey;
+use crate::repo_store::{RepoStore, RepoStoreError};
+use serde_json::{Map, Value};
+use std::sync::{Arc, Mutex, RwLock};
+
+struct Inner<S> {
+    store: S,
+    /// Held by the write in progress
+    writer: Mutex<()>,
+    /// Last commit of the active branch when the last write ended
+    head: RwLock<Option<String>>,
+}
+
+/// A handle sharing one store between threads, cloned cheaply, `Send` and `Sync` when the store is.
+/// Writes, commits and pulls go through [SharedStore::write] one at a time, while any number of threads
+/// read concurrently from [views](StoreView) of the last commit, never from the working tree a write may be changing.
+/// Documents written but not committed are not seen by readers until committed.
+pub struct SharedStore<S: RepoStore<Map<String, Value>>> {
+    inner: Arc<Inner<S>>,
+}
+
+impl<S: RepoStore<Map<String, Value>>> Clone for SharedStore<S> {
+    fn clone(&self) -> Self {
+        Self { inner: self.inner.clone() }
+    }
+}
+
+impl<S: RepoStore<Map<String, Value>>> SharedStore<S> {
+    /// Initializes `store` and shares it
+    pub fn open(store: S) -> Result<Self, RepoStoreError> {
+        store.initialize()?;
+        let head = store.head()?;
+        Ok(Self {
+            inner: Arc::new(Inner {
+                store,
+                writer: Mutex::new(()),
+                head: RwLock::new(head),
+            }),
+        })
+    }
+
+    /// Id of the commit read by new views, `None` before the first commit
+    pub fn head(&self) -> Option<String> {
+        self.inner.head.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// View of the documents at the last commit, unaffected by the writes made after
+    pub fn view(&self) -> StoreView<S> {
+        StoreView {
+            inner: self.inner.clone(),
+            commit: self.head(),
+        }
+    }
+
+    /// Loads the document as of the last commit
+    pub fn document(&self, path: &str) -> Result<Document<Map<String, Value>>, RepoStoreError> {
+        self.view().document(path)
+    }
+
+    /// Lists the documents of `dir` as of the last commit, see [StoreView::documents]
+    pub fn documents(&self, dir: &str) -> Result<Vec<String>, RepoStoreError> {
+        self.view().documents(dir)
+    }
+
+    /// Queries the documents of `dir` as of the last commit, see [StoreView::find]
+    pub fn find<K: QCKey>(&self, dir: &str, clause: &QueryClause<K>) -> Result<Vec<String>, RepoStoreError> {
+        self.view().find(dir, clause)
+    }
+
+    /// Runs `f` with exclusive access to the store once the write in progress ends,
+    /// then moves the handle to the last commit, whether `f` succeeded or not
+    pub fn write<R>(&self, f: impl FnOnce(&S) -> Result<R, RepoStoreError>) -> Result<R, RepoStoreError> {
+        let _writer = self.inner.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let result = f(&self.inner.store);
+        let head = self.inner.store.head();
+        match (result, head) {
+            (result, Ok(head)) => {
+                *self.inner.head.write().unwrap_or_else(|e| e.into_inner()) = head;
+                result
+            },
+            (Err(e), Err(_)) => Err(e),
+            (Ok(_), Err(e)) => Err(e),
+        }
+    }
+
+    /// Commits all the changes, see [RepoStore::commit]
+    pub fn commit(&self, msg: &str) -> Result<(), RepoStoreError> {
+        self.write(|store| store.commit(msg))
+    }
+
+    pub fn pull(&self, rebase: bool) -> Result<(), RepoStoreError> {
+        self.write(|store| store.pull(rebase))
+    }
+
+    pub fn push(&self) -> Result<(), RepoStoreError> {
+        self.write(|store| store.push())
+    }
+
+    /// Moves the handle to the last commit, to see the commits made to the repository outside of it
+    pub fn refresh(&self) -> Result<(), RepoStoreError> {
+        self.write(|_| Ok(()))
+    }
+}
+
+/// The documents of a [SharedStore] at one commit, read without waiting for writes nor seeing them
+pub struct StoreView<S: RepoStore<Map<String, Value>>> {
+    inner: Arc<Inner<S>>,
+    commit: Option<String>,
+}
+
+impl<S: RepoStore<Map<String, Value>>> StoreView<S> {
+    /// Id of the commit read, `None` for a store without commits
+    pub fn commit(&self) -> Option<&str> {
+        self.commit.as_deref()
+    }
+
+    pub fn document(&self, path: &str) -> Result<Document<Map<String, Value>>, RepoStoreError> {
+        match &self.commit {
+            Some(commit) => self.inner.store.document_at(path, &Revision::rev(commit)),
+            None => Err(RepoStoreError::Read(format!("{} not found", path).into())),
+        }
+    }
+
+    /// Lists the names of the files directly in `dir`, sorted and skipping hidden ones as [RepoStore::documents] does
+    pub fn documents(&self, dir: &str) -> Result<Vec<String>, RepoStoreError> {
+        let Some(commit) = &self.commit else {
+            return Ok(Vec::new());
+        };
+        let dir = dir.trim_matches('/');
+        let prefix = if dir.is_empty() { String::new() } else { format!("{}/", dir) };
+        Ok(self.inner.store.paths_at(&Revision::rev(commit))?
+            .iter()
+            .filter_map(|p| p.strip_prefix(prefix.as_str()))
+            .filter(|name| !name.contains('/') && !name.starts_with('.'))
+            .map(String::from)
+            .collect())
+    }
+
+    /// Evaluates `clause` against every JSON document of `dir`, returning the names of the matching ones.
+    /// Documents missing a key referenced by the clause do not match.
+    pub fn find<K: QCKey>(&self, dir: &str, clause: &QueryClause<K>) -> Result<Vec<String>, RepoStoreError> {
+        let dir = dir.trim_matches('/');
+        let mut found = Vec::new();
+        for name in self.documents(dir)?.into_iter().filter(|n| n.ends_with(".json")) {
+            let path = if dir.is_empty() { name.clone() } else { format!("{}/{}", dir, name) };
+            let value = Value::Object(self.document(path.as_str())?.content().clone());
+            if let Ok(true) = clause.eval(&QueryData::load::<String>(&value)) {
+                found.push(name);
+            }
+        }
+        Ok(found)
+    }
+}
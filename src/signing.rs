@@ -0,0 +1,119 @@
+/// Format of the signatures made by [CommitSigning]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigningFormat {
+    OpenPgp,
+    Ssh,
+}
+
+/// Signs the commits of a store, with `gpg` or `ssh-keygen` as git does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitSigning {
+    format: SigningFormat,
+    key: String,
+}
+
+impl CommitSigning {
+    /// Signs with the OpenPGP key `key_id` of the gpg keyring
+    pub fn gpg(key_id: &str) -> Self {
+        Self {
+            format: SigningFormat::OpenPgp,
+            key: String::from(key_id),
+        }
+    }
+
+    /// Signs with the SSH private key file at `key_path`, or the public key of an agent identity
+    pub fn ssh(key_path: &str) -> Self {
+        Self {
+            format: SigningFormat::Ssh,
+            key: String::from(key_path),
+        }
+    }
+
+    pub fn format(&self) -> SigningFormat {
+        self.format
+    }
+
+    pub fn key(&self) -> &str {
+        self.key.as_str()
+    }
+
+    /// git configuration signing every commit
+    pub(crate) fn config_entries(&self) -> Vec<(&'static str, String)> {
+        let format = match self.format {
+            SigningFormat::OpenPgp => "openpgp",
+            SigningFormat::Ssh => "ssh",
+        };
+        vec![
+            ("gpg.format", String::from(format)),
+            ("user.signingKey", self.key.clone()),
+            ("commit.gpgSign", String::from("true")),
+        ]
+    }
+}
+
+/// Validity of a commit signature, as reported by `git log --format=%G?`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureStatus {
+    /// Valid signature by a trusted key
+    Good,
+    /// Valid signature by a key of unknown or missing trust, such as an SSH key missing from the allowed signers
+    Untrusted,
+    Bad,
+    /// Valid signature made with an expired key, or which expired
+    Expired,
+    /// Valid signature made with a revoked key
+    Revoked,
+    /// Signature that could not be checked, the key being unknown
+    Unverifiable,
+}
+
+/// The signature of a commit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitSignature {
+    status: SignatureStatus,
+    signer: String,
+    key: String,
+}
+
+impl CommitSignature {
+    pub fn new(status: SignatureStatus, signer: &str, key: &str) -> Self {
+        Self {
+            status,
+            signer: String::from(signer),
+            key: String::from(key),
+        }
+    }
+
+    pub fn status(&self) -> SignatureStatus {
+        self.status
+    }
+
+    /// Whether the signature is valid and made by a trusted key
+    pub fn is_trusted(&self) -> bool {
+        self.status == SignatureStatus::Good
+    }
+
+    /// Identity of the signer, empty when the signature could not be checked
+    pub fn signer(&self) -> &str {
+        self.signer.as_str()
+    }
+
+    /// Fingerprint or id of the signing key
+    pub fn key(&self) -> &str {
+        self.key.as_str()
+    }
+
+    /// Parses the `%G?`, `%GS` and `%GK` placeholders of `git log`, `None` for an unsigned commit
+    pub(crate) fn parse(status: &str, signer: &str, key: &str) -> Option<Self> {
+        let status = match status {
+            "G" => SignatureStatus::Good,
+            "U" => SignatureStatus::Untrusted,
+            "B" => SignatureStatus::Bad,
+            "X" | "Y" => SignatureStatus::Expired,
+            "R" => SignatureStatus::Revoked,
+            "E" => SignatureStatus::Unverifiable,
+            _ => return None,
+        };
+        Some(Self::new(status, signer, key))
+    }
+}
@@ -0,0 +1,151 @@
+use crate::audit::{self, CommitOptions};
+use crate::history::Revision;
+use crate::json_document::Document;
+use crate::repo_store::{RepoStore, RepoStoreError};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+enum Operation {
+    Put(Map<String, Value>),
+    Remove,
+}
+
+/// A document as it was before the transaction was applied
+enum Previous {
+    Absent,
+    /// Revision and content of the document, uncommitted edits included
+    Document(String, Map<String, Value>),
+    /// A file that does not load as a document, only restored to its committed state
+    Unreadable,
+}
+
+/// A set of document writes applied and committed together as a single commit.
+/// Writes are buffered until [Transaction::commit], which holds the [store lock](RepoStore::lock_store) while it applies
+/// and commits them; if applying or committing them fails, every touched document is restored to its state
+/// before the transaction, uncommitted edits included.
+pub struct Transaction<'a, S: RepoStore<Map<String, Value>>> {
+    store: &'a S,
+    operations: BTreeMap<String, Operation>,
+}
+
+impl<'a, S: RepoStore<Map<String, Value>>> Transaction<'a, S> {
+    pub fn new(store: &'a S) -> Self {
+        Self {
+            store,
+            operations: BTreeMap::new(),
+        }
+    }
+
+    /// Writes `document` at `path`, replacing any previous write to it in this transaction
+    pub fn put(&mut self, path: &str, document: Document<Map<String, Value>>) {
+        self.operations.insert(String::from(path), Operation::Put(document.content().clone()));
+    }
+
+    /// Removes the document at `path`
+    pub fn remove(&mut self, path: &str) {
+        self.operations.insert(String::from(path), Operation::Remove);
+    }
+
+    /// Reads the document at `path` as seen by this transaction, including its pending writes
+    pub fn get(&self, path: &str) -> Result<Document<Map<String, Value>>, RepoStoreError> {
+        match self.operations.get(path) {
+            Some(Operation::Put(content)) => Ok(Document::new(content.clone())),
+            Some(Operation::Remove) => Err(RepoStoreError::Read(format!("{} removed in transaction", path).into())),
+            None => self.store.document(path),
+        }
+    }
+
+    /// Paths of the documents written or removed by this transaction
+    pub fn paths(&self) -> Vec<String> {
+        self.operations.keys().cloned().collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Applies all the writes and commits them with `msg`. Committing an empty transaction,
+    /// or one whose writes leave the committed documents as they are, does nothing.
+    pub fn commit(self, msg: &str) -> Result<(), RepoStoreError> {
+        self.commit_with(msg, &CommitOptions::new())
+    }
+
+    /// Applies all the writes and commits them with `msg`, recording `options` in the commit trailers
+    /// as a [transaction](audit::Operation::Transaction) unless they name another operation
+    pub fn commit_with(self, msg: &str, options: &CommitOptions) -> Result<(), RepoStoreError> {
+        if self.operations.is_empty() {
+            return Ok(());
+        }
+        let paths = self.paths();
+        let options = options.clone().default_operation(audit::Operation::Transaction);
+        let _lock = self.store.lock_store()?;
+        let previous = self.previous();
+        match self.apply().and_then(|_| self.commit_applied(msg, &paths, &options)) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                self.restore(&paths, &previous);
+                Err(e)
+            },
+        }
+    }
+
+    /// Discards all the pending writes
+    pub fn rollback(self) {}
+
+    /// The documents touched by the transaction as they are before it is applied
+    fn previous(&self) -> BTreeMap<String, Previous> {
+        self.operations.keys()
+            .map(|path| {
+                let previous = match self.store.revision(path) {
+                    Ok(None) => Previous::Absent,
+                    Ok(Some(revision)) => match self.store.document(path) {
+                        Ok(doc) => Previous::Document(revision, doc.content().clone()),
+                        Err(_) => Previous::Unreadable,
+                    },
+                    Err(_) => Previous::Unreadable,
+                };
+                (path.clone(), previous)
+            })
+            .collect()
+    }
+
+    /// Restores the committed documents, then writes back the uncommitted ones
+    fn restore(&self, paths: &[String], previous: &BTreeMap<String, Previous>) {
+        let _ = self.store.restore_documents(paths);
+        for (path, previous) in previous {
+            let current = self.store.revision(path).ok().flatten();
+            let _ = match previous {
+                Previous::Absent if current.is_some() => self.store.remove_document(path),
+                Previous::Document(revision, content) if current.as_ref() != Some(revision) => {
+                    self.store.write_document(path, &mut Document::new(content.clone()))
+                },
+                _ => Ok(()),
+            };
+        }
+    }
+
+    /// Commits the applied writes, unless none of the documents differs from its committed version
+    fn commit_applied(&self, msg: &str, paths: &[String], options: &CommitOptions) -> Result<(), RepoStoreError> {
+        let head = self.store.head()?;
+        for path in paths {
+            let committed = match &head {
+                Some(commit) => self.store.revision_at(path, &Revision::rev(commit))?,
+                None => None,
+            };
+            if self.store.revision(path)? != committed {
+                return self.store.commit_with(msg, Some(paths), options);
+            }
+        }
+        Ok(())
+    }
+
+    fn apply(&self) -> Result<(), RepoStoreError> {
+        for (path, operation) in &self.operations {
+            match operation {
+                Operation::Put(content) => self.store.write_document(path, &mut Document::new(content.clone()))?,
+                Operation::Remove => self.store.remove_document(path)?,
+            }
+        }
+        Ok(())
+    }
+}
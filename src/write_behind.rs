@@ -0,0 +1,218 @@
+use crate::audit::{CommitOptions, Operation};
+use crate::detached::Detached;
+use crate::json_document::Document;
+use crate::repo_store::{RepoStore, RepoStoreError};
+use crate::transaction::Transaction;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// When a [WriteBehindStore] commits its buffered writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommitPolicy {
+    /// Commits after every write
+    EachWrite,
+    /// Commits once the given number of writes are buffered, edits of the same document counting each
+    Writes(usize),
+    /// Commits the writes at most the given time after the first one was buffered, from a background thread
+    Interval(Duration),
+    /// Commits only on [WriteBehindStore::flush]
+    Manual,
+}
+
+#[derive(Default)]
+struct Buffer {
+    /// Last content written to each document, `None` once removed
+    documents: BTreeMap<String, Option<Map<String, Value>>>,
+    writes: usize,
+    since: Option<Instant>,
+    /// Error of the last flush of the background thread, until reported
+    failed: Option<Detached>,
+}
+
+struct Shared<S> {
+    store: S,
+    /// Message and options of the commits
+    commit: Mutex<(String, CommitOptions)>,
+    buffer: Mutex<Buffer>,
+}
+
+impl<S: RepoStore<Map<String, Value>>> Shared<S> {
+    fn buffer(&self) -> MutexGuard<'_, Buffer> {
+        self.buffer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn commit(&self) -> MutexGuard<'_, (String, CommitOptions)> {
+        self.commit.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Commits the buffered writes as one commit, keeping them buffered when it fails.
+    /// Writes leaving a document as stored are dropped, so edits undone before the flush make no commit.
+    fn flush(&self, buffer: &mut Buffer) -> Result<(), RepoStoreError> {
+        let mut transaction = Transaction::new(&self.store);
+        for (path, content) in &buffer.documents {
+            match content {
+                Some(content) if self.store.document(path).is_ok_and(|d| d.content() == content) => {},
+                Some(content) => transaction.put(path, Document::new(content.clone())),
+                None if self.store.revision(path)?.is_none() => {},
+                None => transaction.remove(path),
+            }
+        }
+        let (message, options) = self.commit().clone();
+        transaction.commit_with(&message, &options)?;
+        *buffer = Buffer::default();
+        Ok(())
+    }
+}
+
+/// Buffers document writes in memory and commits them to the wrapped store according to a [CommitPolicy],
+/// successive edits of a document being coalesced into a single change.
+/// Each flush makes one commit recorded as a [flush](Operation::Flush), and the writes left are flushed when the store is dropped.
+/// Reads through [WriteBehindStore::document] see the buffered writes, unlike reads from the wrapped store.
+/// When a flush of the background thread fails, the writes stay buffered and the next write returns the error,
+/// the errors it wraps reduced to their messages.
+pub struct WriteBehindStore<S: RepoStore<Map<String, Value>> + Send + Sync + 'static> {
+    shared: Arc<Shared<S>>,
+    policy: CommitPolicy,
+    /// Background thread of the interval policy, started by the first write
+    flusher: OnceLock<(Sender<()>, JoinHandle<()>)>,
+}
+
+impl<S: RepoStore<Map<String, Value>> + Send + Sync + 'static> WriteBehindStore<S> {
+    pub fn new(store: S, policy: CommitPolicy) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                store,
+                commit: Mutex::new((String::from("Write-behind flush"), CommitOptions::new().operation(Operation::Flush))),
+                buffer: Mutex::new(Buffer::default()),
+            }),
+            policy,
+            flusher: OnceLock::new(),
+        }
+    }
+
+    /// Sets the message of the commits, `Write-behind flush` by default
+    pub fn message(self, msg: &str) -> Self {
+        self.shared.commit().0 = String::from(msg);
+        self
+    }
+
+    /// Sets the options the commits are made with
+    pub fn options(self, options: CommitOptions) -> Self {
+        self.shared.commit().1 = options.default_operation(Operation::Flush);
+        self
+    }
+
+    /// Flushes the writes due every time they may have come due, until stopped
+    fn start_flusher(shared: Arc<Shared<S>>, interval: Duration) -> (Sender<()>, JoinHandle<()>) {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let handle = thread::spawn(move || loop {
+            let wait = shared.buffer().since.map_or(interval, |since| interval.saturating_sub(since.elapsed()));
+            match stopped.recv_timeout(wait) {
+                Err(RecvTimeoutError::Timeout) => {
+                    let mut buffer = shared.buffer();
+                    if buffer.since.is_some_and(|since| since.elapsed() >= interval) {
+                        // a failed flush is retried after another interval, its error reported by the next call
+                        if let Err(e) = shared.flush(&mut buffer) {
+                            buffer.since = Some(Instant::now());
+                            buffer.failed = Some(Detached::detach(e));
+                        }
+                    }
+                },
+                _ => break,
+            }
+        });
+        (stop, handle)
+    }
+
+    /// The wrapped store
+    pub fn store(&self) -> &S {
+        &self.shared.store
+    }
+
+    pub fn policy(&self) -> CommitPolicy {
+        self.policy
+    }
+
+    /// Loads the document, as last written to the buffer or else from the wrapped store
+    pub fn document(&self, path: &str) -> Result<Document<Map<String, Value>>, RepoStoreError> {
+        match self.shared.buffer().documents.get(path) {
+            Some(Some(content)) => Ok(Document::new(content.clone())),
+            Some(None) => Err(RepoStoreError::Read(format!("{} removed", path).into())),
+            None => self.shared.store.document(path),
+        }
+    }
+
+    /// Buffers the write of `document`, committing the buffer when the policy says so.
+    /// Fails without buffering it with the error of a background flush not reported yet.
+    pub fn write_document(&self, path: &str, document: &Document<Map<String, Value>>) -> Result<(), RepoStoreError> {
+        self.buffer_write(path, Some(document.content().clone()))
+    }
+
+    /// Buffers the removal of the document, committing the buffer when the policy says so
+    pub fn remove_document(&self, path: &str) -> Result<(), RepoStoreError> {
+        self.buffer_write(path, None)
+    }
+
+    fn buffer_write(&self, path: &str, content: Option<Map<String, Value>>) -> Result<(), RepoStoreError> {
+        if let CommitPolicy::Interval(interval) = self.policy {
+            self.flusher.get_or_init(|| Self::start_flusher(self.shared.clone(), interval));
+        }
+        let mut buffer = self.shared.buffer();
+        if let Some(e) = buffer.failed.take() {
+            return Err(e.attach());
+        }
+        buffer.documents.insert(String::from(path), content);
+        buffer.writes += 1;
+        buffer.since.get_or_insert_with(Instant::now);
+        let due = match self.policy {
+            CommitPolicy::EachWrite => true,
+            CommitPolicy::Writes(writes) => buffer.writes >= writes,
+            CommitPolicy::Interval(_) | CommitPolicy::Manual => false,
+        };
+        if due {
+            self.shared.flush(&mut buffer)?;
+        }
+        Ok(())
+    }
+
+    /// Paths of the documents with buffered writes, sorted
+    pub fn pending(&self) -> Vec<String> {
+        self.shared.buffer().documents.keys().cloned().collect()
+    }
+
+    /// Commits the buffered writes as one commit, doing nothing when there are none.
+    /// The writes stay buffered when the commit fails, as they do when a background flush fails.
+    pub fn flush(&self) -> Result<(), RepoStoreError> {
+        let mut buffer = self.shared.buffer();
+        // a background flush failure is superseded by this attempt
+        buffer.failed = None;
+        if buffer.documents.is_empty() {
+            return Ok(());
+        }
+        self.shared.flush(&mut buffer)
+    }
+
+    /// Flushes the buffered writes and releases the store, reporting the error dropping would hide
+    pub fn close(mut self) -> Result<(), RepoStoreError> {
+        self.stop_flusher();
+        self.flush()
+    }
+
+    fn stop_flusher(&mut self) {
+        if let Some((stop, handle)) = self.flusher.take() {
+            drop(stop);
+            let _ = handle.join();
+        }
+    }
+}
+
+impl<S: RepoStore<Map<String, Value>> + Send + Sync + 'static> Drop for WriteBehindStore<S> {
+    fn drop(&mut self) {
+        self.stop_flusher();
+        let _ = self.flush();
+    }
+}
@@ -0,0 +1,178 @@
+#![cfg(feature = "async")]
+
+mod common;
+
+use common::{bare_repo, doc, git, git_store, TestDir};
+use gitobi::async_store::{AsyncGitStore, BlockingPoolStore};
+use gitobi::history::Revision;
+use gitobi::json_document::{map_from_str, map_into_string, Document};
+use gitobi::repo_store::{LockPolicy, RepoStore, RepoStoreError};
+use serde_json::json;
+use std::future;
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn blocking_pool_store_operations() {
+    let dir = TestDir::new("async-store");
+    let url = bare_repo(&dir, &[("users/john.json", r#"{"name": "John"}"#)]);
+    let store = BlockingPoolStore::new(git_store(&dir, "cache", &url));
+    store.initialize().await.unwrap();
+
+    let john = store.document("users/john.json").await.unwrap();
+    let written = store.write_document("users/john.json", doc(json!({"name": "John", "age": 43}))).await.unwrap();
+    assert!(written.revision().is_some());
+    store.commit("john 43").await.unwrap();
+    let (jane, mark) = tokio::join!(
+        store.write_document("users/jane.json", doc(json!({"name": "Jane"}))),
+        store.write_document("users/mark.json", doc(json!({"name": "Mark"}))),
+    );
+    jane.unwrap();
+    mark.unwrap();
+    store.commit_documents("add jane and mark", &[String::from("users/jane.json"), String::from("users/mark.json")]).await.unwrap();
+    store.push().await.unwrap();
+
+    assert_eq!(store.documents("users").await.unwrap(), ["jane.json", "john.json", "mark.json"]);
+    assert_eq!(store.revisions("users/john.json").await.unwrap().len(), 2);
+    assert_eq!(store.document_at("users/john.json", &Revision::rev("HEAD~1")).await.unwrap().content().get("age").unwrap(), 43);
+    assert_eq!(git(&dir.join("origin.git"), &["log", "-1", "--format=%s"]), "add jane and mark\n");
+
+    // errors keep their kind and message
+    let conflict = store.write_document_if("users/john.json", doc(json!({"name": "Johnny"})), john.revision()).await;
+    assert!(matches!(conflict, Err(RepoStoreError::RevisionConflict(p)) if p == "users/john.json"));
+    let missing = store.document("users/nobody.json").await;
+    assert!(matches!(&missing, Err(RepoStoreError::Read(e)) if !e.to_string().is_empty()));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dropped_calls_complete() {
+    let dir = TestDir::new("async-store-cancel");
+    let url = bare_repo(&dir, &[]);
+    let store = BlockingPoolStore::new(git_store(&dir, "cache", &url));
+    store.initialize().await.unwrap();
+    store.write_document("a.json", doc(json!({"v": 1}))).await.unwrap();
+
+    // the commit starts then its future is dropped
+    let mut commit = Box::pin(store.commit("add a"));
+    tokio::select! {
+        biased;
+        _ = &mut commit => panic!("commit completed on first poll"),
+        _ = future::ready(()) => {},
+    }
+    drop(commit);
+    let mut commits = 0;
+    for _ in 0..500 {
+        commits = store.commits(None, &Revision::rev("HEAD")).await.unwrap().len();
+        if commits == 2 {
+            break;
+        }
+        tokio::task::yield_now().await;
+    }
+    assert_eq!(commits, 2);
+    assert!(git(store.store().repo_path(), &["status", "--porcelain"]).is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn async_git_store_operations() {
+    let dir = TestDir::new("async-git-store");
+    let url = bare_repo(&dir, &[("users/john.json", r#"{"name": "John"}"#)]);
+    let store = AsyncGitStore::new(git_store(&dir, "cache", &url));
+    store.initialize().await.unwrap();
+
+    let john = store.document("users/john.json").await.unwrap();
+    assert_eq!(john.revision(), store.store().revision("users/john.json").unwrap().as_deref());
+    let written = store.write_document("users/john.json", doc(json!({"name": "John", "age": 43}))).await.unwrap();
+    assert_eq!(written.revision(), store.store().revision("users/john.json").unwrap().as_deref());
+    store.commit("john 43").await.unwrap();
+    let (jane, mark) = tokio::join!(
+        store.write_document("users/jane.json", doc(json!({"name": "Jane"}))),
+        store.write_document("users/mark.json", doc(json!({"name": "Mark"}))),
+    );
+    jane.unwrap();
+    mark.unwrap();
+    store.commit_documents("add jane and mark", &[String::from("users/jane.json"), String::from("users/mark.json")]).await.unwrap();
+    store.push().await.unwrap();
+
+    assert_eq!(store.documents("users").await.unwrap(), ["jane.json", "john.json", "mark.json"]);
+    assert_eq!(store.revisions("users/john.json").await.unwrap().len(), 2);
+    assert_eq!(store.commits(None, &Revision::rev("HEAD")).await.unwrap().len(), 3);
+    assert_eq!(store.head().await.unwrap(), store.store().head().unwrap());
+    let previous = store.document_at("users/john.json", &Revision::rev("HEAD~1")).await.unwrap();
+    assert_eq!(previous.content().get("age").unwrap(), 43);
+    assert_eq!(previous.revision(), store.revision_at("users/john.json", &Revision::rev("HEAD~1")).await.unwrap().as_deref());
+    assert_eq!(store.revision_at("users/jane.json", &Revision::rev("HEAD~1")).await.unwrap(), None);
+    assert_eq!(git(&dir.join("origin.git"), &["log", "-1", "--format=%s"]), "add jane and mark\n");
+
+    // errors keep their kind and message
+    let conflict = store.write_document_if("users/john.json", doc(json!({"name": "Johnny"})), john.revision()).await;
+    assert!(matches!(conflict, Err(RepoStoreError::RevisionConflict(p)) if p == "users/john.json"));
+    let missing = store.document("users/nobody.json").await;
+    assert!(matches!(&missing, Err(RepoStoreError::Read(e)) if !e.to_string().is_empty()));
+    assert!(matches!(store.document("../outside.json").await, Err(RepoStoreError::InvalidPath(_))));
+
+    store.remove_document_if("users/mark.json", store.revision("users/mark.json").await.unwrap().unwrap().as_str()).await.unwrap();
+    assert_eq!(store.revision("users/mark.json").await.unwrap(), None);
+    store.create_dir("groups").await.unwrap();
+    assert!(store.documents("groups").await.unwrap().is_empty());
+    store.remove_dir("groups").await.unwrap();
+    assert!(!store.store().repo_path().join("groups").exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dropped_async_writes_complete() {
+    let dir = TestDir::new("async-git-store-cancel");
+    let url = bare_repo(&dir, &[]);
+    let store = AsyncGitStore::new(git_store(&dir, "cache", &url));
+    store.initialize().await.unwrap();
+
+    // the write starts then its future is dropped
+    let mut write = Box::pin(store.write_document("a.json", doc(json!({"v": 1}))));
+    tokio::select! {
+        biased;
+        _ = &mut write => panic!("write completed on first poll"),
+        _ = future::ready(()) => {},
+    }
+    drop(write);
+    let mut revision = None;
+    for _ in 0..500 {
+        revision = store.revision("a.json").await.unwrap();
+        if revision.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(revision.is_some());
+    assert_eq!(store.document("a.json").await.unwrap().content().get("v").unwrap(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn async_writes_wait_for_lock() {
+    let dir = TestDir::new("async-git-store-lock");
+    let url = bare_repo(&dir, &[]);
+    let store = AsyncGitStore::new(git_store(&dir, "cache", &url).lock_policy(LockPolicy::new(Duration::from_millis(100), Duration::from_millis(10))));
+    store.initialize().await.unwrap();
+
+    // the lock taken by a blocking call of the same store
+    let lock = store.store().lock_store().unwrap();
+    let locked = store.write_document("a.json", doc(json!({"v": 1}))).await;
+    assert!(matches!(locked, Err(RepoStoreError::Locked(_))));
+    drop(lock);
+    store.write_document("a.json", doc(json!({"v": 1}))).await.unwrap();
+    // concurrent writes wait for the lock each other holds
+    let (first, second) = tokio::join!(
+        store.write_document("b.json", doc(json!({"v": 1}))),
+        store.write_document("c.json", doc(json!({"v": 1}))),
+    );
+    first.unwrap();
+    second.unwrap();
+}
+
+#[tokio::test]
+async fn async_document_io() {
+    let mut document = Document::load_async(&mut r#"{"name": "John", "age": 43}"#.as_bytes(), map_from_str).await.unwrap();
+    assert_eq!(document.content().get("age").unwrap(), 43);
+    let mut written = Vec::new();
+    document.write_async(&mut written, map_into_string).await.unwrap();
+    let reloaded = Document::load_async(&mut written.as_slice(), map_from_str).await.unwrap();
+    assert_eq!(reloaded.content(), document.content());
+    assert!(Document::load_async(&mut "{".as_bytes(), map_from_str).await.is_err());
+}
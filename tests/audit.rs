@@ -0,0 +1,126 @@
+mod common;
+
+use common::{bare_repo, doc, git_store, TestDir};
+use gitobi::audit::{audit_log, CommitOptions, CommitTrailers, DocumentKey, Operation};
+use gitobi::bare_store::BareGitStore;
+use gitobi::history::Revision;
+use gitobi::json_document::Document;
+use gitobi::memory_store::InMemoryStore;
+use gitobi::repo_store::{GitCommit, RepoStore, RepoStoreError};
+use gitobi::transaction::Transaction;
+use serde_json::{json, Map, Value};
+
+/// Records a transaction, a plain commit and a snapshot restore, checking the trailers read back
+fn audited_operations<S: RepoStore<Map<String, Value>>>(store: &S) {
+    store.create_snapshot("start", "before the changes").unwrap();
+    let mut transaction = Transaction::new(store);
+    transaction.put("users/john.json", doc(json!({"name": "John", "age": 44, "tags": {"role": "admin"}})));
+    transaction.put("users/jane.json", doc(json!({"name": "Jane"})));
+    transaction.commit_with("john 44, add jane", &CommitOptions::new().actor("alice").request_id("req-1")).unwrap();
+    store.remove_document("users/jane.json").unwrap();
+    store.write_document("notes.txt", &mut Document::new(Map::new())).unwrap();
+    store.commit("remove jane").unwrap();
+    store.restore_snapshot("start", "back to start").unwrap();
+
+    let records = audit_log(store, Some(&Revision::rev("start")), &Revision::rev("HEAD")).unwrap();
+    assert_eq!(records.len(), 3);
+    let transaction = records[0].trailers();
+    assert_eq!(records[0].commit().message(), "john 44, add jane");
+    assert_eq!(transaction.operation(), &Operation::Transaction);
+    assert_eq!((transaction.actor(), transaction.request_id()), (Some("alice"), Some("req-1")));
+    assert_eq!(transaction.documents(), ["users/jane.json", "users/john.json"]);
+    assert_eq!(transaction.keys(), [
+        DocumentKey::new("users/jane.json", "name"),
+        DocumentKey::new("users/john.json", "age"),
+        DocumentKey::new("users/john.json", "tags.role"),
+    ]);
+
+    let commit = records[1].trailers();
+    assert_eq!(records[1].commit().message(), "remove jane");
+    assert_eq!(commit.operation(), &Operation::Commit);
+    assert_eq!(commit.actor(), None);
+    assert_eq!(commit.documents(), ["notes.txt", "users/jane.json"]);
+    assert_eq!(commit.keys(), [DocumentKey::new("users/jane.json", "name")]);
+
+    let restore = records[2].trailers();
+    assert_eq!(restore.operation(), &Operation::Restore);
+    assert_eq!(restore.documents(), ["notes.txt", "users/john.json"]);
+    assert_eq!(restore.keys().len(), 2);
+}
+
+#[test]
+fn git_store_trailers() {
+    let dir = TestDir::new("audit-git-store");
+    let url = bare_repo(&dir, &[("users/john.json", r#"{"name": "John", "age": 43}"#)]);
+    let store = git_store(&dir, "cache", &url);
+    store.initialize().unwrap();
+    audited_operations(&store);
+    // the seed commit was made outside of the store
+    let commits = store.commits(None, &Revision::rev("HEAD")).unwrap();
+    assert_eq!(commits.len(), 4);
+    assert!(commits[0].trailers().is_none());
+}
+
+#[test]
+fn memory_and_bare_store_trailers() {
+    let memory = InMemoryStore::new("store", GitCommit::default());
+    memory.initialize().unwrap();
+    memory.write_document("users/john.json", &mut doc(json!({"name": "John", "age": 43}))).unwrap();
+    memory.commit("add john").unwrap();
+    audited_operations(&memory);
+
+    let dir = TestDir::new("audit-bare-store");
+    bare_repo(&dir, &[("users/john.json", r#"{"name": "John", "age": 43}"#)]);
+    let bare = BareGitStore::new(dir.join("origin.git").to_str().unwrap(), "main", GitCommit::new("server", "server@example.com"));
+    bare.initialize().unwrap();
+    audited_operations(&bare);
+
+    bare.write_document("users/mark.json", &mut doc(json!({"name": "Mark"}))).unwrap();
+    bare.commit_with("import mark", None, &CommitOptions::new().operation(Operation::Other(String::from("import")))).unwrap();
+    let revision = &bare.revisions("users/mark.json").unwrap()[0];
+    assert_eq!(revision.message(), "import mark");
+    assert_eq!(revision.trailers().unwrap().operation().as_str(), "import");
+}
+
+#[test]
+fn parse_trailers() {
+    let (message, trailers) = CommitTrailers::parse("fix limits\n\nOperation: commit\nRequest-Id: 7\nDocument: a.json\nKey: a.json:x.y:z\n");
+    let trailers = trailers.unwrap();
+    assert_eq!(message, "fix limits");
+    assert_eq!(trailers.request_id(), Some("7"));
+    assert_eq!(trailers.keys(), [DocumentKey::new("a.json", "x.y:z")]);
+    let (_, trailers) = CommitTrailers::parse("import\n\nOperation: commit\nDocument: 2024:01/a.json\nDocument: 2024:01/a.json:b.json\nKey: 2024:01/a.json:b.json:c\nKey: 2024:01/a.json:url:x");
+    assert_eq!(trailers.unwrap().keys(), [DocumentKey::new("2024:01/a.json:b.json", "c"), DocumentKey::new("2024:01/a.json", "url:x")]);
+    // keys of documents not listed are not written by the stores
+    assert_eq!(CommitTrailers::parse("x\n\nOperation: commit\nKey: a.json:x").1, None);
+    // other trailers are left to the message
+    let foreign = "fix limits\n\nOperation: commit\nSigned-off-by: Jane <jane@example.com>";
+    assert_eq!(CommitTrailers::parse(foreign), (foreign, None));
+    assert_eq!(CommitTrailers::parse("raise limits\n\nticket 42"), ("raise limits\n\nticket 42", None));
+}
+
+/// Fails to commit a trailer value spanning lines, then reads back keys and paths holding colons
+fn multiline_rejected<S: RepoStore<Map<String, Value>>>(store: &S) {
+    store.write_document("logs/2024:01.json", &mut doc(json!({"a:b": 1}))).unwrap();
+    let options = CommitOptions::new().actor("mallory\nActor: alice");
+    assert!(matches!(store.commit_with("import", None, &options), Err(RepoStoreError::Commit(_))));
+    assert!(store.commits(None, &Revision::rev("HEAD")).unwrap().iter().all(|c| c.message() != "import"));
+
+    store.commit_with("import", None, &CommitOptions::new().actor("mallory")).unwrap();
+    let records = audit_log(store, None, &Revision::rev("HEAD")).unwrap();
+    let trailers = records.last().unwrap().trailers();
+    assert_eq!(trailers.actor(), Some("mallory"));
+    assert_eq!(trailers.keys(), [DocumentKey::new("logs/2024:01.json", "a:b")]);
+}
+
+#[test]
+fn multiline_trailer_rejected() {
+    let dir = TestDir::new("audit-multiline");
+    let url = bare_repo(&dir, &[]);
+    let store = git_store(&dir, "cache", &url);
+    store.initialize().unwrap();
+    multiline_rejected(&store);
+    let memory = InMemoryStore::new("store", GitCommit::default());
+    memory.initialize().unwrap();
+    multiline_rejected(&memory);
+}
@@ -0,0 +1,162 @@
+mod common;
+
+use common::{bare_repo, git, write_file, TestDir};
+use gitobi::backend::{CloneOptions, GitBackend, GitBackendError, GitwrapBackend, Remote};
+use gitobi::repo_store::Identity;
+
+#[test]
+fn gitwrap_backend_typed_errors() {
+    let dir = TestDir::new("backend-errors");
+    let url = bare_repo(&dir, &[]);
+    let backend = GitwrapBackend;
+    let path = dir.join("clone");
+    backend.clone_repo(&Remote::new(&url, None), &path, Some("main"), &CloneOptions::new()).unwrap();
+    git(&path, &["config", "user.name", "clone"]);
+    git(&path, &["config", "user.email", "clone@example.com"]);
+
+    assert!(backend.is_repo(&path));
+    assert!(!backend.is_repo(&dir.path().join("missing")));
+    std::fs::create_dir_all(path.join("docs")).unwrap();
+    assert!(!backend.is_repo(&path.join("docs")));
+
+    assert!(matches!(backend.commit(&path, "empty", None, None, None), Err(GitBackendError::NothingToCommit)));
+    write_file(&path, "docs/a.json", "{}");
+    write_file(&path, "docs/b.json", "{}");
+    assert_eq!(backend.status(&path).unwrap(), vec!["docs/a.json", "docs/b.json"]);
+    let jane = Identity::new("Jane", "jane@example.com");
+    backend.commit(&path, "add a", Some(&["docs/a.json".to_string()]), Some(&jane), None).unwrap();
+    assert_eq!(backend.status(&path).unwrap(), vec!["docs/b.json"]);
+    // nothing is staged under the paths, while other changes are
+    assert!(matches!(backend.commit(&path, "again", Some(&["docs/a.json".to_string()]), None, None), Err(GitBackendError::NothingToCommit)));
+    assert_eq!(backend.head_file(&path, "docs/a.json", None).unwrap().as_deref(), Some("{}"));
+    assert_eq!(backend.head_file(&path, "docs/b.json", None).unwrap(), None);
+    assert_eq!(backend.hash_blob(&path, "docs/a.json", b"{}").unwrap(), git(&path, &["rev-parse", "HEAD:docs/a.json"]).trim());
+    assert_eq!(git(&path, &["log", "-1", "--format=%an <%ae> %cn <%ce>"]).trim(), "Jane <jane@example.com> clone <clone@example.com>");
+
+    git(&dir.join("seed"), &["commit", "--allow-empty", "-m", "remote"]);
+    git(&dir.join("seed"), &["push", "origin", "main"]);
+    assert!(matches!(backend.push(&path, &Remote::new(&url, None)), Err(GitBackendError::Rejected(_))));
+}
+
+#[test]
+#[cfg(unix)]
+fn gitwrap_backend_push_declined_by_hook() {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = TestDir::new("backend-declined");
+    let url = bare_repo(&dir, &[]);
+    let backend = GitwrapBackend;
+    let path = dir.join("clone");
+    backend.clone_repo(&Remote::new(&url, None), &path, Some("main"), &CloneOptions::new()).unwrap();
+    git(&path, &["commit", "--allow-empty", "-m", "local"]);
+    let hook = dir.join("origin.git/hooks/pre-receive");
+    std::fs::write(&hook, "#!/bin/sh\nexit 1\n").unwrap();
+    std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    // a hook declining the push is no rejection a pull would get past
+    assert!(matches!(backend.push(&path, &Remote::new(&url, None)), Err(GitBackendError::Git(_))));
+}
+
+#[cfg(feature = "git2")]
+mod git2_backend {
+    use super::*;
+    use common::git_store;
+    use gitobi::audit::{CommitOptions, DocumentKey};
+    use gitobi::git2_backend::Git2Backend;
+    use gitobi::query::{QryClause, QueryableDocument};
+    use gitobi::repo_store::{RepoStore, RepoStoreError};
+
+    #[test]
+    fn git2_store_commit_push_pull() {
+        let dir = TestDir::new("git2-commit-push-pull");
+        let url = bare_repo(&dir, &[("users/john.json", r#"{"name": "John", "age": 43}"#)]);
+        let writer = git_store(&dir, "writer", &url).backend(Git2Backend);
+        let reader = git_store(&dir, "reader", &url).backend(Git2Backend);
+        writer.initialize().unwrap();
+        reader.initialize().unwrap();
+        assert!(writer.initialize().is_ok());
+
+        let mut doc = writer.document("users/john.json").unwrap();
+        doc.update("age", 44.into(), None::<QryClause>).unwrap();
+        writer.write_document("users/john.json", &mut doc).unwrap();
+        writer.write_document("users/eomer.json", &mut doc).unwrap();
+        writer.commit_documents("update john", &["users/john.json".to_string()]).unwrap();
+        assert!(matches!(writer.commit_documents("again", &["users/john.json".to_string()]), Err(RepoStoreError::Commit(_))));
+        // the trailers compare with the committed document read in process
+        let update = &writer.revisions("users/john.json").unwrap()[0];
+        assert_eq!(update.trailers().unwrap().keys(), [DocumentKey::new("users/john.json", "age")]);
+        let options = CommitOptions::new().author(Identity::new("Eomer", "eomer@example.com"));
+        writer.commit_with("add eomer", None, &options).unwrap();
+        writer.push().unwrap();
+
+        reader.pull(true).unwrap();
+        let john = reader.document("users/john.json").unwrap();
+        assert_eq!(john.content().get("age").unwrap().as_i64().unwrap(), 44);
+        let log = git(reader.repo_path(), &["log", "--format=%an %cn %s", "-2"]);
+        assert_eq!(log, "Eomer writer add eomer\nwriter writer update john\n");
+        assert!(git(writer.repo_path(), &["status", "--porcelain"]).is_empty());
+    }
+
+    #[test]
+    fn git2_store_rebases_and_merges() {
+        let dir = TestDir::new("git2-rebase");
+        let url = bare_repo(&dir, &[("doc.json", r#"{"a": 1, "b": 1}"#), ("other.json", "{}")]);
+        let first = git_store(&dir, "first", &url).backend(Git2Backend);
+        let second = git_store(&dir, "second", &url).backend(Git2Backend);
+        first.initialize().unwrap();
+        second.initialize().unwrap();
+
+        write_file(first.repo_path(), "other.json", r#"{"x": 1}"#);
+        first.commit("first").unwrap();
+        first.push().unwrap();
+        write_file(second.repo_path(), "doc.json", r#"{"a": 2, "b": 1}"#);
+        second.commit("second").unwrap();
+        assert!(matches!(second.push(), Err(RepoStoreError::PushRejected(_))));
+
+        // no conflict: the local commit is replayed on top of the remote one
+        second.pull(true).unwrap();
+        assert_eq!(git(second.repo_path(), &["log", "--format=%s", "-2"]), "second\nfirst\n");
+        second.push().unwrap();
+
+        // conflicting commits are merged key by key
+        write_file(first.repo_path(), "doc.json", r#"{"a": 1, "b": 3}"#);
+        first.commit("first b").unwrap();
+        first.pull(true).unwrap();
+        let doc = first.document("doc.json").unwrap();
+        assert_eq!(doc.content().get("a").unwrap().as_i64().unwrap(), 2);
+        assert_eq!(doc.content().get("b").unwrap().as_i64().unwrap(), 3);
+        let parents = git(first.repo_path(), &["rev-list", "--parents", "-1", "HEAD"]);
+        assert_eq!(parents.split_whitespace().count(), 3);
+        first.push().unwrap();
+        assert!(git(first.repo_path(), &["status", "--porcelain"]).is_empty());
+    }
+
+    #[test]
+    fn git2_hash_blob() {
+        let dir = TestDir::new("git2-hash-blob");
+        let url = bare_repo(&dir, &[("doc.json", "{}")]);
+        let store = git_store(&dir, "store", &url).backend(Git2Backend);
+        store.initialize().unwrap();
+        let path = store.repo_path();
+        let expected = git(path, &["rev-parse", "HEAD:doc.json"]);
+        assert_eq!(Git2Backend.hash_blob(path, "doc.json", b"{}").unwrap(), expected.trim());
+        assert_eq!(store.document("doc.json").unwrap().revision(), Some(expected.trim()));
+
+        // end of line conversions set for the path apply, as with the git command line
+        write_file(path, ".gitattributes", "*.json text\n");
+        let content = b"{\r\n}\r\n";
+        assert_eq!(Git2Backend.hash_blob(path, "doc.json", content).unwrap(), GitwrapBackend.hash_blob(path, "doc.json", content).unwrap());
+        assert_ne!(Git2Backend.hash_blob(path, "doc.json", content).unwrap(), Git2Backend.hash_blob(path, "doc.txt", content).unwrap());
+    }
+
+    #[test]
+    fn git2_clone_options() {
+        let dir = TestDir::new("git2-clone-options");
+        let url = bare_repo(&dir, &[]);
+        let partial = git_store(&dir, "partial", &url).backend(Git2Backend).clone_options(CloneOptions::new().filter("blob:none"));
+        assert!(matches!(partial.initialize(), Err(RepoStoreError::Initialize(_))));
+        let store = git_store(&dir, "cache", &url).backend(Git2Backend);
+        store.initialize().unwrap();
+        store.fetch_history(None).unwrap();
+        assert!(!store.is_shallow());
+    }
+}
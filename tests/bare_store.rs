@@ -0,0 +1,122 @@
+mod common;
+
+use common::{bare_repo, doc, git, TestDir};
+use gitobi::bare_store::BareGitStore;
+use gitobi::collection::Collection;
+use gitobi::history::Revision;
+use gitobi::repo_store::{CleanMode, GitCommit, RepoStore, RepoStoreError};
+use serde_json::json;
+use std::sync::Arc;
+use std::thread;
+
+fn bare_store(dir: &TestDir, name: &str) -> BareGitStore {
+    let store = BareGitStore::new(dir.join("origin.git").to_str().unwrap(), "main", GitCommit::new(name, "store@example.com"));
+    store.initialize().unwrap();
+    store
+}
+
+#[test]
+fn bare_store_reads_and_commits() {
+    let dir = TestDir::new("bare-store");
+    bare_repo(&dir, &[("users/john.json", r#"{"name": "John", "age": 43}"#)]);
+    let store = bare_store(&dir, "server");
+
+    let users = Collection::open(&store, "users").unwrap();
+    assert_eq!(users.get("john").unwrap().content().get("age").unwrap(), 43);
+    let first = store.head().unwrap().unwrap();
+
+    users.put("john", &mut doc(json!({"name": "John", "age": 44}))).unwrap();
+    users.put("jane", &mut doc(json!({"name": "Jane"}))).unwrap();
+    assert_eq!(users.ids().unwrap(), vec!["jane", "john"]);
+    assert_eq!(store.head().unwrap().unwrap(), first);
+    store.commit_documents("update john", &["users/john.json".to_string()]).unwrap();
+    assert!(matches!(store.commit_documents("again", &["users/john.json".to_string()]), Err(RepoStoreError::Commit(_))));
+    users.remove("jane").unwrap();
+    assert_eq!(users.ids().unwrap(), vec!["john"]);
+    // adding and removing jane leaves nothing to commit
+    assert!(matches!(store.commit("add and remove jane"), Err(RepoStoreError::Commit(_))));
+
+    let log = git(store.repo_path(), &["log", "--format=%an %s", "main"]);
+    assert_eq!(log, "server update john\nFixture seed\n");
+    assert!(!store.repo_path().join("users").exists());
+    let old = store.document_at("users/john.json", &Revision::rev(&first)).unwrap();
+    assert_eq!(old.content().get("age").unwrap(), 43);
+    assert_eq!(store.revisions("users/john.json").unwrap().len(), 2);
+
+    // the seed clone sees the commit
+    git(&dir.join("seed"), &["pull", "origin", "main"]);
+    assert!(std::fs::read_to_string(dir.join("seed").join("users/john.json")).unwrap().contains("44"));
+}
+
+#[test]
+fn bare_store_initializes_new_repository() {
+    let dir = TestDir::new("bare-store-init");
+    let store = BareGitStore::new(dir.join("docs.git").to_str().unwrap(), "data", GitCommit::new("server", "store@example.com"));
+    store.initialize().unwrap();
+    store.initialize().unwrap();
+    assert!(store.head().unwrap().is_none());
+    assert!(store.documents("").unwrap().is_empty());
+
+    store.write_document("doc.json", &mut doc(json!({"a": 1}))).unwrap();
+    store.commit("first").unwrap();
+    assert_eq!(store.document("doc.json").unwrap().revision(), store.revision("doc.json").unwrap().as_deref());
+    assert!(git(store.repo_path(), &["show", "data:doc.json"]).contains("\"a\""));
+}
+
+#[test]
+fn bare_store_conditional_writes_across_handles() {
+    let dir = TestDir::new("bare-store-cas");
+    bare_repo(&dir, &[("counter.json", r#"{"value": 0}"#)]);
+    let first = bare_store(&dir, "first");
+    let second = bare_store(&dir, "second");
+
+    let base = first.revision("counter.json").unwrap();
+    first.write_document_if("counter.json", &mut doc(json!({"value": 1})), base.as_deref()).unwrap();
+    second.write_document_if("counter.json", &mut doc(json!({"value": 2})), base.as_deref()).unwrap();
+    first.commit("first").unwrap();
+    // the branch changed the document since the second handle read it
+    assert!(matches!(second.commit("second"), Err(RepoStoreError::RevisionConflict(_))));
+    assert_eq!(second.clean(CleanMode::WorkingTree).unwrap(), vec!["counter.json"]);
+    assert_eq!(second.document("counter.json").unwrap().content().get("value").unwrap(), 1);
+}
+
+#[test]
+fn bare_store_shared_copy() {
+    let dir = TestDir::new("bare-store-shared");
+    bare_repo(&dir, &[("counter.json", r#"{"value": 0}"#)]);
+    let store = bare_store(&dir, "store");
+    store.write_document("counter.json", &mut doc(json!({"value": 1}))).unwrap();
+    store.commit("one").unwrap();
+    let head = store.head().unwrap();
+
+    // the commits are already in the shared copy, only uncommitted writes are discarded
+    store.pull(true).unwrap();
+    store.push().unwrap();
+    store.write_document("counter.json", &mut doc(json!({"value": 2}))).unwrap();
+    assert_eq!(store.clean(CleanMode::Remote).unwrap(), vec!["counter.json"]);
+    assert_eq!(store.head().unwrap(), head);
+    assert_eq!(store.document("counter.json").unwrap().content().get("value").unwrap(), 1);
+}
+
+#[test]
+fn bare_store_concurrent_commits() {
+    let dir = TestDir::new("bare-store-concurrent");
+    bare_repo(&dir, &[]);
+    let stores: Vec<Arc<BareGitStore>> = (0..4).map(|i| Arc::new(bare_store(&dir, format!("writer{}", i).as_str()))).collect();
+
+    let handles: Vec<_> = stores.iter().enumerate().map(|(i, store)| {
+        let store = store.clone();
+        thread::spawn(move || {
+            for n in 0..3 {
+                store.write_document(format!("docs/{}-{}.json", i, n).as_str(), &mut doc(json!({"n": n}))).unwrap();
+                store.commit(format!("writer {} doc {}", i, n).as_str()).unwrap();
+            }
+        })
+    }).collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    assert_eq!(stores[0].documents("docs").unwrap().len(), 12);
+    let count = git(stores[0].repo_path(), &["rev-list", "--count", "main"]);
+    assert_eq!(count.trim(), "13");
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use common::{bare_repo, doc, git, git_store, push_commit, write_file, TestDir};
+use gitobi::blame::blame;
+use gitobi::json_document::leaf_keys;
+use gitobi::memory_store::InMemoryStore;
+use gitobi::repo_store::{GitCommit, RepoStore, RepoStoreError};
+use serde_json::json;
+
+#[test]
+fn blame_keys() {
+    let dir = TestDir::new("blame");
+    let url = bare_repo(&dir, &[]);
+    let c1 = push_commit(&dir, &[("tenant.json", r#"{"name": "acme", "limits": {"max_users": 100, "max_docs": 10}}"#)], "create", "Alice", 1_700_000_000);
+    let c2 = push_commit(&dir, &[("tenant.json", r#"{"name": "acme", "limits": {"max_users": 500, "max_docs": 10}, "tags": ["a"]}"#)], "raise users", "Bob", 1_700_000_100);
+    let c3 = push_commit(&dir, &[("tenant.json", r#"{"limits": {"max_docs": 10, "max_users": 500}, "tags": ["a"], "name": "ACME"}"#)], "rename", "Carol", 1_700_000_200);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    let blamed = blame(&store, "tenant.json").unwrap();
+    let keys: Vec<&str> = blamed.iter().map(|b| b.key()).collect();
+    assert_eq!(keys, vec!["limits.max_docs", "limits.max_users", "name", "tags"]);
+
+    assert_eq!(blamed[0].revision().id(), c1);
+    assert_eq!(blamed[0].revision().author(), "Alice");
+    assert_eq!(blamed[1].revision().id(), c2);
+    assert_eq!(blamed[1].revision().author(), "Bob");
+    assert_eq!(blamed[1].revision().date(), 1_700_000_100);
+    assert_eq!(*blamed[1].value(), json!(500));
+    assert_eq!(blamed[2].revision().id(), c3);
+    assert_eq!(blamed[3].revision().id(), c2);
+    assert_eq!(*blamed[3].value(), json!(["a"]));
+}
+
+#[test]
+fn blame_key_removed_and_restored() {
+    let dir = TestDir::new("blame-restored");
+    let url = bare_repo(&dir, &[]);
+    push_commit(&dir, &[("doc.json", r#"{"a": 1, "b": 1}"#)], "create", "Alice", 1_700_000_000);
+    push_commit(&dir, &[("doc.json", r#"{"b": 1}"#)], "drop a", "Bob", 1_700_000_100);
+    let c3 = push_commit(&dir, &[("doc.json", r#"{"a": 1, "b": 1}"#)], "restore a", "Carol", 1_700_000_200);
+    git(&dir.join("seed"), &["rm", "-q", "doc.json"]);
+    git(&dir.join("seed"), &["commit", "-m", "delete"]);
+    git(&dir.join("seed"), &["push", "origin", "main"]);
+    let c5 = push_commit(&dir, &[("doc.json", r#"{"a": 1}"#)], "recreate", "Dave", 1_700_000_400);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    let blamed = blame(&store, "doc.json").unwrap();
+    assert_eq!(blamed.len(), 1);
+    assert_eq!(blamed[0].key(), "a");
+    assert_eq!(blamed[0].revision().id(), c5);
+    assert_ne!(blamed[0].revision().id(), c3);
+
+    assert!(blame(&store, "missing.json").unwrap().is_empty());
+}
+
+#[test]
+fn blame_fails_on_unreadable_revision() {
+    let dir = TestDir::new("blame-unreadable");
+    let url = bare_repo(&dir, &[]);
+    push_commit(&dir, &[("doc.json", r#"{"a": 1}"#)], "create", "Alice", 1_700_000_000);
+    push_commit(&dir, &[("doc.json", r#"{"a": "#)], "break", "Bob", 1_700_000_100);
+    push_commit(&dir, &[("doc.json", r#"{"a": 1, "b": 2}"#)], "fix", "Carol", 1_700_000_200);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    // a document which does not parse is not taken for a deleted one, blaming "a" to the last commit
+    assert!(matches!(blame(&store, "doc.json"), Err(RepoStoreError::Read(_))));
+    let head = git(store.repo_path(), &["rev-parse", "HEAD:doc.json"]);
+    assert_eq!(store.revision_at("doc.json", &"HEAD".into()).unwrap().as_deref(), Some(head.trim()));
+    assert_eq!(store.revision_at("missing.json", &"HEAD".into()).unwrap(), None);
+    assert!(store.revision_at("doc.json", &"missing".into()).is_err());
+}
+
+#[test]
+fn blame_merged_branch() {
+    let dir = TestDir::new("blame-merge");
+    let url = bare_repo(&dir, &[]);
+    let seed = dir.join("seed");
+    let c1 = push_commit(&dir, &[("doc.json", "{\n  \"a\": 1,\n  \"x\": 0,\n  \"y\": 0,\n  \"b\": 1\n}\n")], "create", "Alice", 1_700_000_000);
+    git(&seed, &["checkout", "-q", "-b", "side"]);
+    write_file(&seed, "doc.json", "{\n  \"a\": 1,\n  \"x\": 0,\n  \"y\": 0,\n  \"b\": 2\n}\n");
+    git(&seed, &["commit", "-q", "-am", "side b"]);
+    git(&seed, &["checkout", "-q", "main"]);
+    // committed before the side branch, so listed after it by date
+    let c2 = push_commit(&dir, &[("doc.json", "{\n  \"a\": 2,\n  \"x\": 0,\n  \"y\": 0,\n  \"b\": 1\n}\n")], "main a", "Bob", 1_700_000_100);
+    git(&seed, &["merge", "-q", "--no-ff", "-m", "merge side", "side"]);
+    git(&seed, &["push", "-q", "origin", "main"]);
+    let merge = git(&seed, &["rev-parse", "HEAD"]).trim().to_string();
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    let blamed = blame(&store, "doc.json").unwrap();
+    let blamed: Vec<(&str, &str)> = blamed.iter().map(|b| (b.key(), b.revision().id())).collect();
+    assert_eq!(blamed, [("a", c2.as_str()), ("b", merge.as_str()), ("x", c1.as_str()), ("y", c1.as_str())]);
+}
+
+#[test]
+fn blame_reads_only_document_commits() {
+    let dir = TestDir::new("blame-document-commits");
+    let url = bare_repo(&dir, &[]);
+    let c1 = push_commit(&dir, &[("doc.json", r#"{"a": 1}"#)], "create", "Alice", 1_700_000_000);
+    push_commit(&dir, &[("other.json", r#"{"z": 1}"#)], "other", "Bob", 1_700_000_100);
+    let c3 = push_commit(&dir, &[("doc.json", r#"{"a": 2}"#)], "update", "Carol", 1_700_000_200);
+    push_commit(&dir, &[("other.json", r#"{"z": 2}"#)], "other again", "Dave", 1_700_000_300);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    let commits = store.document_commits("doc.json", &"HEAD".into()).unwrap();
+    let ids: Vec<&str> = commits.iter().map(|c| c.id()).collect();
+    assert_eq!(ids, [c1.as_str(), c3.as_str()]);
+    assert_eq!(blame(&store, "doc.json").unwrap()[0].revision().id(), c3);
+
+    let memory = InMemoryStore::new("memory", GitCommit::new("memory", "memory@example.com"));
+    memory.initialize().unwrap();
+    memory.write_document("doc.json", &mut doc(json!({"a": 1}))).unwrap();
+    memory.commit("create").unwrap();
+    memory.write_document("other.json", &mut doc(json!({"z": 1}))).unwrap();
+    memory.commit("other").unwrap();
+    memory.remove_document("doc.json").unwrap();
+    memory.commit("delete").unwrap();
+    let messages: Vec<String> = memory.document_commits("doc.json", &"HEAD".into()).unwrap().iter().map(|c| c.message().to_string()).collect();
+    assert_eq!(messages, ["create", "delete"]);
+    assert!(blame(&memory, "doc.json").unwrap().is_empty());
+}
+
+#[test]
+fn blame_leaf_keys() {
+    let data = r#"
+        {
+            "name": "John",
+            "address": {
+                "zip": 7777,
+                "geo": {
+                    "lat": 1.5
+                }
+            },
+            "phones": [],
+            "extra": {}
+        }"#;
+
+    let doc = serde_json::from_str(data).unwrap();
+    let mut keys = leaf_keys(&doc);
+    keys.sort();
+
+    assert_eq!(keys, vec!["address.geo.lat", "address.zip", "extra", "name", "phones"]);
+}
@@ -0,0 +1,164 @@
+mod common;
+
+use common::{bare_repo, doc, git, git_store, write_file, TestDir};
+use gitobi::bare_store::BareGitStore;
+use gitobi::history::Revision;
+use gitobi::memory_store::{InMemoryRemote, InMemoryStore};
+use gitobi::repo_store::{GitCommit, RepoStore, RepoStoreError};
+use serde_json::json;
+
+fn value(store: &dyn RepoStore<serde_json::Map<String, serde_json::Value>>, path: &str, key: &str) -> serde_json::Value {
+    store.document(path).unwrap().content().get(key).cloned().unwrap_or_default()
+}
+
+/// Forks `tenant` from the active branch, changing `a` there and `b` on the active branch,
+/// then both changing `c` so that merging conflicts
+fn diverge(store: &dyn RepoStore<serde_json::Map<String, serde_json::Value>>) {
+    store.write_document("doc.json", &mut doc(json!({"a": 1, "b": 1, "c": 1}))).unwrap();
+    store.commit("seed doc").unwrap();
+    store.create_branch("tenant", &Revision::rev("HEAD")).unwrap();
+    store.write_document("doc.json", &mut doc(json!({"a": 1, "b": 2, "c": 1}))).unwrap();
+    store.commit("main b").unwrap();
+    store.switch_branch("tenant").unwrap();
+    store.write_document("doc.json", &mut doc(json!({"a": 2, "b": 1, "c": 1}))).unwrap();
+    store.commit("tenant a").unwrap();
+    store.switch_branch("main").unwrap();
+
+    store.merge_branch("tenant").unwrap();
+    assert_eq!(value(store, "doc.json", "a"), 2);
+    assert_eq!(value(store, "doc.json", "b"), 2);
+    // merging again changes nothing
+    store.merge_branch("tenant").unwrap();
+
+    store.write_document("doc.json", &mut doc(json!({"a": 2, "b": 2, "c": 3}))).unwrap();
+    store.write_document("notes.txt", &mut doc(json!({}))).unwrap();
+    store.commit("main c").unwrap();
+    store.switch_branch("tenant").unwrap();
+    store.write_document("doc.json", &mut doc(json!({"a": 2, "b": 1, "c": 4}))).unwrap();
+    store.write_document("notes.txt", &mut doc(json!({"x": 1}))).unwrap();
+    store.commit("tenant c").unwrap();
+    store.switch_branch("main").unwrap();
+    match store.merge_branch("tenant") {
+        Err(RepoStoreError::MergeConflict(conflicts)) => {
+            let paths: Vec<&str> = conflicts.iter().map(|c| c.path()).collect();
+            assert_eq!(paths, vec!["doc.json", "notes.txt"]);
+            assert_eq!(conflicts[0].keys()[0].key(), "c");
+            assert!(conflicts[1].keys().is_empty());
+        },
+        other => panic!("expected a merge conflict, got {:?}", other),
+    }
+    // the active branch is left as it was
+    assert_eq!(value(store, "doc.json", "c"), 3);
+    assert_eq!(store.current_branch().unwrap(), "main");
+}
+
+#[test]
+fn git_store_branches() {
+    let dir = TestDir::new("git-store-branches");
+    let url = bare_repo(&dir, &[("users/john.json", r#"{"name": "John", "age": 43}"#)]);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+    let seed = git(store.repo_path(), &["rev-parse", "HEAD"]).trim().to_string();
+    write_file(store.repo_path(), "users/john.json", r#"{"name": "John", "age": 44}"#);
+    store.commit("john 44").unwrap();
+
+    store.create_branch("experiment", &Revision::rev(&seed)).unwrap();
+    assert!(matches!(store.create_branch("experiment", &Revision::rev("HEAD")), Err(RepoStoreError::Branch(_))));
+    assert_eq!(store.branches().unwrap(), vec!["experiment", "main"]);
+    assert_eq!(store.current_branch().unwrap(), "main");
+
+    store.switch_branch("experiment").unwrap();
+    assert_eq!(value(&store, "users/john.json", "age"), 43);
+    store.write_document("users/jane.json", &mut doc(json!({"name": "Jane"}))).unwrap();
+    store.commit("add jane").unwrap();
+    store.push().unwrap();
+    assert!(matches!(store.delete_branch("experiment"), Err(RepoStoreError::Branch(_))));
+
+    // another clone picks the branch up from the remote
+    let other = git_store(&dir, "other", &url);
+    other.initialize().unwrap();
+    other.switch_branch("experiment").unwrap();
+    assert_eq!(value(&other, "users/jane.json", "name"), "Jane");
+    other.switch_branch("main").unwrap();
+    other.delete_branch("experiment").unwrap();
+    assert_eq!(other.branches().unwrap(), vec!["main"]);
+    assert!(matches!(other.switch_branch("missing"), Err(RepoStoreError::Branch(_))));
+}
+
+#[test]
+fn git_store_merge_branch() {
+    let dir = TestDir::new("git-store-merge-branch");
+    let url = bare_repo(&dir, &[]);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+    diverge(&store);
+    assert!(git(store.repo_path(), &["status", "--porcelain"]).is_empty());
+    // the commit before "main c" merged tenant
+    let parents = git(store.repo_path(), &["rev-list", "--parents", "-1", "HEAD~1"]);
+    assert_eq!(parents.split_whitespace().count(), 3);
+}
+
+#[test]
+fn memory_store_branches() {
+    let remote = InMemoryRemote::new();
+    let store = InMemoryStore::new("store", GitCommit::default()).remote(&remote);
+    store.initialize().unwrap();
+    diverge(&store);
+    assert_eq!(store.branches().unwrap(), vec!["main", "tenant"]);
+
+    store.switch_branch("tenant").unwrap();
+    store.push().unwrap();
+    let other = InMemoryStore::new("other", GitCommit::default()).remote(&remote);
+    other.initialize().unwrap();
+    assert_eq!(other.current_branch().unwrap(), "main");
+    assert!(other.document("doc.json").is_err());
+    other.switch_branch("tenant").unwrap();
+    assert_eq!(value(&other, "doc.json", "c"), 4);
+    assert!(matches!(other.delete_branch("tenant"), Err(RepoStoreError::Branch(_))));
+}
+
+#[test]
+fn bare_store_branches() {
+    let dir = TestDir::new("bare-store-branches");
+    bare_repo(&dir, &[]);
+    let store = BareGitStore::new(dir.join("origin.git").to_str().unwrap(), "main", GitCommit::new("server", "store@example.com"));
+    store.initialize().unwrap();
+    diverge(&store);
+    let parents = git(store.repo_path(), &["rev-list", "--parents", "-1", "main~1"]);
+    assert_eq!(parents.split_whitespace().count(), 3);
+
+    store.write_document("draft.json", &mut doc(json!({}))).unwrap();
+    assert!(matches!(store.switch_branch("tenant"), Err(RepoStoreError::Branch(_))));
+    store.commit("draft").unwrap();
+    store.delete_branch("tenant").unwrap();
+    assert_eq!(store.branches().unwrap(), vec!["main"]);
+    assert!(matches!(store.merge_branch("tenant"), Err(RepoStoreError::Branch(_))));
+}
+
+#[test]
+fn bare_store_merge_modify_delete_conflict() {
+    let dir = TestDir::new("bare-store-modify-delete");
+    bare_repo(&dir, &[]);
+    let store = BareGitStore::new(dir.join("origin.git").to_str().unwrap(), "main", GitCommit::new("server", "store@example.com"));
+    store.initialize().unwrap();
+    store.write_document("doc.json", &mut doc(json!({"a": 1, "b": 1}))).unwrap();
+    store.commit("seed doc").unwrap();
+    store.create_branch("tenant", &Revision::rev("HEAD")).unwrap();
+    store.write_document("doc.json", &mut doc(json!({"a": 2, "b": 1}))).unwrap();
+    store.commit("main a").unwrap();
+    store.switch_branch("tenant").unwrap();
+    store.remove_document("doc.json").unwrap();
+    store.commit("tenant removal").unwrap();
+    store.switch_branch("main").unwrap();
+
+    // the deletion is not merged key by key into a partial document
+    match store.merge_branch("tenant") {
+        Err(RepoStoreError::MergeConflict(conflicts)) => {
+            assert_eq!(conflicts.len(), 1);
+            assert_eq!(conflicts[0].path(), "doc.json");
+            assert!(conflicts[0].keys().is_empty());
+        },
+        other => panic!("expected a merge conflict, got {:?}", other),
+    }
+    assert_eq!(value(&store, "doc.json", "a"), 2);
+}
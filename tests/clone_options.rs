@@ -0,0 +1,84 @@
+mod common;
+
+use common::{bare_repo, doc, git, git_store, push_commit, TestDir};
+use gitobi::backend::CloneOptions;
+use gitobi::history::Revision;
+use gitobi::repo_store::{CleanMode, RepoStore, RepoStoreError};
+use serde_json::json;
+
+/// Objects of the history of HEAD missing from the repository
+fn missing_objects(repo: &std::path::Path) -> usize {
+    git(repo, &["rev-list", "--objects", "--missing=print", "HEAD"]).lines().filter(|l| l.starts_with('?')).count()
+}
+
+#[test]
+fn shallow_clone() {
+    let dir = TestDir::new("clone-shallow");
+    let url = bare_repo(&dir, &[("users/john.json", r#"{"name": "John", "age": 40}"#)]);
+    for age in 41..44 {
+        push_commit(&dir, &[("users/john.json", format!(r#"{{"name": "John", "age": {}}}"#, age).as_str())], "birthday", "John", 1_700_000_000 + age);
+    }
+    let store = git_store(&dir, "cache", &url).clone_options(CloneOptions::new().depth(2));
+    store.initialize().unwrap();
+    assert!(store.is_shallow());
+    assert_eq!(store.commits(None, &Revision::rev("HEAD")).unwrap().len(), 2);
+    assert_eq!(store.revisions("users/john.json").unwrap().len(), 2);
+
+    store.write_document("users/jane.json", &mut doc(json!({"name": "Jane"}))).unwrap();
+    store.commit("add jane").unwrap();
+    store.push().unwrap();
+    push_commit(&dir, &[("users/mark.json", r#"{"name": "Mark"}"#)], "add mark", "Mark", 1_800_000_000);
+    store.pull(true).unwrap();
+    assert_eq!(store.documents("users").unwrap(), ["jane.json", "john.json", "mark.json"]);
+    assert_eq!(git(&dir.join("origin.git"), &["log", "-2", "--format=%s"]), "add mark\nadd jane\n");
+
+    store.fetch_history(None).unwrap();
+    assert!(!store.is_shallow());
+    assert_eq!(store.revisions("users/john.json").unwrap().len(), 4);
+    assert_eq!(store.document_at("users/john.json", &Revision::rev("HEAD~5")).unwrap().content().get("age").unwrap(), 40);
+}
+
+#[test]
+fn partial_sparse_clone() {
+    let dir = TestDir::new("clone-partial-sparse");
+    let url = bare_repo(&dir, &[
+        ("users/john.json", r#"{"name": "John"}"#),
+        ("orders/1.json", r#"{"total": 10}"#),
+        ("orders/2.json", r#"{"total": 20}"#),
+    ]);
+    git(&dir.join("origin.git"), &["config", "uploadpack.allowFilter", "true"]);
+    let store = git_store(&dir, "cache", &url)
+        .clone_options(CloneOptions::new().filter("blob:none").sparse(&["users"]));
+    store.initialize().unwrap();
+    let repo = store.repo_path().to_path_buf();
+    assert!(repo.join("README.md").is_file() && repo.join("users/john.json").is_file());
+    assert!(!repo.join("orders").exists());
+
+    assert_eq!(store.documents("users").unwrap(), ["john.json"]);
+    assert!(matches!(store.documents("orders"), Err(RepoStoreError::Read(_))));
+    assert!(matches!(store.document("orders/1.json"), Err(RepoStoreError::Read(_))));
+    assert!(matches!(store.write_document("orders/3.json", &mut doc(json!({"total": 30}))), Err(RepoStoreError::Write(_))));
+    // the blobs outside of the sparse checkout are fetched when first read
+    let missing = missing_objects(&repo);
+    assert_eq!(missing, 2);
+    assert_eq!(store.document_at("orders/1.json", &Revision::rev("HEAD")).unwrap().content().get("total").unwrap(), 10);
+    assert_eq!(missing_objects(&repo), missing - 1);
+    assert_eq!(store.paths_at(&Revision::rev("HEAD")).unwrap().len(), 4);
+
+    store.write_document("users/jane.json", &mut doc(json!({"name": "Jane"}))).unwrap();
+    store.commit("add jane").unwrap();
+    push_commit(&dir, &[("orders/3.json", r#"{"total": 30}"#), ("users/mark.json", r#"{"name": "Mark"}"#)], "add mark", "Mark", 1_800_000_000);
+    store.pull(true).unwrap();
+    store.push().unwrap();
+    assert_eq!(store.documents("users").unwrap(), ["jane.json", "john.json", "mark.json"]);
+    assert!(!repo.join("orders").exists());
+    assert_eq!(git(&dir.join("origin.git"), &["ls-tree", "-r", "--name-only", "main"]), "README.md\norders/1.json\norders/2.json\norders/3.json\nusers/jane.json\nusers/john.json\nusers/mark.json\n");
+
+    // resetting to the upstream fetches the blobs it checks out
+    push_commit(&dir, &[("users/mark.json", r#"{"name": "Marcus"}"#)], "rename mark", "Mark", 1_800_000_100);
+    store.fetch().unwrap();
+    store.write_document("users/draft.json", &mut doc(json!({}))).unwrap();
+    store.commit("draft").unwrap();
+    assert_eq!(store.clean(CleanMode::Remote).unwrap(), ["users/draft.json"]);
+    assert_eq!(store.document("users/mark.json").unwrap().content().get("name").unwrap(), "Marcus");
+}
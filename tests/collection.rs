@@ -0,0 +1,81 @@
+mod common;
+
+use common::{bare_repo, doc, git_store, TestDir};
+use gitobi::collection::Collection;
+use gitobi::query::{QryClause, QueryClause};
+use gitobi::repo_store::RepoStore;
+use serde_json::json;
+
+#[test]
+fn collection_create_put_ids() {
+    let dir = TestDir::new("collection-create");
+    let url = bare_repo(&dir, &[]);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    let users = Collection::create(&store, "users").unwrap();
+    assert!(users.ids().unwrap().is_empty());
+
+    users.put("john", &mut doc(json!({"name": "John", "age": 43}))).unwrap();
+    users.put("jane", &mut doc(json!({"name": "Jane", "age": 37}))).unwrap();
+    assert_eq!(users.ids().unwrap(), vec!["jane", "john"]);
+    assert_eq!(users.get("john").unwrap().content().get("age").unwrap().as_i64().unwrap(), 43);
+
+    users.remove("jane").unwrap();
+    assert_eq!(users.ids().unwrap(), vec!["john"]);
+
+    // empty collections survive a commit
+    Collection::create(&store, "empty").unwrap();
+    store.commit("collections").unwrap();
+    assert!(Collection::open(&store, "empty").unwrap().ids().unwrap().is_empty());
+}
+
+#[test]
+fn collection_drop() {
+    let dir = TestDir::new("collection-drop");
+    let url = bare_repo(&dir, &[("users/john.json", r#"{"name": "John"}"#)]);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    let users = Collection::open(&store, "users").unwrap();
+    assert_eq!(users.ids().unwrap(), vec!["john"]);
+    users.drop_collection().unwrap();
+
+    assert!(Collection::open(&store, "users").unwrap().ids().is_err());
+}
+
+#[test]
+fn collection_find() {
+    let dir = TestDir::new("collection-find");
+    let url = bare_repo(&dir, &[]);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    let users = Collection::create(&store, "users").unwrap();
+    users.put("john", &mut doc(json!({"name": "John", "age": 43, "address": {"city": "Edoras"}}))).unwrap();
+    users.put("jane", &mut doc(json!({"name": "Jane", "age": 37, "address": {"city": "Minas Tirith"}}))).unwrap();
+    users.put("joe", &mut doc(json!({"name": "Joe"}))).unwrap();
+
+    let qry: QryClause = QueryClause::greater_than("age", 40);
+    assert_eq!(users.find(&qry).unwrap(), vec!["john"]);
+
+    let qry: QryClause = QueryClause::or(QueryClause::equal("address.city", "Minas Tirith"), QueryClause::equal("age", 43));
+    assert_eq!(users.find(&qry).unwrap(), vec!["jane", "john"]);
+
+    let qry: QryClause = QueryClause::equal("name", "Nobody");
+    assert!(users.find(&qry).unwrap().is_empty());
+}
+
+#[test]
+fn collection_invalid_names() {
+    let dir = TestDir::new("collection-invalid");
+    let url = bare_repo(&dir, &[]);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    assert!(Collection::open(&store, "../escape").is_err());
+    assert!(Collection::open(&store, ".git").is_err());
+    let users = Collection::create(&store, "users").unwrap();
+    assert!(users.get("a/b").is_err());
+    assert!(users.document_path("").is_err());
+}
@@ -0,0 +1,112 @@
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use gitobi::json_document::Document;
+use gitobi::repo_store::{GitAuth, GitCommit, GitStore};
+use serde_json::{Map, Value};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A scratch directory removed when dropped
+pub struct TestDir {
+    path: PathBuf,
+}
+
+impl TestDir {
+    pub fn new(prefix: &str) -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+        let n = COUNTER.fetch_add(1, Ordering::SeqCst);
+        let path = std::env::temp_dir().join(format!("gitobi-{}-{}-{}-{}", prefix, std::process::id(), nanos, n));
+        fs::create_dir_all(&path).unwrap();
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn join(&self, p: &str) -> PathBuf {
+        self.path.join(p)
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Runs a git command in `dir` with a fixed identity, panicking on failure
+pub fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(["-c", "user.name=Fixture", "-c", "user.email=fixture@example.com", "-c", "init.defaultBranch=main"])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Creates a bare `origin.git` repository under `dir` seeded with the given files, returning its url
+pub fn bare_repo(dir: &TestDir, files: &[(&str, &str)]) -> String {
+    let origin = dir.join("origin.git");
+    fs::create_dir_all(&origin).unwrap();
+    git(&origin, &["init", "--bare", "--initial-branch=main"]);
+
+    let seed = dir.join("seed");
+    let url = format!("file://{}", origin.to_str().unwrap());
+    git(dir.path(), &["clone", url.as_str(), seed.to_str().unwrap()]);
+    git(&seed, &["checkout", "-b", "main"]);
+    fs::write(seed.join("README.md"), "fixture\n").unwrap();
+    for (path, content) in files {
+        write_file(&seed, path, content);
+    }
+    git(&seed, &["add", "-A"]);
+    git(&seed, &["commit", "-m", "seed"]);
+    git(&seed, &["push", "origin", "main"]);
+    url
+}
+
+/// Commits `files` in the seed clone of [bare_repo] with the given author and date, pushing it to origin
+pub fn push_commit(dir: &TestDir, files: &[(&str, &str)], msg: &str, author: &str, timestamp: i64) -> String {
+    let seed = dir.join("seed");
+    git(&seed, &["pull", "--rebase", "origin", "main"]);
+    for (path, content) in files {
+        write_file(&seed, path, content);
+    }
+    git(&seed, &["add", "-A"]);
+    let date = format!("@{} +0000", timestamp);
+    let output = Command::new("git")
+        .args(["-c", format!("user.name={}", author).as_str(), "-c", "user.email=fixture@example.com", "commit", "-m", msg])
+        .env("GIT_AUTHOR_DATE", &date)
+        .env("GIT_COMMITTER_DATE", &date)
+        .current_dir(&seed)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "commit failed: {}", String::from_utf8_lossy(&output.stderr));
+    git(&seed, &["push", "origin", "main"]);
+    git(&seed, &["rev-parse", "HEAD"]).trim().to_string()
+}
+
+/// Writes `content` to `path` relative to `root`, creating any missing directories
+pub fn write_file(root: &Path, path: &str, content: &str) {
+    let file = root.join(path);
+    fs::create_dir_all(file.parent().unwrap()).unwrap();
+    fs::write(file, content).unwrap();
+}
+
+/// Builds a store cloning `url` into `dir/<name>`
+pub fn git_store(dir: &TestDir, name: &str, url: &str) -> GitStore {
+    let path = dir.join(name);
+    GitStore::new(name, url, path.to_str().unwrap(), Some("main"), GitAuth::default(), GitCommit::new(name, "store@example.com"))
+}
+
+/// A document holding the JSON object `value`
+pub fn doc(value: Value) -> Document<Map<String, Value>> {
+    Document::new(value.as_object().unwrap().clone())
+}
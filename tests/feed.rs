@@ -0,0 +1,81 @@
+mod common;
+
+use common::{bare_repo, doc, git, git_store, push_commit, TestDir};
+use gitobi::feed::{changes, changes_since, ChangeKind, FeedCursor};
+use gitobi::history::Revision;
+use gitobi::memory_store::InMemoryStore;
+use gitobi::repo_store::{GitCommit, RepoStore};
+use serde_json::{json, Value};
+
+#[test]
+fn feed_after_pull() {
+    let dir = TestDir::new("feed-after-pull");
+    let url = bare_repo(&dir, &[("users/john.json", r#"{"name": "John", "age": 43}"#)]);
+    let store = git_store(&dir, "cache", &url);
+    store.initialize().unwrap();
+    let seen = changes_since(&store, None).unwrap();
+    assert_eq!(seen.events().len(), 1);
+    assert_eq!(seen.events()[0].kind(), ChangeKind::Created);
+    assert_eq!(seen.events()[0].keys()[0].after(), Some(&json!(43)));
+    // the cursor is persisted as a string
+    let cursor = seen.cursor().unwrap().to_string();
+
+    push_commit(&dir, &[("users/john.json", r#"{"name": "John", "age": 44}"#), ("users/jane.json", r#"{"name": "Jane"}"#)], "john 44, add jane", "admin", 1700000000);
+    push_commit(&dir, &[("users/jane.json", r#"{"name": "Jane", "tags": {"role": "dev"}}"#)], "jane role", "admin", 1700000100);
+    store.pull(true).unwrap();
+
+    let feed = changes_since(&store, Some(&FeedCursor::new(&cursor))).unwrap();
+    let events: Vec<(ChangeKind, &str, &str)> = feed.events().iter().map(|e| (e.kind(), e.path(), e.commit().message())).collect();
+    assert_eq!(events, vec![
+        (ChangeKind::Created, "users/jane.json", "john 44, add jane"),
+        (ChangeKind::Updated, "users/john.json", "john 44, add jane"),
+        (ChangeKind::Updated, "users/jane.json", "jane role"),
+    ]);
+    let john = &feed.events()[1];
+    assert_eq!(john.commit().author(), "admin");
+    assert_eq!(john.keys().len(), 1);
+    assert_eq!((john.keys()[0].key(), john.keys()[0].before(), john.keys()[0].after()), ("age", Some(&json!(43)), Some(&json!(44))));
+    assert_eq!(feed.events()[2].keys()[0].key(), "tags.role");
+
+    // resuming from the new cursor finds nothing until the next change
+    let resumed = changes_since(&store, feed.cursor()).unwrap();
+    assert!(resumed.events().is_empty());
+    assert_eq!(resumed.cursor(), feed.cursor());
+    push_commit(&dir, &[("README.md", "changed\n")], "no documents changed", "admin", 1700000200);
+    let seed = dir.join("seed");
+    git(&seed, &["rm", "-q", "users/john.json"]);
+    git(&seed, &["commit", "-m", "remove john"]);
+    git(&seed, &["push", "origin", "main"]);
+    store.pull(true).unwrap();
+    let feed = changes_since(&store, resumed.cursor()).unwrap();
+    assert_eq!(feed.events().len(), 1);
+    assert_eq!(feed.events()[0].kind(), ChangeKind::Deleted);
+    assert_eq!(feed.events()[0].keys()[0].after(), None);
+
+    // the same changes between two revisions
+    let between = changes(&store, Some(&Revision::rev(&cursor)), &Revision::as_of(1700000150)).unwrap();
+    assert_eq!(between.events().len(), 3);
+}
+
+#[test]
+fn memory_store_feed() {
+    let store = InMemoryStore::new("store", GitCommit::default());
+    store.initialize().unwrap();
+    let empty = changes_since(&store, None).unwrap();
+    assert!(empty.events().is_empty() && empty.cursor().is_none());
+
+    store.write_document("doc.json", &mut doc(json!({"a": 1}))).unwrap();
+    store.commit("first").unwrap();
+    let first = changes_since(&store, None).unwrap();
+    store.write_document("doc.json", &mut doc(json!({"a": 2, "b": true}))).unwrap();
+    store.commit("second").unwrap();
+    let feed = changes_since(&store, first.cursor()).unwrap();
+    assert_eq!(feed.events().len(), 1);
+    let keys: Vec<(&str, Option<&Value>, Option<&Value>)> = feed.events()[0].keys().iter().map(|k| (k.key(), k.before(), k.after())).collect();
+    assert_eq!(keys, vec![("a", Some(&json!(1)), Some(&json!(2))), ("b", None, Some(&json!(true)))]);
+
+    // nothing new since a branch, the cursor is the commit it points to
+    let same = changes(&store, Some(&Revision::rev("main")), &Revision::rev("HEAD")).unwrap();
+    assert!(same.events().is_empty());
+    assert_eq!(same.cursor().map(FeedCursor::as_str), store.head().unwrap().as_deref());
+}
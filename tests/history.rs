@@ -0,0 +1,110 @@
+mod common;
+
+use common::{bare_repo, doc, git, git_store, push_commit, TestDir};
+use gitobi::history::Revision;
+use gitobi::repo_store::RepoStore;
+
+const T1: i64 = 1_700_000_000;
+const T2: i64 = 1_700_086_400;
+const T3: i64 = 1_700_172_800;
+
+fn fixture(dir: &TestDir) -> (String, Vec<String>) {
+    let url = bare_repo(dir, &[]);
+    let c1 = push_commit(dir, &[("limits.json", r#"{"max_users": 100}"#)], "create limits", "Alice", T1);
+    let c2 = push_commit(dir, &[("limits.json", r#"{"max_users": 200}"#)], "raise limits\n\nticket 42", "Bob", T2);
+    let c3 = push_commit(dir, &[("limits.json", r#"{"max_users": 500}"#)], "raise limits again", "Carol", T3);
+    (url, vec![c1, c2, c3])
+}
+
+fn max_users<S: RepoStore<serde_json::Map<String, serde_json::Value>>>(store: &S, revision: &Revision) -> i64 {
+    let doc = store.document_at("limits.json", revision).unwrap();
+    doc.content().get("max_users").unwrap().as_i64().unwrap()
+}
+
+#[test]
+fn history_document_at_commit_branch_tag() {
+    let dir = TestDir::new("history-at");
+    let (url, commits) = fixture(&dir);
+    git(&dir.join("seed"), &["tag", "v1", commits[0].as_str()]);
+    git(&dir.join("seed"), &["push", "origin", "v1"]);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    assert_eq!(max_users(&store, &Revision::rev(commits[1].as_str())), 200);
+    assert_eq!(max_users(&store, &Revision::rev(&commits[1][..8])), 200);
+    assert_eq!(max_users(&store, &"main".into()), 500);
+    assert_eq!(max_users(&store, &"origin/main~2".into()), 100);
+    assert_eq!(max_users(&store, &"v1".into()), 100);
+
+    assert!(store.document_at("limits.json", &"no-such-branch".into()).is_err());
+    assert!(store.document_at("missing.json", &"main".into()).is_err());
+}
+
+#[test]
+fn history_document_as_of() {
+    let dir = TestDir::new("history-as-of");
+    let (url, _) = fixture(&dir);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    assert_eq!(max_users(&store, &Revision::as_of(T1)), 100);
+    assert_eq!(max_users(&store, &Revision::as_of(T2 - 1)), 100);
+    assert_eq!(max_users(&store, &Revision::as_of(T2 + 1)), 200);
+    assert_eq!(max_users(&store, &Revision::as_of(T3 * 2)), 500);
+
+    // before the document existed
+    assert!(store.document_at("limits.json", &Revision::as_of(T1 - 1)).is_err());
+}
+
+#[test]
+fn history_revisions() {
+    let dir = TestDir::new("history-revisions");
+    let (url, commits) = fixture(&dir);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    let revisions = store.revisions("limits.json").unwrap();
+    assert_eq!(revisions.len(), 3);
+    assert_eq!(revisions[0].id(), commits[2]);
+    assert_eq!(revisions[0].author(), "Carol");
+    assert_eq!(revisions[0].date(), T3);
+    assert_eq!(revisions[0].message(), "raise limits again");
+    assert_eq!(revisions[1].author(), "Bob");
+    assert_eq!(revisions[1].email(), "fixture@example.com");
+    assert_eq!(revisions[1].message(), "raise limits\n\nticket 42");
+    assert_eq!(revisions[2].id(), commits[0]);
+
+    assert!(store.revisions("missing.json").unwrap().is_empty());
+}
+
+#[test]
+fn history_reads_ignore_git_warnings() {
+    let dir = TestDir::new("history-warnings");
+    let (url, commits) = fixture(&dir);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+    // a deprecated setting makes every git command print a warning on stderr
+    git(store.repo_path(), &["config", "core.fsyncObjectFiles", "true"]);
+
+    assert_eq!(store.resolve(&"main~1".into()).unwrap(), commits[1]);
+    assert_eq!(max_users(&store, &Revision::as_of(T2 + 1)), 200);
+    assert_eq!(store.revisions("limits.json").unwrap()[2].id(), commits[0]);
+    assert_eq!(store.paths_at(&"main".into()).unwrap(), ["README.md", "limits.json"]);
+}
+
+#[test]
+fn history_dates_are_commit_dates() {
+    let dir = TestDir::new("history-commit-dates");
+    let (url, _) = fixture(&dir);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+    store.write_document("limits.json", &mut doc(serde_json::json!({"max_users": 1000}))).unwrap();
+    store.commit("raise limits once more").unwrap();
+    // authored long before it was committed, as a rebased commit is
+    git(store.repo_path(), &["commit", "--amend", "--no-edit", format!("--date=@{}", T1).as_str()]);
+
+    let latest = &store.revisions("limits.json").unwrap()[0];
+    assert!(latest.date() > T3);
+    assert_eq!(max_users(&store, &Revision::as_of(latest.date())), 1000);
+    assert_eq!(max_users(&store, &Revision::as_of(latest.date() - 1)), 500);
+}
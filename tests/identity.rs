@@ -0,0 +1,59 @@
+mod common;
+
+use common::{bare_repo, doc, git, git_store, TestDir};
+use gitobi::audit::CommitOptions;
+use gitobi::bare_store::BareGitStore;
+use gitobi::memory_store::InMemoryStore;
+use gitobi::repo_store::{GitCommit, Identity, RepoStore};
+use gitobi::transaction::Transaction;
+use serde_json::{json, Map, Value};
+use std::path::Path;
+
+/// Commits on behalf of two users, returning the authors of the document revisions, newest first
+fn commit_as_users<S: RepoStore<Map<String, Value>>>(store: &S) -> Vec<(String, String)> {
+    let jane = Identity::new("Jane", "jane@example.com");
+    let admin = Identity::new("Admin", "admin@example.com");
+    store.write_document("users/mark.json", &mut doc(json!({"name": "Mark"}))).unwrap();
+    store.commit_with("add mark", None, &CommitOptions::new().author(jane.clone())).unwrap();
+
+    let mut transaction = Transaction::new(store);
+    transaction.put("users/mark.json", doc(json!({"name": "Mark", "age": 30})));
+    transaction.commit_with("mark 30", &CommitOptions::new().author(Identity::new("Mark", "mark@example.com")).committer(admin)).unwrap();
+    store.write_document("users/mark.json", &mut doc(json!({"name": "Mark", "age": 31}))).unwrap();
+    store.commit("mark 31").unwrap();
+
+    store.revisions("users/mark.json").unwrap()
+        .iter()
+        .map(|r| (r.author().to_string(), r.email().to_string()))
+        .collect()
+}
+
+fn committers(repo: &Path) -> String {
+    git(repo, &["log", "-3", "--format=%an/%cn"])
+}
+
+#[test]
+fn git_store_commit_identity() {
+    let dir = TestDir::new("identity-git-store");
+    let url = bare_repo(&dir, &[]);
+    let store = git_store(&dir, "cache", &url);
+    store.initialize().unwrap();
+    let authors = commit_as_users(&store);
+    assert_eq!(authors[1], (String::from("Mark"), String::from("mark@example.com")));
+    assert_eq!(committers(store.repo_path()), "cache/cache\nMark/Admin\nJane/cache\n");
+}
+
+#[test]
+fn memory_and_bare_store_commit_identity() {
+    let memory = InMemoryStore::new("store", GitCommit::new("service", "service@example.com"));
+    memory.initialize().unwrap();
+    let authors: Vec<String> = commit_as_users(&memory).into_iter().map(|(name, _)| name).collect();
+    assert_eq!(authors, ["service", "Mark", "Jane"]);
+
+    let dir = TestDir::new("identity-bare-store");
+    bare_repo(&dir, &[]);
+    let bare = BareGitStore::new(dir.join("origin.git").to_str().unwrap(), "main", GitCommit::new("service", "service@example.com"));
+    bare.initialize().unwrap();
+    commit_as_users(&bare);
+    assert_eq!(committers(bare.repo_path()), "service/service\nMark/Admin\nJane/service\n");
+}
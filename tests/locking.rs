@@ -0,0 +1,153 @@
+mod common;
+
+use common::{bare_repo, doc, git, git_store, TestDir};
+use gitobi::repo_store::{LockPolicy, RepoStore, RepoStoreError};
+use gitobi::transaction::Transaction;
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Takes the lock on `lock` from another thread through its own handle, as another process would,
+/// holding it until the returned sender is dropped
+fn hold_lock(lock: &Path) -> (mpsc::Sender<()>, thread::JoinHandle<()>) {
+    let file = fs::OpenOptions::new().read(true).write(true).create(true).truncate(false).open(lock).unwrap();
+    let (locked_tx, locked_rx) = mpsc::channel();
+    let (release_tx, release_rx) = mpsc::channel::<()>();
+    let holder = thread::spawn(move || {
+        file.try_lock().unwrap();
+        locked_tx.send(()).unwrap();
+        // returns once the sender is dropped, closing the file releasing the lock
+        let _ = release_rx.recv();
+    });
+    locked_rx.recv().unwrap();
+    (release_tx, holder)
+}
+
+#[test]
+fn lock_held_by_another_process() {
+    let dir = TestDir::new("lock-held");
+    let url = bare_repo(&dir, &[]);
+    let store = git_store(&dir, "cache", &url).lock_policy(LockPolicy::new(Duration::from_millis(100), Duration::from_millis(10)));
+    store.initialize().unwrap();
+    let lock = store.repo_path().join(".git").join("gitobi.lock");
+
+    let (release, holder) = hold_lock(&lock);
+    let result = store.write_document("a.json", &mut doc(json!({"v": 1})));
+    assert!(matches!(result, Err(RepoStoreError::Locked(_))), "{:?}", result);
+    assert!(matches!(store.commit("nothing"), Err(RepoStoreError::Locked(_))));
+    // closing the handle of the holder releases the lock
+    drop(release);
+    holder.join().unwrap();
+    store.write_document("a.json", &mut doc(json!({"v": 1}))).unwrap();
+    store.commit("add a").unwrap();
+    assert_eq!(fs::read_to_string(&lock).unwrap(), std::process::id().to_string());
+}
+
+#[test]
+fn stale_lock_file_raced() {
+    let dir = TestDir::new("lock-stale");
+    let url = bare_repo(&dir, &[]);
+    let store = git_store(&dir, "cache", &url);
+    store.initialize().unwrap();
+    store.write_document("counter.json", &mut doc(json!({"count": 0}))).unwrap();
+    // a lock file left by a process that died, no longer locked
+    let mut dead = Command::new("git").arg("--version").stdout(Stdio::null()).spawn().unwrap();
+    dead.wait().unwrap();
+    fs::write(store.repo_path().join(".git").join("gitobi.lock"), dead.id().to_string()).unwrap();
+
+    // increments checking the revision they read race on the stale lock, none of them lost
+    let racers: Vec<_> = (0..4).map(|_| {
+        let store = git_store(&dir, "cache", &url);
+        thread::spawn(move || {
+            let mut done = 0;
+            while done < 5 {
+                let current = store.document("counter.json").unwrap();
+                let count = current.content()["count"].as_u64().unwrap();
+                match store.write_document_if("counter.json", &mut doc(json!({"count": count + 1})), current.revision()) {
+                    Ok(_) => done += 1,
+                    Err(RepoStoreError::RevisionConflict(_)) => {},
+                    Err(e) => panic!("{}", e),
+                }
+            }
+        })
+    }).collect();
+    for racer in racers {
+        racer.join().unwrap();
+    }
+    assert_eq!(store.document("counter.json").unwrap().content()["count"], 20);
+}
+
+#[test]
+fn concurrent_stores_on_one_repo() {
+    let dir = TestDir::new("lock-concurrent");
+    let url = bare_repo(&dir, &[]);
+    git_store(&dir, "cache", &url).initialize().unwrap();
+    let writers: Vec<_> = (0..4).map(|writer| {
+        let store = git_store(&dir, "cache", &url);
+        thread::spawn(move || {
+            for i in 0..5 {
+                let path = format!("docs/{}-{}.json", writer, i);
+                store.write_document(&path, &mut doc(json!({"writer": writer, "i": i}))).unwrap();
+                store.commit_documents(format!("add {}", path).as_str(), &[path]).unwrap();
+            }
+        })
+    }).collect();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+    let store = git_store(&dir, "cache", &url);
+    assert_eq!(store.documents("docs").unwrap().len(), 20);
+    assert_eq!(git(store.repo_path(), &["rev-list", "--count", "HEAD"]).trim(), "21");
+    assert!(git(store.repo_path(), &["status", "--porcelain"]).is_empty());
+}
+
+#[test]
+fn concurrent_initialize() {
+    let dir = TestDir::new("lock-initialize");
+    let url = bare_repo(&dir, &[("users/john.json", r#"{"name": "John"}"#)]);
+    // each store finds the clone missing or half done, only one of them cloning it
+    let initializers: Vec<_> = (0..4).map(|_| {
+        let store = git_store(&dir, "cache", &url).lock_policy(LockPolicy::new(Duration::from_secs(30), Duration::from_millis(10)));
+        thread::spawn(move || store.initialize().map_err(|e| e.to_string()))
+    }).collect();
+    for initializer in initializers {
+        initializer.join().unwrap().unwrap();
+    }
+    let store = git_store(&dir, "cache", &url);
+    assert_eq!(store.document("users/john.json").unwrap().content()["name"], "John");
+    assert!(git(store.repo_path(), &["status", "--porcelain"]).is_empty());
+    assert!(dir.join("cache.gitobi.lock").exists());
+}
+
+#[test]
+fn transaction_holds_lock() {
+    let dir = TestDir::new("lock-transaction");
+    let url = bare_repo(&dir, &[]);
+    let store = git_store(&dir, "cache", &url).lock_policy(LockPolicy::new(Duration::from_millis(100), Duration::from_millis(10)));
+    store.initialize().unwrap();
+    let lock = store.repo_path().join(".git").join("gitobi.lock");
+
+    // the transaction takes the lock before reading or writing anything
+    let (release, holder) = hold_lock(&lock);
+    let mut tx = Transaction::new(&store);
+    tx.put("a.json", doc(json!({"v": 1})));
+    assert!(matches!(tx.commit("add a"), Err(RepoStoreError::Locked(_))));
+    assert!(!store.repo_path().join("a.json").exists());
+    drop(release);
+    holder.join().unwrap();
+
+    // while it holds the lock, other stores wait for it
+    let guard = store.lock_store().unwrap();
+    let other = git_store(&dir, "cache", &url).lock_policy(LockPolicy::no_wait());
+    let blocked = thread::spawn(move || matches!(other.write_document("b.json", &mut doc(json!({}))), Err(RepoStoreError::Locked(_))));
+    assert!(blocked.join().unwrap());
+    store.write_document("a.json", &mut doc(json!({"v": 1}))).unwrap();
+    drop(guard);
+    let mut tx = Transaction::new(&store);
+    tx.put("a.json", doc(json!({"v": 2})));
+    tx.commit("update a").unwrap();
+}
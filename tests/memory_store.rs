@@ -0,0 +1,141 @@
+mod common;
+
+use common::doc;
+use gitobi::collection::Collection;
+use gitobi::history::Revision;
+use gitobi::memory_store::{InMemoryRemote, InMemoryStore};
+use gitobi::merge::MergePolicy;
+use gitobi::repo_store::{CleanMode, GitCommit, RepoStore, RepoStoreError, SyncPolicy};
+use gitobi::transaction::Transaction;
+use serde_json::json;
+
+fn store(name: &str, remote: &InMemoryRemote) -> InMemoryStore {
+    let store = InMemoryStore::new(name, GitCommit::new(name, "store@example.com")).remote(remote);
+    store.initialize().unwrap();
+    store
+}
+
+#[test]
+fn memory_store_documents_and_history() {
+    let store = InMemoryStore::new("store", GitCommit::new("store", "store@example.com"));
+    store.initialize().unwrap();
+
+    let users = Collection::create(&store, "users").unwrap();
+    users.put("john", &mut doc(json!({"name": "John", "age": 43}))).unwrap();
+    users.put("jane", &mut doc(json!({"name": "Jane"}))).unwrap();
+    assert_eq!(users.ids().unwrap(), vec!["jane", "john"]);
+    store.commit("add users").unwrap();
+    assert!(matches!(store.commit("again"), Err(RepoStoreError::Commit(_))));
+    let first = store.resolve(&Revision::rev("HEAD")).unwrap();
+
+    let mut tx = Transaction::new(&store);
+    tx.put("users/john.json", doc(json!({"name": "John", "age": 44})));
+    tx.remove("users/jane.json");
+    tx.commit("update users").unwrap();
+
+    let revisions = store.revisions("users/john.json").unwrap();
+    assert_eq!(revisions.len(), 2);
+    assert_eq!(revisions[0].message(), "update users");
+    assert_eq!(revisions[1].author(), "store");
+    let old = store.document_at("users/john.json", &Revision::rev(&first[..8])).unwrap();
+    assert_eq!(old.content().get("age").unwrap().as_i64().unwrap(), 43);
+    assert!(store.document("users/jane.json").is_err());
+    assert_eq!(store.revisions("users/jane.json").unwrap().len(), 2);
+}
+
+#[test]
+fn memory_store_revisions_and_clean() {
+    let store = InMemoryStore::new("store", GitCommit::default());
+    store.initialize().unwrap();
+    let mut john = doc(json!({"age": 43}));
+    store.write_document_if("john.json", &mut john, None).unwrap();
+    let revision = john.revision().unwrap().to_string();
+    assert_eq!(store.revision("john.json").unwrap().as_deref(), Some(revision.as_str()));
+    assert!(matches!(store.write_document_if("john.json", &mut doc(json!({})), None), Err(RepoStoreError::RevisionConflict(_))));
+    store.commit("john").unwrap();
+
+    store.write_document("john.json", &mut doc(json!({"age": 44}))).unwrap();
+    store.write_document("draft.json", &mut doc(json!({}))).unwrap();
+    assert!(matches!(store.remove_document_if("john.json", &revision), Err(RepoStoreError::RevisionConflict(_))));
+    assert_eq!(store.clean(CleanMode::WorkingTree).unwrap(), vec!["draft.json", "john.json"]);
+    assert_eq!(store.document("john.json").unwrap().revision(), Some(revision.as_str()));
+    assert!(matches!(store.clean(CleanMode::Remote), Err(RepoStoreError::Clean(_))));
+}
+
+#[test]
+fn memory_store_push_pull() {
+    let remote = InMemoryRemote::new();
+    let writer = store("writer", &remote);
+    writer.write_document("doc.json", &mut doc(json!({"a": 1, "b": 1}))).unwrap();
+    writer.commit("seed").unwrap();
+    writer.push().unwrap();
+
+    let reader = store("reader", &remote);
+    assert_eq!(reader.document("doc.json").unwrap().content().get("a").unwrap(), 1);
+
+    // diverging edits to different keys are rebased and merged
+    writer.write_document("doc.json", &mut doc(json!({"a": 2, "b": 1}))).unwrap();
+    writer.commit("writer a").unwrap();
+    writer.push().unwrap();
+    reader.write_document("doc.json", &mut doc(json!({"a": 1, "b": 3}))).unwrap();
+    reader.commit("reader b").unwrap();
+    assert!(matches!(reader.push(), Err(RepoStoreError::PushRejected(_))));
+    reader.sync(&SyncPolicy::default()).unwrap();
+
+    writer.pull(false).unwrap();
+    let merged = writer.document("doc.json").unwrap();
+    assert_eq!(merged.content().get("a").unwrap(), 2);
+    assert_eq!(merged.content().get("b").unwrap(), 3);
+    let messages: Vec<String> = writer.revisions("doc.json").unwrap().iter().map(|r| r.message().to_string()).collect();
+    assert_eq!(messages.len(), 3);
+    assert!(messages.contains(&"reader b".to_string()));
+}
+
+#[test]
+fn memory_store_pull_conflicts() {
+    let remote = InMemoryRemote::new();
+    let first = store("first", &remote);
+    first.write_document("doc.json", &mut doc(json!({"a": 1}))).unwrap();
+    first.commit("seed").unwrap();
+    first.push().unwrap();
+    let second = store("second", &remote);
+
+    first.write_document("doc.json", &mut doc(json!({"a": 2}))).unwrap();
+    first.commit("first").unwrap();
+    first.push().unwrap();
+    second.write_document("doc.json", &mut doc(json!({"a": 3}))).unwrap();
+    second.commit("second").unwrap();
+
+    match second.pull(true) {
+        Err(RepoStoreError::Conflict(conflicts)) => assert_eq!(conflicts[0].key(), "a"),
+        other => panic!("expected a conflict, got {:?}", other),
+    }
+    assert_eq!(second.document("doc.json").unwrap().content().get("a").unwrap(), 3);
+
+    let second = second.merge_policy(MergePolicy::Theirs);
+    second.pull(true).unwrap();
+    assert_eq!(second.document("doc.json").unwrap().content().get("a").unwrap(), 2);
+    assert!(matches!(second.clean(CleanMode::Remote), Ok(paths) if paths.is_empty()));
+}
+
+#[test]
+fn memory_store_pull_modify_delete_conflict() {
+    let remote = InMemoryRemote::new();
+    let first = store("first", &remote);
+    first.write_document("doc.json", &mut doc(json!({"a": 1, "b": 1}))).unwrap();
+    first.commit("seed").unwrap();
+    first.push().unwrap();
+    let second = store("second", &remote).merge_policy(MergePolicy::Theirs);
+
+    first.write_document("doc.json", &mut doc(json!({"a": 2, "b": 1}))).unwrap();
+    first.commit("first").unwrap();
+    first.push().unwrap();
+    second.remove_document("doc.json").unwrap();
+    second.commit("second").unwrap();
+
+    // the deletion is not merged key by key into a partial document
+    for rebase in [true, false] {
+        assert!(matches!(second.pull(rebase), Err(RepoStoreError::Pull(_))), "rebase={}", rebase);
+        assert!(second.document("doc.json").is_err());
+    }
+}
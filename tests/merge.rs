@@ -0,0 +1,170 @@
+mod common;
+
+use common::{bare_repo, git, git_store, push_commit, write_file, TestDir};
+use gitobi::merge::{three_way_merge, MergePolicy, MergeTimes};
+use gitobi::repo_store::{GitStore, RepoStore, RepoStoreError};
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+
+const TIMES: MergeTimes = MergeTimes { ours: 2, theirs: 1 };
+
+fn map(value: Value) -> Map<String, Value> {
+    value.as_object().unwrap().clone()
+}
+
+#[test]
+fn merge_disjoint_keys() {
+    let base = map(json!({"a": 1, "b": 1, "nested": {"x": 1, "y": 1}, "gone": true}));
+    let ours = map(json!({"a": 2, "b": 1, "nested": {"x": 2, "y": 1}, "gone": true, "new_ours": 1}));
+    let theirs = map(json!({"a": 1, "b": 2, "nested": {"x": 1, "y": 2}, "new_theirs": 1}));
+
+    let merged = three_way_merge("doc.json", &base, &ours, &theirs, &MergePolicy::Report, TIMES).unwrap();
+    assert_eq!(Value::Object(merged), json!({"a": 2, "b": 2, "nested": {"x": 2, "y": 2}, "new_ours": 1, "new_theirs": 1}));
+}
+
+#[test]
+fn merge_same_change_is_not_a_conflict() {
+    let base = map(json!({"a": 1}));
+    let ours = map(json!({"a": 2, "b": [1, 2]}));
+    let theirs = map(json!({"a": 2, "b": [1, 2]}));
+
+    let merged = three_way_merge("doc.json", &base, &ours, &theirs, &MergePolicy::Report, TIMES).unwrap();
+    assert_eq!(Value::Object(merged), json!({"a": 2, "b": [1, 2]}));
+}
+
+#[test]
+fn merge_report_conflicts() {
+    let base = map(json!({"a": 1, "limits": {"max": 10}, "c": 1}));
+    let ours = map(json!({"a": 2, "limits": {"max": 20}}));
+    let theirs = map(json!({"a": 3, "limits": {"max": 30}, "c": 2}));
+
+    let conflicts = three_way_merge("doc.json", &base, &ours, &theirs, &MergePolicy::Report, TIMES).unwrap_err();
+    let keys: Vec<&str> = conflicts.iter().map(|c| c.key()).collect();
+    assert_eq!(keys, vec!["a", "c", "limits.max"]);
+    assert_eq!(conflicts[1].path(), "doc.json");
+    assert_eq!(conflicts[1].base(), Some(&json!(1)));
+    assert_eq!(conflicts[1].ours(), None);
+    assert_eq!(conflicts[1].theirs(), Some(&json!(2)));
+}
+
+#[test]
+fn merge_policies() {
+    let base = map(json!({"a": 1, "b": 1}));
+    let ours = map(json!({"a": 2}));
+    let theirs = map(json!({"a": 3, "b": 3}));
+
+    let merged = three_way_merge("doc.json", &base, &ours, &theirs, &MergePolicy::Ours, TIMES).unwrap();
+    assert_eq!(Value::Object(merged), json!({"a": 2}));
+    let merged = three_way_merge("doc.json", &base, &ours, &theirs, &MergePolicy::Theirs, TIMES).unwrap();
+    assert_eq!(Value::Object(merged), json!({"a": 3, "b": 3}));
+    let merged = three_way_merge("doc.json", &base, &ours, &theirs, &MergePolicy::Newest, TIMES).unwrap();
+    assert_eq!(Value::Object(merged), json!({"a": 2}));
+    let merged = three_way_merge("doc.json", &base, &ours, &theirs, &MergePolicy::Newest, MergeTimes { ours: 1, theirs: 1 }).unwrap();
+    assert_eq!(Value::Object(merged), json!({"a": 3, "b": 3}));
+
+    let sum = MergePolicy::Callback(Arc::new(|c| {
+        let o = c.ours().and_then(Value::as_i64).unwrap_or_default();
+        let t = c.theirs().and_then(Value::as_i64).unwrap_or_default();
+        Some(json!(o + t))
+    }));
+    let merged = three_way_merge("doc.json", &base, &ours, &theirs, &sum, TIMES).unwrap();
+    assert_eq!(Value::Object(merged), json!({"a": 5, "b": 3}));
+}
+
+fn diverged(dir: &TestDir, policy: MergePolicy, local: &str, remote: &str) -> GitStore {
+    let url = bare_repo(dir, &[("doc.json", r#"{"a": 1, "b": 1, "nested": {"x": 1, "y": 1}}"#)]);
+    let store = git_store(dir, "store", &url).merge_policy(policy);
+    store.initialize().unwrap();
+    push_commit(dir, &[("doc.json", remote), ("other.txt", "remote")], "remote change", "Remote", 1_600_000_000);
+    write_file(store.repo_path(), "doc.json", local);
+    store.commit("local change").unwrap();
+    store
+}
+
+fn content(store: &GitStore) -> Value {
+    Value::Object(store.document("doc.json").unwrap().content().clone())
+}
+
+#[test]
+fn pull_merges_disjoint_changes() {
+    for rebase in [true, false] {
+        let dir = TestDir::new("pull-merge");
+        let store = diverged(&dir, MergePolicy::Report, r#"{"a": 2, "b": 1, "nested": {"x": 1, "y": 2}}"#, r#"{"a": 1, "b": 2, "nested": {"x": 2, "y": 1}}"#);
+
+        store.pull(rebase).unwrap();
+        assert_eq!(content(&store), json!({"a": 2, "b": 2, "nested": {"x": 2, "y": 2}}));
+        assert!(store.repo_path().join("other.txt").exists());
+        let status = git(store.repo_path(), &["status", "--porcelain"]);
+        assert!(status.is_empty());
+        store.push().unwrap();
+    }
+}
+
+#[test]
+fn pull_reports_key_conflicts() {
+    for rebase in [true, false] {
+        let dir = TestDir::new("pull-conflict");
+        let store = diverged(&dir, MergePolicy::Report, r#"{"a": 2, "b": 1, "nested": {"x": 1, "y": 1}}"#, r#"{"a": 3, "b": 1, "nested": {"x": 1, "y": 1}}"#);
+        let head = git(store.repo_path(), &["rev-parse", "HEAD"]);
+
+        match store.pull(rebase) {
+            Err(RepoStoreError::Conflict(conflicts)) => {
+                assert_eq!(conflicts.len(), 1);
+                assert_eq!(conflicts[0].path(), "doc.json");
+                assert_eq!(conflicts[0].key(), "a");
+                assert_eq!(conflicts[0].ours(), Some(&json!(2)));
+                assert_eq!(conflicts[0].theirs(), Some(&json!(3)));
+            },
+            other => panic!("unexpected pull result {:?}", other),
+        }
+        // the interrupted pull is rolled back
+        assert_eq!(git(store.repo_path(), &["rev-parse", "HEAD"]), head);
+        assert!(git(store.repo_path(), &["status", "--porcelain"]).is_empty());
+        assert_eq!(content(&store), json!({"a": 2, "b": 1, "nested": {"x": 1, "y": 1}}));
+    }
+}
+
+#[test]
+fn pull_resolves_with_policy() {
+    for (policy, expected) in [(MergePolicy::Ours, 2), (MergePolicy::Theirs, 3), (MergePolicy::Newest, 2)] {
+        for rebase in [true, false] {
+            let dir = TestDir::new("pull-policy");
+            let store = diverged(&dir, policy.clone(), r#"{"a": 2, "b": 1, "nested": {"x": 1, "y": 1}}"#, r#"{"a": 3, "b": 2, "nested": {"x": 1, "y": 1}}"#);
+
+            store.pull(rebase).unwrap();
+            assert_eq!(content(&store), json!({"a": expected, "b": 2, "nested": {"x": 1, "y": 1}}), "{:?} rebase={}", policy, rebase);
+        }
+    }
+}
+
+#[test]
+fn pull_fails_on_non_document_conflict() {
+    let dir = TestDir::new("pull-non-document");
+    let url = bare_repo(&dir, &[("notes.txt", "base")]);
+    let store = git_store(&dir, "store", &url).merge_policy(MergePolicy::Ours);
+    store.initialize().unwrap();
+    push_commit(&dir, &[("notes.txt", "remote")], "remote change", "Remote", 1_600_000_000);
+    write_file(store.repo_path(), "notes.txt", "local");
+    store.commit("local change").unwrap();
+
+    assert!(matches!(store.pull(true), Err(RepoStoreError::Pull(_))));
+    assert!(git(store.repo_path(), &["status", "--porcelain"]).is_empty());
+}
+
+#[test]
+fn pull_fails_on_modify_delete_conflict() {
+    for rebase in [true, false] {
+        let dir = TestDir::new("pull-modify-delete");
+        let url = bare_repo(&dir, &[("doc.json", r#"{"a": 1, "b": 1}"#)]);
+        let store = git_store(&dir, "store", &url).merge_policy(MergePolicy::Theirs);
+        store.initialize().unwrap();
+        push_commit(&dir, &[("doc.json", r#"{"a": 2, "b": 1}"#)], "remote change", "Remote", 1_600_000_000);
+        store.remove_document("doc.json").unwrap();
+        store.commit("local removal").unwrap();
+
+        // the deletion is not merged key by key into a partial document
+        assert!(matches!(store.pull(rebase), Err(RepoStoreError::Pull(_))), "rebase={}", rebase);
+        assert!(git(store.repo_path(), &["status", "--porcelain"]).is_empty());
+        assert!(store.document("doc.json").is_err());
+    }
+}
@@ -0,0 +1,288 @@
+mod common;
+
+use common::{bare_repo, doc, git, git_store, write_file, TestDir};
+use gitobi::bare_store::BareGitStore;
+use gitobi::memory_store::InMemoryStore;
+use gitobi::query::{QryClause, QueryableDocument};
+use gitobi::repo_store::{CleanMode, GitCommit, RepoStore, RepoStoreError, SyncPolicy};
+use serde_json::{json, Map, Value};
+use std::time::Duration;
+
+#[test]
+fn git_store_initialize() {
+    let dir = TestDir::new("initialize");
+    let url = bare_repo(&dir, &[]);
+    let store = git_store(&dir, "store", &url);
+
+    store.initialize().unwrap();
+    assert!(store.repo_path().join(".git").exists());
+    assert!(store.repo_path().join("README.md").exists());
+
+    // initializing an existing clone is a no-op
+    store.initialize().unwrap();
+    let user = git(store.repo_path(), &["config", "--local", "user.name"]);
+    assert_eq!(user.trim(), "store");
+}
+
+#[test]
+fn git_store_initialize_replaces_invalid_dir() {
+    let dir = TestDir::new("initialize-invalid");
+    let url = bare_repo(&dir, &[]);
+    let store = git_store(&dir, "store", &url);
+
+    std::fs::create_dir_all(store.repo_path()).unwrap();
+    std::fs::write(store.repo_path().join("garbage"), "x").unwrap();
+
+    store.initialize().unwrap();
+    assert!(store.repo_path().join("README.md").exists());
+    assert!(!store.repo_path().join("garbage").exists());
+}
+
+#[test]
+fn git_store_document() {
+    let dir = TestDir::new("document");
+    let url = bare_repo(&dir, &[("users/john.json", r#"{"name": "John", "age": 43}"#)]);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    let doc = store.document("users/john.json").unwrap();
+    assert_eq!(doc.content().get("name").unwrap().as_str().unwrap(), "John");
+
+    assert!(store.document("users/missing.json").is_err());
+}
+
+#[test]
+fn git_store_rejects_paths_outside_working_tree() {
+    let dir = TestDir::new("invalid-path");
+    let url = bare_repo(&dir, &[("users/john.json", r#"{"name": "John"}"#)]);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+    write_file(dir.path(), "outside/secret.json", "{}");
+
+    for path in ["/etc/passwd", "../outside/secret.json", "users/../../outside/secret.json", ".git/config", "./.git/HEAD"] {
+        assert!(matches!(store.document(path), Err(RepoStoreError::InvalidPath(_))), "{}", path);
+        assert!(matches!(store.write_document(path, &mut doc(json!({}))), Err(RepoStoreError::InvalidPath(_))), "{}", path);
+        assert!(matches!(store.remove_document(path), Err(RepoStoreError::InvalidPath(_))), "{}", path);
+        assert!(matches!(store.revision(path), Err(RepoStoreError::InvalidPath(_))), "{}", path);
+    }
+    for dir in ["../outside", "/tmp", ".git", ".git/hooks", "", "."] {
+        assert!(matches!(store.remove_dir(dir), Err(RepoStoreError::InvalidPath(_))), "{}", dir);
+    }
+    assert!(matches!(store.documents("../outside"), Err(RepoStoreError::InvalidPath(_))));
+    assert!(matches!(store.create_dir(".git/objects/x"), Err(RepoStoreError::InvalidPath(_))));
+    assert!(dir.join("outside/secret.json").exists());
+    assert!(store.repo_path().join(".git/config").exists());
+
+    // paths inside the working tree are still accepted
+    assert_eq!(store.document("./users/john.json").unwrap().content().get("name").unwrap(), "John");
+    assert_eq!(store.documents("users").unwrap(), vec!["john.json"]);
+}
+
+#[test]
+fn git_store_commit_push_pull() {
+    let dir = TestDir::new("commit-push-pull");
+    let url = bare_repo(&dir, &[("users/john.json", r#"{"name": "John", "age": 43}"#)]);
+    let writer = git_store(&dir, "writer", &url);
+    let reader = git_store(&dir, "reader", &url);
+    writer.initialize().unwrap();
+    reader.initialize().unwrap();
+
+    let mut doc = writer.document("users/john.json").unwrap();
+    doc.update("age", 44.into(), None::<QryClause>).unwrap();
+    writer.write_document("users/john.json", &mut doc).unwrap();
+    let mut new_doc = writer.document("users/john.json").unwrap();
+    new_doc.update("name", "Eomer".into(), None::<QryClause>).unwrap();
+    writer.write_document("users/eomer.json", &mut new_doc).unwrap();
+    writer.commit("update john, add eomer").unwrap();
+    writer.push().unwrap();
+
+    reader.pull(true).unwrap();
+    let john = reader.document("users/john.json").unwrap();
+    assert_eq!(john.content().get("age").unwrap().as_i64().unwrap(), 44);
+    let eomer = reader.document("users/eomer.json").unwrap();
+    assert_eq!(eomer.content().get("name").unwrap().as_str().unwrap(), "Eomer");
+
+    let log = git(reader.repo_path(), &["log", "-1", "--format=%an %s"]);
+    assert_eq!(log.trim(), "writer update john, add eomer");
+}
+
+#[test]
+fn git_store_commit_nothing_fails() {
+    let dir = TestDir::new("commit-nothing");
+    let url = bare_repo(&dir, &[]);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    assert!(store.commit("nothing").is_err());
+}
+
+#[test]
+fn git_store_clean_working_tree() {
+    let dir = TestDir::new("clean-working-tree");
+    let url = bare_repo(&dir, &[("users/john.json", r#"{"name": "John"}"#), ("users/jane.json", r#"{"name": "Jane"}"#)]);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    write_file(store.repo_path(), "users/committed.json", r#"{"name": "Committed"}"#);
+    store.commit("local commit").unwrap();
+    write_file(store.repo_path(), "users/john.json", r#"{"name": "Jo"#);
+    std::fs::remove_file(store.repo_path().join("users/jane.json")).unwrap();
+    write_file(store.repo_path(), "users/tmp/half.json", r#"{"na"#);
+
+    let discarded = store.clean(CleanMode::WorkingTree).unwrap();
+    assert_eq!(discarded, vec!["users/jane.json", "users/john.json", "users/tmp/half.json"]);
+
+    let john = store.document("users/john.json").unwrap();
+    assert_eq!(john.content().get("name").unwrap().as_str().unwrap(), "John");
+    assert!(store.document("users/jane.json").is_ok());
+    assert!(!store.repo_path().join("users/tmp").exists());
+    // local commits are kept
+    assert!(store.document("users/committed.json").is_ok());
+}
+
+#[test]
+fn git_store_clean_remote() {
+    let dir = TestDir::new("clean-remote");
+    let url = bare_repo(&dir, &[("users/john.json", r#"{"name": "John"}"#)]);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    write_file(store.repo_path(), "users/unpushed.json", r#"{"name": "Unpushed"}"#);
+    store.commit("local commit").unwrap();
+    write_file(store.repo_path(), "users/untracked.json", r#"{"name": "Untracked"}"#);
+
+    let discarded = store.clean(CleanMode::Remote).unwrap();
+    assert_eq!(discarded, vec!["users/unpushed.json", "users/untracked.json"]);
+    assert!(store.document("users/unpushed.json").is_err());
+
+    let head = git(store.repo_path(), &["rev-parse", "HEAD"]);
+    let upstream = git(store.repo_path(), &["rev-parse", "@{upstream}"]);
+    assert_eq!(head, upstream);
+}
+
+#[test]
+fn git_store_clean_nothing() {
+    let dir = TestDir::new("clean-nothing");
+    let url = bare_repo(&dir, &[]);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    assert!(store.clean(CleanMode::Remote).unwrap().is_empty());
+}
+
+fn sync_policy(attempts: u32) -> SyncPolicy {
+    SyncPolicy::new(attempts, Duration::from_millis(1), Duration::from_millis(5))
+}
+
+#[test]
+fn git_store_push_rejected() {
+    let dir = TestDir::new("push-rejected");
+    let url = bare_repo(&dir, &[]);
+    let first = git_store(&dir, "first", &url);
+    let second = git_store(&dir, "second", &url);
+    first.initialize().unwrap();
+    second.initialize().unwrap();
+
+    write_file(first.repo_path(), "a.json", r#"{"a": 1}"#);
+    first.commit("first").unwrap();
+    first.push().unwrap();
+    write_file(second.repo_path(), "b.json", r#"{"b": 1}"#);
+    second.commit("second").unwrap();
+
+    assert!(matches!(second.push(), Err(RepoStoreError::PushRejected(_))));
+    match second.sync(&sync_policy(1)) {
+        Err(RepoStoreError::SyncFailed(1, e)) => assert!(matches!(*e, RepoStoreError::PushRejected(_))),
+        other => panic!("unexpected sync result {:?}", other),
+    }
+}
+
+#[test]
+fn git_store_sync_retries() {
+    let dir = TestDir::new("sync-retries");
+    let url = bare_repo(&dir, &[]);
+    let first = git_store(&dir, "first", &url);
+    let second = git_store(&dir, "second", &url);
+    first.initialize().unwrap();
+    second.initialize().unwrap();
+
+    write_file(first.repo_path(), "a.json", r#"{"a": 1}"#);
+    first.commit("first").unwrap();
+    first.sync(&sync_policy(3)).unwrap();
+    write_file(second.repo_path(), "b.json", r#"{"b": 1}"#);
+    second.commit("second").unwrap();
+    second.sync(&sync_policy(3)).unwrap();
+
+    first.pull(true).unwrap();
+    assert!(first.document("b.json").is_ok());
+    let log = git(first.repo_path(), &["log", "--format=%s"]);
+    assert_eq!(log.lines().collect::<Vec<_>>(), vec!["second", "first", "seed"]);
+}
+
+#[test]
+fn git_store_sync_conflict() {
+    let dir = TestDir::new("sync-conflict");
+    let url = bare_repo(&dir, &[("doc.json", r#"{"a": 0}"#)]);
+    let first = git_store(&dir, "first", &url);
+    let second = git_store(&dir, "second", &url);
+    first.initialize().unwrap();
+    second.initialize().unwrap();
+
+    write_file(first.repo_path(), "doc.json", r#"{"a": 1}"#);
+    first.commit("first").unwrap();
+    first.push().unwrap();
+    write_file(second.repo_path(), "doc.json", r#"{"a": 2}"#);
+    second.commit("second").unwrap();
+
+    match second.sync(&sync_policy(3)) {
+        Err(RepoStoreError::SyncFailed(1, e)) => match *e {
+            RepoStoreError::Conflict(conflicts) => assert_eq!(conflicts[0].key(), "a"),
+            other => panic!("unexpected sync error {:?}", other),
+        },
+        other => panic!("unexpected sync result {:?}", other),
+    }
+    let log = git(second.repo_path(), &["log", "-1", "--format=%s"]);
+    assert_eq!(log.trim(), "second");
+}
+
+#[test]
+fn sync_policy_backoff() {
+    let policy = SyncPolicy::new(5, Duration::from_millis(100), Duration::from_millis(300));
+    assert_eq!(policy.delay(1), Duration::from_millis(100));
+    assert_eq!(policy.delay(2), Duration::from_millis(200));
+    assert_eq!(policy.delay(3), Duration::from_millis(300));
+    assert_eq!(policy.delay(40), Duration::from_millis(300));
+    assert_eq!(SyncPolicy::new(0, Duration::ZERO, Duration::ZERO).attempts(), 1);
+}
+
+/// Lists and removes a directory given with a trailing slash, next to a file sharing its name as a prefix
+fn directory_paths<S: RepoStore<Map<String, Value>>>(store: &S) {
+    store.write_document("users/john.json", &mut doc(json!({"name": "John"}))).unwrap();
+    store.write_document("users/admins/jane.json", &mut doc(json!({"name": "Jane"}))).unwrap();
+    store.write_document("users.json", &mut doc(json!({"count": 2}))).unwrap();
+    assert_eq!(store.documents("users/").unwrap(), ["john.json"]);
+    assert_eq!(store.documents("users").unwrap(), ["john.json"]);
+
+    store.remove_dir("users/").unwrap();
+    assert_eq!(store.revision("users/john.json").unwrap(), None);
+    assert_eq!(store.revision("users/admins/jane.json").unwrap(), None);
+    assert!(store.revision("users.json").unwrap().is_some());
+}
+
+#[test]
+fn stores_filter_directories_alike() {
+    let dir = TestDir::new("directory-paths");
+    let url = bare_repo(&dir, &[]);
+    let store = git_store(&dir, "cache", &url);
+    store.initialize().unwrap();
+    directory_paths(&store);
+
+    let memory = InMemoryStore::new("store", GitCommit::default());
+    memory.initialize().unwrap();
+    directory_paths(&memory);
+
+    let bare_dir = TestDir::new("directory-paths-bare");
+    bare_repo(&bare_dir, &[]);
+    let bare = BareGitStore::new(bare_dir.join("origin.git").to_str().unwrap(), "main", GitCommit::new("server", "server@example.com"));
+    bare.initialize().unwrap();
+    directory_paths(&bare);
+}
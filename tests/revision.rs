@@ -0,0 +1,181 @@
+mod common;
+
+use common::{bare_repo, doc, git, git_store, TestDir};
+use gitobi::collection::Collection;
+use gitobi::query::{QryClause, QueryableDocument};
+use gitobi::bare_store::BareGitStore;
+use gitobi::repo_store::{GitCommit, RepoStore, RepoStoreError};
+use serde_json::json;
+use std::thread;
+
+#[test]
+fn revision_on_read_and_write() {
+    let dir = TestDir::new("revision-read");
+    let url = bare_repo(&dir, &[("users/john.json", r#"{"name": "John"}"#)]);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    let mut doc = store.document("users/john.json").unwrap();
+    let blob = git(store.repo_path(), &["rev-parse", "HEAD:users/john.json"]);
+    assert_eq!(doc.revision(), Some(blob.trim()));
+    assert_eq!(store.document_at("users/john.json", &"HEAD".into()).unwrap().revision(), Some(blob.trim()));
+
+    doc.update("name", "Johnny".into(), None::<QryClause>).unwrap();
+    store.write_document("users/john.json", &mut doc).unwrap();
+    assert_ne!(doc.revision(), Some(blob.trim()));
+    assert_eq!(doc.revision(), store.revision("users/john.json").unwrap().as_deref());
+    assert_eq!(store.revision("users/missing.json").unwrap(), None);
+}
+
+#[test]
+fn revision_compare_and_swap() {
+    let dir = TestDir::new("revision-cas");
+    let url = bare_repo(&dir, &[("users/john.json", r#"{"name": "John", "age": 43}"#)]);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    let mut worker_a = store.document("users/john.json").unwrap();
+    let mut worker_b = store.document("users/john.json").unwrap();
+    let read_revision = worker_a.revision().unwrap().to_string();
+
+    worker_a.update("age", 44.into(), None::<QryClause>).unwrap();
+    store.write_document_if("users/john.json", &mut worker_a, Some(read_revision.as_str())).unwrap();
+
+    worker_b.update("name", "Jon".into(), None::<QryClause>).unwrap();
+    let stale = worker_b.revision().unwrap().to_string();
+    match store.write_document_if("users/john.json", &mut worker_b, Some(stale.as_str())) {
+        Err(RepoStoreError::RevisionConflict(path)) => assert_eq!(path, "users/john.json"),
+        other => panic!("unexpected write result {:?}", other),
+    }
+    assert!(matches!(store.remove_document_if("users/john.json", stale.as_str()), Err(RepoStoreError::RevisionConflict(_))));
+
+    // re-read and retry
+    let mut worker_b = store.document("users/john.json").unwrap();
+    worker_b.update("name", "Jon".into(), None::<QryClause>).unwrap();
+    let current = worker_b.revision().unwrap().to_string();
+    store.write_document_if("users/john.json", &mut worker_b, Some(current.as_str())).unwrap();
+    let john = store.document("users/john.json").unwrap();
+    assert_eq!(serde_json::Value::Object(john.content().clone()), json!({"name": "Jon", "age": 44}));
+
+    store.remove_document_if("users/john.json", john.revision().unwrap()).unwrap();
+    assert!(store.document("users/john.json").is_err());
+}
+
+#[test]
+fn revision_create_only() {
+    let dir = TestDir::new("revision-create");
+    let url = bare_repo(&dir, &[]);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    let users = Collection::create(&store, "users").unwrap();
+    let mut first = doc(json!({"name": "John"}));
+    users.put_if("john", &mut first, None).unwrap();
+    assert!(first.revision().is_some());
+
+    let mut second = doc(json!({"name": "Other John"}));
+    assert!(matches!(users.put_if("john", &mut second, None), Err(RepoStoreError::RevisionConflict(_))));
+    assert_eq!(users.get("john").unwrap().content().get("name").unwrap(), "John");
+}
+
+#[test]
+fn revision_concurrent_increments() {
+    let dir = TestDir::new("revision-threads");
+    let url = bare_repo(&dir, &[("counter.json", r#"{"value": 0}"#)]);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    let workers: Vec<_> = (0..4).map(|_| {
+        let store = store.clone();
+        thread::spawn(move || {
+            for _ in 0..5 {
+                loop {
+                    let mut doc = store.document("counter.json").unwrap();
+                    let value = doc.content().get("value").unwrap().as_i64().unwrap();
+                    let revision = doc.revision().map(String::from);
+                    doc.update("value", (value + 1).into(), None::<QryClause>).unwrap();
+                    match store.write_document_if("counter.json", &mut doc, revision.as_deref()) {
+                        Ok(_) => break,
+                        Err(RepoStoreError::RevisionConflict(_)) => continue,
+                        Err(e) => panic!("{}", e),
+                    }
+                }
+            }
+        })
+    }).collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    let doc = store.document("counter.json").unwrap();
+    assert_eq!(doc.content().get("value").unwrap().as_i64().unwrap(), 20);
+}
+
+#[test]
+fn revision_read_while_rewritten() {
+    let dir = TestDir::new("revision-rewritten");
+    let url = bare_repo(&dir, &[("counter.json", r#"{"value": 0}"#)]);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    // reads complete while the document never stops changing
+    let writer = {
+        let store = store.clone();
+        thread::spawn(move || {
+            for value in 1..=200 {
+                store.write_document("counter.json", &mut doc(json!({"value": value}))).unwrap();
+            }
+        })
+    };
+    while !writer.is_finished() {
+        let doc = store.document("counter.json").unwrap();
+        assert!(doc.content().get("value").unwrap().is_i64());
+        assert!(doc.revision().is_some_and(|r| r.len() == 40));
+    }
+    writer.join().unwrap();
+    let blob = git(store.repo_path(), &["hash-object", "counter.json"]);
+    assert_eq!(store.document("counter.json").unwrap().revision(), Some(blob.trim()));
+}
+
+#[test]
+fn revision_of_converted_document() {
+    let dir = TestDir::new("revision-crlf");
+    let url = bare_repo(&dir, &[(".gitattributes", "*.json text eol=crlf\n"), ("users/john.json", "{\"name\": \"John\"}\n")]);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+
+    // the checked out document ends its lines with CRLF, the blob stored with LF
+    let content = std::fs::read(store.repo_path().join("users/john.json")).unwrap();
+    assert!(content.ends_with(b"\r\n"));
+    let blob = git(store.repo_path(), &["rev-parse", "HEAD:users/john.json"]);
+    let john = store.document("users/john.json").unwrap();
+    assert_eq!(john.revision(), Some(blob.trim()));
+    assert_eq!(store.revision("users/john.json").unwrap().as_deref(), Some(blob.trim()));
+    store.remove_document_if("users/john.json", blob.trim()).unwrap();
+}
+
+#[test]
+fn revision_in_sha256_repository() {
+    let dir = TestDir::new("revision-sha256");
+    let origin = dir.join("origin.git");
+    std::fs::create_dir_all(&origin).unwrap();
+    git(&origin, &["init", "--bare", "--object-format=sha256", "--initial-branch=main"]);
+    let store = BareGitStore::new(origin.to_str().unwrap(), "main", GitCommit::new("store", "store@example.com"));
+    store.initialize().unwrap();
+
+    let mut john = doc(json!({"name": "John"}));
+    store.write_document("users/john.json", &mut john).unwrap();
+    store.commit("add john").unwrap();
+    let blob = git(&origin, &["rev-parse", "main:users/john.json"]);
+    assert_eq!(blob.trim().len(), 64);
+    assert_eq!(john.revision(), Some(blob.trim()));
+    assert_eq!(store.document("users/john.json").unwrap().revision(), Some(blob.trim()));
+    store.write_document_if("users/john.json", &mut doc(json!({"name": "Johnny"})), Some(blob.trim())).unwrap();
+    store.write_document("users/jane.json", &mut doc(json!({"name": "Jane"}))).unwrap();
+    store.commit("rename john").unwrap();
+
+    store.remove_document("users/john.json").unwrap();
+    store.commit("remove john").unwrap();
+    assert!(store.document("users/john.json").is_err());
+    assert_eq!(git(&origin, &["ls-tree", "-r", "--name-only", "main"]).trim(), "users/jane.json");
+}
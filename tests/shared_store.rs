@@ -0,0 +1,83 @@
+mod common;
+
+use common::{bare_repo, doc, git_store, TestDir};
+use gitobi::bare_store::BareGitStore;
+use gitobi::memory_store::InMemoryStore;
+use gitobi::query::{QryClause, QueryClause};
+use gitobi::repo_store::{GitCommit, GitStore, RepoStore};
+use gitobi::shared_store::SharedStore;
+use serde_json::json;
+use std::thread;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn shared_handles_are_send_and_sync() {
+    assert_send_sync::<SharedStore<GitStore>>();
+    assert_send_sync::<SharedStore<BareGitStore>>();
+    assert_send_sync::<SharedStore<InMemoryStore>>();
+}
+
+#[test]
+fn readers_see_committed_documents() {
+    let dir = TestDir::new("shared-committed");
+    let url = bare_repo(&dir, &[("users/john.json", r#"{"name": "John", "age": 43}"#)]);
+    let shared = SharedStore::open(git_store(&dir, "cache", &url)).unwrap();
+    let view = shared.view();
+
+    shared.write(|store| store.write_document("users/jane.json", &mut doc(json!({"name": "Jane", "age": 38})))).unwrap();
+    assert!(shared.document("users/jane.json").is_err());
+    assert_eq!(shared.documents("users").unwrap(), ["john.json"]);
+    shared.commit("add jane").unwrap();
+
+    let qry: QryClause = QueryClause::less_than("age", 40);
+    assert_eq!(shared.find("users", &qry).unwrap(), ["jane.json"]);
+    assert_eq!(shared.documents("users").unwrap(), ["jane.json", "john.json"]);
+    // older views keep reading the commit they were taken at
+    assert_eq!(view.documents("users").unwrap(), ["john.json"]);
+    assert_ne!(view.commit(), shared.head().as_deref());
+
+    let empty = SharedStore::open(InMemoryStore::new("store", GitCommit::default())).unwrap();
+    assert!(empty.head().is_none());
+    assert!(empty.documents("").unwrap().is_empty());
+}
+
+#[test]
+fn concurrent_readers_and_writers() {
+    let dir = TestDir::new("shared-concurrent");
+    let url = bare_repo(&dir, &[]);
+    let shared = SharedStore::open(git_store(&dir, "cache", &url)).unwrap();
+    shared.write(|store| {
+        store.write_document("counter.json", &mut doc(json!({"count": 0})))?;
+        store.commit("start")
+    }).unwrap();
+
+    let writers: Vec<_> = (0..2).map(|writer| {
+        let shared = shared.clone();
+        thread::spawn(move || {
+            for i in 0..5 {
+                shared.write(|store| {
+                    let count = store.document("counter.json")?.content()["count"].as_u64().unwrap();
+                    store.write_document(format!("log/{}-{}.json", writer, i).as_str(), &mut doc(json!({"count": count + 1})))?;
+                    store.write_document("counter.json", &mut doc(json!({"count": count + 1})))?;
+                    store.commit("count")
+                }).unwrap();
+            }
+        })
+    }).collect();
+    let readers: Vec<_> = (0..4).map(|_| {
+        let shared = shared.clone();
+        thread::spawn(move || {
+            for _ in 0..10 {
+                // the counter and the log of a view always agree
+                let view = shared.view();
+                let count = view.document("counter.json").unwrap().content()["count"].as_u64().unwrap();
+                assert_eq!(view.documents("log").unwrap().len() as u64, count);
+            }
+        })
+    }).collect();
+    for handle in writers.into_iter().chain(readers) {
+        handle.join().unwrap();
+    }
+    assert_eq!(shared.document("counter.json").unwrap().content()["count"], 10);
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use common::{bare_repo, doc, git, write_file, TestDir};
+use gitobi::bare_store::BareGitStore;
+use gitobi::history::Revision;
+use gitobi::repo_store::{GitAuth, GitCommit, GitStore, RepoStore, RepoStoreError};
+use gitobi::signing::{CommitSigning, SignatureStatus};
+use serde_json::json;
+use std::process::Command;
+
+/// Generates an SSH signing key under `dir`, returning its path
+fn ssh_key(dir: &TestDir, name: &str) -> String {
+    let path = dir.join(name);
+    let output = Command::new("ssh-keygen")
+        .args(["-q", "-t", "ed25519", "-N", "", "-C", name, "-f", path.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "ssh-keygen failed: {}", String::from_utf8_lossy(&output.stderr));
+    path.to_str().unwrap().to_string()
+}
+
+/// Writes an allowed signers file trusting `key` for `email`
+fn allowed_signers(dir: &TestDir, email: &str, key: &str) -> String {
+    let public_key = std::fs::read_to_string(format!("{}.pub", key)).unwrap();
+    write_file(dir.path(), "allowed_signers", format!("{} {}", email, public_key).as_str());
+    dir.join("allowed_signers").to_str().unwrap().to_string()
+}
+
+fn signed_store(dir: &TestDir, name: &str, url: &str, key: &str, allowed: &str) -> GitStore {
+    let commit = GitCommit::new(name, format!("{}@example.com", name).as_str()).signing(CommitSigning::ssh(key));
+    GitStore::new(name, url, dir.join(name).to_str().unwrap(), Some("main"), GitAuth::default(), commit)
+        .allowed_signers(allowed)
+}
+
+#[test]
+fn git_store_signed_commits() {
+    let dir = TestDir::new("git-store-signing");
+    let url = bare_repo(&dir, &[("users/john.json", r#"{"name": "John"}"#)]);
+    let key = ssh_key(&dir, "writer-key");
+    let allowed = allowed_signers(&dir, "writer@example.com", &key);
+    let writer = signed_store(&dir, "writer", &url, &key, &allowed);
+    writer.initialize().unwrap();
+    writer.write_document("users/jane.json", &mut doc(json!({"name": "Jane"}))).unwrap();
+    writer.commit("add jane").unwrap();
+    writer.push().unwrap();
+
+    let revisions = writer.revisions("users/jane.json").unwrap();
+    let signature = revisions[0].signature().unwrap();
+    assert_eq!(signature.status(), SignatureStatus::Good);
+    assert_eq!(signature.signer(), "writer@example.com");
+    assert!(signature.key().starts_with("SHA256:"));
+    assert!(writer.revisions("users/john.json").unwrap()[0].signature().is_none());
+
+    // a key missing from the allowed signers makes a valid but untrusted signature
+    let rogue_key = ssh_key(&dir, "rogue-key");
+    let rogue = signed_store(&dir, "rogue", &url, &rogue_key, &allowed);
+    rogue.initialize().unwrap();
+    rogue.write_document("users/john.json", &mut doc(json!({"name": "Johnny"}))).unwrap();
+    rogue.commit("rename john").unwrap();
+    rogue.push().unwrap();
+
+    let reader = GitStore::new("reader", &url, dir.join("reader").to_str().unwrap(), Some("main"), GitAuth::default(), GitCommit::new("reader", "reader@example.com"))
+        .allowed_signers(&allowed)
+        .require_signatures(true);
+    reader.initialize().unwrap();
+    assert_eq!(reader.revisions("users/john.json").unwrap()[0].signature().unwrap().status(), SignatureStatus::Untrusted);
+    assert_eq!(reader.document("users/jane.json").unwrap().content().get("name").unwrap(), "Jane");
+    assert!(matches!(reader.document("users/john.json"), Err(RepoStoreError::UntrustedDocument(_))));
+    assert!(matches!(reader.document_at("users/john.json", &Revision::rev("HEAD~1")), Err(RepoStoreError::UntrustedDocument(_))));
+    // uncommitted changes are not covered by any signature
+    write_file(reader.repo_path(), "users/jane.json", r#"{"name": "Janet"}"#);
+    assert!(matches!(reader.document("users/jane.json"), Err(RepoStoreError::UntrustedDocument(_))));
+}
+
+#[test]
+fn bare_store_signed_commits() {
+    let dir = TestDir::new("bare-store-signing");
+    bare_repo(&dir, &[("users/john.json", r#"{"name": "John"}"#)]);
+    let key = ssh_key(&dir, "server-key");
+    let allowed = allowed_signers(&dir, "server@example.com", &key);
+    let store = BareGitStore::new(dir.join("origin.git").to_str().unwrap(), "main", GitCommit::new("server", "server@example.com").signing(CommitSigning::ssh(&key)))
+        .allowed_signers(&allowed)
+        .require_signatures(true);
+    store.initialize().unwrap();
+
+    assert!(matches!(store.document("users/john.json"), Err(RepoStoreError::UntrustedDocument(_))));
+    store.write_document("users/jane.json", &mut doc(json!({"name": "Jane"}))).unwrap();
+    assert!(matches!(store.document("users/jane.json"), Err(RepoStoreError::UntrustedDocument(_))));
+    store.commit("add jane").unwrap();
+    assert_eq!(store.document("users/jane.json").unwrap().content().get("name").unwrap(), "Jane");
+    assert!(store.revisions("users/jane.json").unwrap()[0].is_trusted());
+    // the repository configuration is left untouched
+    assert!(!git(store.repo_path(), &["config", "--list", "--local"]).contains("gpgsign"));
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use common::{bare_repo, doc, git, git_store, TestDir};
+use gitobi::bare_store::BareGitStore;
+use gitobi::diff::{diff, diff_snapshots};
+use gitobi::history::{PathChange, Revision};
+use gitobi::memory_store::InMemoryStore;
+use gitobi::repo_store::{GitCommit, RepoStore, RepoStoreError};
+use serde_json::{json, Map, Value};
+
+/// Snapshots the store before and after a batch job, diffs and restores the first snapshot
+fn snapshot_batch_job<S: RepoStore<Map<String, Value>>>(store: &S) {
+    store.write_document("users/john.json", &mut doc(json!({"name": "John", "age": 43, "address": {"city": "Paris"}}))).unwrap();
+    store.write_document("users/jane.json", &mut doc(json!({"name": "Jane"}))).unwrap();
+    store.commit("seed users").unwrap();
+    store.create_snapshot("before-job", "before the batch job").unwrap();
+    assert!(matches!(store.create_snapshot("before-job", "again"), Err(RepoStoreError::Snapshot(_))));
+
+    store.write_document("users/john.json", &mut doc(json!({"name": "John", "age": 44, "address": {"city": "Lyon"}}))).unwrap();
+    store.remove_document("users/jane.json").unwrap();
+    store.write_document("users/eomer.json", &mut doc(json!({"name": "Eomer"}))).unwrap();
+    store.commit("batch job").unwrap();
+    store.create_snapshot("after-job", "after the batch job").unwrap();
+
+    let snapshots = store.snapshots().unwrap();
+    let mut names: Vec<&str> = snapshots.iter().map(|s| s.name()).collect();
+    names.sort();
+    assert_eq!(names, vec!["after-job", "before-job"]);
+    let before = snapshots.iter().find(|s| s.name() == "before-job").unwrap();
+    assert_eq!(before.message(), "before the batch job");
+    assert_eq!(before.tagger(), "store");
+
+    assert_eq!(store.changed_paths(Some(&Revision::rev("before-job")), &Revision::rev("after-job")).unwrap(), vec![
+        PathChange::Added(String::from("users/eomer.json")),
+        PathChange::Removed(String::from("users/jane.json")),
+        PathChange::Modified(String::from("users/john.json")),
+    ]);
+    assert!(store.changed_paths(None, &Revision::rev("before-job")).unwrap().contains(&PathChange::Added(String::from("users/jane.json"))));
+
+    let changes = diff_snapshots(store, "before-job", "after-job").unwrap();
+    assert_eq!(changes.added(), ["users/eomer.json"]);
+    assert_eq!(changes.removed(), ["users/jane.json"]);
+    assert_eq!(changes.modified().len(), 1);
+    let john = &changes.modified()[0];
+    assert_eq!(john.path(), "users/john.json");
+    let keys: Vec<(&str, Option<&Value>, Option<&Value>)> = john.keys().iter().map(|k| (k.key(), k.before(), k.after())).collect();
+    assert_eq!(keys, vec![
+        ("address.city", Some(&json!("Paris")), Some(&json!("Lyon"))),
+        ("age", Some(&json!(43)), Some(&json!(44))),
+    ]);
+
+    // uncommitted changes are discarded, the history since the snapshot kept
+    store.write_document("users/draft.json", &mut doc(json!({}))).unwrap();
+    store.restore_snapshot("before-job", "restore before-job").unwrap();
+    assert_eq!(store.document("users/john.json").unwrap().content().get("age").unwrap(), 43);
+    assert!(store.document("users/eomer.json").is_err());
+    assert!(store.document("users/draft.json").is_err());
+    assert_eq!(store.revisions("users/john.json").unwrap().len(), 3);
+    assert!(diff(store, &Revision::rev("before-job"), &Revision::rev("HEAD")).unwrap().is_empty());
+    assert!(matches!(store.restore_snapshot("missing", "restore"), Err(RepoStoreError::Snapshot(_))));
+}
+
+#[test]
+fn git_store_snapshots() {
+    let dir = TestDir::new("git-store-snapshots");
+    let url = bare_repo(&dir, &[]);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+    snapshot_batch_job(&store);
+    assert_eq!(git(store.repo_path(), &["cat-file", "-t", "before-job"]).trim(), "tag");
+    assert!(git(store.repo_path(), &["status", "--porcelain"]).is_empty());
+    assert_eq!(store.paths_at(&Revision::rev("after-job")).unwrap(), vec!["README.md", "users/eomer.json", "users/john.json"]);
+}
+
+#[test]
+fn memory_store_snapshots() {
+    let store = InMemoryStore::new("store", GitCommit::new("store", "store@example.com"));
+    store.initialize().unwrap();
+    assert!(matches!(store.create_snapshot("empty", "nothing yet"), Err(RepoStoreError::Snapshot(_))));
+    snapshot_batch_job(&store);
+}
+
+#[test]
+fn bare_store_snapshots() {
+    let dir = TestDir::new("bare-store-snapshots");
+    bare_repo(&dir, &[]);
+    let store = BareGitStore::new(dir.join("origin.git").to_str().unwrap(), "main", GitCommit::new("store", "store@example.com"));
+    store.initialize().unwrap();
+    snapshot_batch_job(&store);
+    assert_eq!(git(store.repo_path(), &["cat-file", "-t", "after-job"]).trim(), "tag");
+}
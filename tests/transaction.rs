@@ -0,0 +1,137 @@
+mod common;
+
+use common::{bare_repo, doc, git, git_store, write_file, TestDir};
+use gitobi::audit::CommitOptions;
+use gitobi::collection::Collection;
+use gitobi::memory_store::InMemoryStore;
+use gitobi::repo_store::{GitCommit, RepoStore};
+use gitobi::transaction::Transaction;
+use serde_json::json;
+
+const SEED: &[(&str, &str)] = &[
+    ("users/john.json", r#"{"name": "John", "team": "red"}"#),
+    ("teams/red.json", r#"{"members": ["john"]}"#),
+    ("teams/blue.json", r#"{"members": []}"#),
+];
+
+#[test]
+fn transaction_commits_once() {
+    let dir = TestDir::new("transaction-commit");
+    let url = bare_repo(&dir, SEED);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+    write_file(store.repo_path(), "scratch.txt", "not part of the transaction");
+    let head = git(store.repo_path(), &["rev-parse", "HEAD"]);
+
+    let users = Collection::open(&store, "users").unwrap();
+    let mut tx = Transaction::new(&store);
+    tx.put(users.document_path("john").unwrap().as_str(), doc(json!({"name": "John", "team": "blue"})));
+    tx.put("teams/red.json", doc(json!({"members": []})));
+    tx.put("teams/blue.json", doc(json!({"members": ["john"]})));
+    tx.put("teams/green.json", doc(json!({"members": []})));
+    tx.remove("teams/green.json");
+    tx.put("audit/1.json", doc(json!({"moved": "john"})));
+    assert_eq!(tx.get("teams/blue.json").unwrap().content().get("members").unwrap(), &json!(["john"]));
+    assert!(tx.get("teams/green.json").is_err());
+    tx.remove("teams/green.json");
+    tx.put("teams/green.json", doc(json!({"members": []})));
+    tx.commit("move john to blue").unwrap();
+
+    let log = git(store.repo_path(), &["log", "--format=%s", format!("{}..HEAD", head.trim()).as_str()]);
+    assert_eq!(log.trim(), "move john to blue");
+    let files = git(store.repo_path(), &["show", "--name-only", "--format=", "HEAD"]);
+    assert_eq!(files.trim().lines().collect::<Vec<_>>(), vec!["audit/1.json", "teams/blue.json", "teams/green.json", "teams/red.json", "users/john.json"]);
+    assert_eq!(store.document("users/john.json").unwrap().content().get("team").unwrap(), "blue");
+    // unrelated changes stay out of the commit
+    assert_eq!(git(store.repo_path(), &["status", "--porcelain"]).trim(), "?? scratch.txt");
+}
+
+#[test]
+fn transaction_rolls_back_on_error() {
+    let dir = TestDir::new("transaction-rollback");
+    let url = bare_repo(&dir, SEED);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+    let head = git(store.repo_path(), &["rev-parse", "HEAD"]);
+
+    let mut tx = Transaction::new(&store);
+    tx.put("audit/1.json", doc(json!({"moved": "john"})));
+    tx.put("teams/blue.json", doc(json!({"members": ["john"]})));
+    tx.remove("teams/missing.json");
+    tx.put("users/john.json", doc(json!({"name": "John", "team": "blue"})));
+    assert!(tx.commit("move john to blue").is_err());
+
+    assert_eq!(git(store.repo_path(), &["rev-parse", "HEAD"]), head);
+    assert!(git(store.repo_path(), &["status", "--porcelain", "--untracked-files=all"]).is_empty());
+    assert_eq!(store.document("teams/blue.json").unwrap().content().get("members").unwrap(), &json!([]));
+}
+
+#[test]
+fn transaction_rollback_and_empty_commit() {
+    let dir = TestDir::new("transaction-discard");
+    let url = bare_repo(&dir, SEED);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+    let head = git(store.repo_path(), &["rev-parse", "HEAD"]);
+
+    let mut tx = Transaction::new(&store);
+    tx.put("teams/blue.json", doc(json!({"members": ["john"]})));
+    tx.rollback();
+    Transaction::new(&store).commit("nothing").unwrap();
+
+    assert_eq!(git(store.repo_path(), &["rev-parse", "HEAD"]), head);
+    assert!(git(store.repo_path(), &["status", "--porcelain"]).is_empty());
+}
+
+#[test]
+fn transaction_without_changes_commits_nothing() {
+    let dir = TestDir::new("transaction-unchanged");
+    let url = bare_repo(&dir, SEED);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+    store.write_document("a.json", &mut doc(json!({"a": 1}))).unwrap();
+    store.commit("add a").unwrap();
+    let head = git(store.repo_path(), &["rev-parse", "HEAD"]);
+
+    let mut tx = Transaction::new(&store);
+    tx.put("a.json", doc(json!({"a": 1})));
+    tx.commit("unchanged").unwrap();
+    assert_eq!(git(store.repo_path(), &["rev-parse", "HEAD"]), head);
+    assert!(git(store.repo_path(), &["status", "--porcelain", "--untracked-files=all"]).is_empty());
+
+    let memory = InMemoryStore::new("memory", GitCommit::new("memory", "memory@example.com"));
+    memory.initialize().unwrap();
+    memory.write_document("a.json", &mut doc(json!({"a": 1}))).unwrap();
+    memory.commit("add a").unwrap();
+    let head = memory.head().unwrap();
+    let mut tx = Transaction::new(&memory);
+    tx.put("a.json", doc(json!({"a": 1})));
+    tx.commit("unchanged").unwrap();
+    assert_eq!(memory.head().unwrap(), head);
+}
+
+#[test]
+fn transaction_rollback_keeps_uncommitted_edits() {
+    let dir = TestDir::new("transaction-uncommitted");
+    let url = bare_repo(&dir, SEED);
+    let store = git_store(&dir, "store", &url);
+    store.initialize().unwrap();
+    store.write_document("users/john.json", &mut doc(json!({"name": "John", "team": "blue"}))).unwrap();
+    store.write_document("users/jane.json", &mut doc(json!({"name": "Jane"}))).unwrap();
+    store.remove_document("teams/red.json").unwrap();
+    let status = git(store.repo_path(), &["status", "--porcelain", "--untracked-files=all"]);
+
+    let mut tx = Transaction::new(&store);
+    tx.put("users/john.json", doc(json!({"name": "John", "team": "green"})));
+    tx.put("users/jane.json", doc(json!({"name": "Jane", "team": "green"})));
+    tx.put("teams/red.json", doc(json!({"members": []})));
+    tx.put("teams/green.json", doc(json!({"members": ["jane", "john"]})));
+    // applied then failing to commit, a trailer value spanning lines
+    assert!(tx.commit_with("create green", &CommitOptions::new().actor("ops\nbot")).is_err());
+
+    assert_eq!(git(store.repo_path(), &["status", "--porcelain", "--untracked-files=all"]), status);
+    assert_eq!(store.document("users/john.json").unwrap().content().get("team").unwrap(), "blue");
+    assert!(store.document("users/jane.json").unwrap().content().get("team").is_none());
+    assert!(!store.repo_path().join("teams/red.json").exists());
+    assert!(!store.repo_path().join("teams/green.json").exists());
+}
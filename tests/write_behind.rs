@@ -0,0 +1,121 @@
+mod common;
+
+use common::{bare_repo, doc, git, git_store, TestDir};
+use gitobi::audit::{CommitOptions, Operation};
+use gitobi::history::Revision;
+use gitobi::memory_store::InMemoryStore;
+use gitobi::repo_store::{GitCommit, RepoStore, RepoStoreError};
+use gitobi::write_behind::{CommitPolicy, WriteBehindStore};
+use serde_json::{json, Map, Value};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn memory_store() -> InMemoryStore {
+    let store = InMemoryStore::new("store", GitCommit::default());
+    store.initialize().unwrap();
+    store
+}
+
+fn commit_count<S: RepoStore<Map<String, Value>>>(store: &S) -> usize {
+    store.commits(None, &Revision::rev("HEAD")).unwrap().len()
+}
+
+#[test]
+fn commit_after_writes() {
+    let store = WriteBehindStore::new(memory_store(), CommitPolicy::Writes(3))
+        .message("batch")
+        .options(CommitOptions::new().actor("importer"));
+    store.write_document("users/john.json", &doc(json!({"name": "John"}))).unwrap();
+    store.write_document("users/john.json", &doc(json!({"name": "John", "age": 43}))).unwrap();
+    assert_eq!(store.pending(), ["users/john.json"]);
+    assert_eq!(store.document("users/john.json").unwrap().content().get("age").unwrap(), 43);
+    assert!(store.store().document("users/john.json").is_err());
+    assert_eq!(commit_count(store.store()), 0);
+
+    store.write_document("users/jane.json", &doc(json!({"name": "Jane"}))).unwrap();
+    assert!(store.pending().is_empty());
+    let commits = store.store().commits(None, &Revision::rev("HEAD")).unwrap();
+    assert_eq!(commits.len(), 1);
+    assert_eq!(commits[0].message(), "batch");
+    let trailers = commits[0].trailers().unwrap();
+    assert_eq!((trailers.operation(), trailers.actor()), (&Operation::Flush, Some("importer")));
+    assert_eq!(trailers.documents(), ["users/jane.json", "users/john.json"]);
+    assert_eq!(store.store().document("users/john.json").unwrap().content().get("age").unwrap(), 43);
+}
+
+#[test]
+fn commit_each_write_and_manually() {
+    let each = WriteBehindStore::new(memory_store(), CommitPolicy::EachWrite);
+    each.write_document("a.json", &doc(json!({"v": 1}))).unwrap();
+    each.write_document("a.json", &doc(json!({"v": 2}))).unwrap();
+    each.remove_document("a.json").unwrap();
+    assert_eq!(commit_count(each.store()), 3);
+
+    let manual = WriteBehindStore::new(memory_store(), CommitPolicy::Manual);
+    manual.write_document("a.json", &doc(json!({"v": 1}))).unwrap();
+    manual.flush().unwrap();
+    // edits undone before the flush make no commit
+    manual.write_document("a.json", &doc(json!({"v": 2}))).unwrap();
+    manual.write_document("a.json", &doc(json!({"v": 1}))).unwrap();
+    manual.write_document("b.json", &doc(json!({"v": 1}))).unwrap();
+    manual.remove_document("b.json").unwrap();
+    manual.flush().unwrap();
+    assert!(manual.pending().is_empty());
+    assert_eq!(commit_count(manual.store()), 1);
+    manual.flush().unwrap();
+}
+
+#[test]
+fn commit_after_interval() {
+    let store = WriteBehindStore::new(memory_store(), CommitPolicy::Interval(Duration::from_millis(50)));
+    store.write_document("a.json", &doc(json!({"v": 1}))).unwrap();
+    store.write_document("b.json", &doc(json!({"v": 1}))).unwrap();
+    let started = Instant::now();
+    while !store.pending().is_empty() && started.elapsed() < Duration::from_secs(5) {
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(commit_count(store.store()), 1);
+    store.write_document("a.json", &doc(json!({"v": 2}))).unwrap();
+    assert_eq!(store.pending(), ["a.json"]);
+    store.close().unwrap();
+}
+
+#[test]
+fn flush_on_drop() {
+    let dir = TestDir::new("write-behind-drop");
+    let url = bare_repo(&dir, &[("users/john.json", r#"{"name": "John"}"#)]);
+    let store = git_store(&dir, "cache", &url);
+    store.initialize().unwrap();
+    let repo = store.repo_path().to_path_buf();
+    {
+        let write_behind = WriteBehindStore::new(store, CommitPolicy::Manual);
+        write_behind.write_document("users/john.json", &doc(json!({"name": "John", "age": 43}))).unwrap();
+        write_behind.remove_document("users/john.json").unwrap();
+        write_behind.write_document("users/jane.json", &doc(json!({"name": "Jane"}))).unwrap();
+    }
+    assert_eq!(git(&repo, &["log", "-1", "--format=%s"]), "Write-behind flush\n");
+    assert_eq!(git(&repo, &["ls-files"]), "README.md\nusers/jane.json\n");
+    assert!(git(&repo, &["status", "--porcelain"]).is_empty());
+}
+
+#[test]
+fn background_flush_error_reported() {
+    // a trailer value spanning lines fails every commit
+    let store = WriteBehindStore::new(memory_store(), CommitPolicy::Interval(Duration::from_millis(20)))
+        .options(CommitOptions::new().actor("bot\nops"));
+    store.write_document("a.json", &doc(json!({"v": 1}))).unwrap();
+    let started = Instant::now();
+    let error = loop {
+        thread::sleep(Duration::from_millis(10));
+        match store.write_document("b.json", &doc(json!({"v": 1}))) {
+            Err(e) => break e,
+            Ok(_) => assert!(started.elapsed() < Duration::from_secs(5)),
+        }
+    };
+    assert!(matches!(error, RepoStoreError::Commit(_)));
+    assert_eq!(store.pending(), ["a.json", "b.json"]);
+    assert_eq!(commit_count(store.store()), 0);
+
+    let store = store.options(CommitOptions::new().actor("bot"));
+    store.close().unwrap();
+}